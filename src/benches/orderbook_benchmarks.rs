@@ -1,116 +1,115 @@
-//! High-Performance OrderBook Trading Engine
+//! OrderBook benchmarks
 //!
-//! A production-grade, lock-free order book engine designed for electronic trading systems.
-//! Features sub-microsecond latency, millions of orders per second throughput, and comprehensive
-//! monitoring capabilities.
-//!
-//! # Features
-//!
-//! - **Lock-free Design**: Uses atomic operations and concurrent data structures
-//! - **High Performance**: Sub-microsecond order operations, millions of orders/second
-//! - **Price-Time Priority**: Maintains strict FIFO ordering within price levels
-//! - **Comprehensive Monitoring**: Built-in metrics with Prometheus and InfluxDB support
-//! - **Multiple Order Types**: Market, Limit, Stop, IOC, FOK orders
-//! - **Thread Safe**: Designed for high-concurrency trading environments
-//!
-//! # Quick Start
-//!
-//! ```rust
-//! use orderbook_trading_engine::orderbook::{OrderBook, types::*};
-//!
-//! // Create a new order book
-//! let book = OrderBook::new("AAPL".to_string());
-//!
-//! // Add a limit order
-//! let order = Order::new_limit("AAPL".to_string(), Side::Buy, 15000, 100, None);
-//! let events = book.add_limit_order(order)?;
-//!
-//! // Check best bid/ask
-//! println!("Best bid: {:?}", book.best_bid());
-//! println!("Best ask: {:?}", book.best_ask());
-//!
-//! # Ok::<(), Box<dyn std::error::Error>>(())
-//! ```
-//!
-//! # Architecture
-//!
-//! The order book uses a two-level data structure:
-//!
-//! 1. **Price Levels**: `DashMap<Price, Arc<PriceLevel>>` for lock-free price level access
-//! 2. **Order Queues**: Within each price level, orders maintain time priority using `VecDeque`
-//!
-//! This design optimizes for:
-//! - Fast order insertion/cancellation
-//! - Efficient order matching
-//! - Minimal memory allocations
-//! - Cache-friendly data layout
+//! Run with `cargo bench --bench orderbook_benchmarks`. The `dashmap_*`
+//! cases reproduce the previous unsorted `DashMap` ladder so the cost of the
+//! sorted ladder can be compared at the same depth.
 
-pub mod metrics;
-pub mod orderbook;
-pub mod utils;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dashmap::DashMap;
+use std::sync::Arc;
 
-// Re-export commonly used types
-pub use orderbook::{
-    error::{OrderBookError, OrderBookResult},
-    types::{Order, OrderId, OrderStatus, OrderType, Price, Quantity, Side, Trade},
-    OrderBook,
-};
+use orderbook_trading_engine::orderbook::{PriceLadder, PriceLevel};
+use orderbook_trading_engine::{Order, OrderBook, Price, Side};
 
-pub use metrics::OrderBookMetrics;
+const LEVELS: u64 = 10_000;
+const BASE_PRICE: Price = 10_000;
 
-#[cfg(test)]
-mod integration_tests {
-    use super::*;
-    use std::sync::Arc;
-    use std::thread;
+/// Book with `LEVELS` bid levels below and `LEVELS` ask levels above the mid
+fn deep_book() -> OrderBook {
+    let book = OrderBook::new("BENCH".to_string());
+    for i in 0..LEVELS {
+        let bid = Order::new_limit("BENCH".to_string(), Side::Buy, BASE_PRICE - 1 - i, 100, None);
+        let ask = Order::new_limit("BENCH".to_string(), Side::Sell, BASE_PRICE + 1 + i, 100, None);
+        book.add_limit_order(bid).unwrap();
+        book.add_limit_order(ask).unwrap();
+    }
+    book
+}
 
-    #[test]
-    fn test_basic_trading_workflow() {
-        let book = OrderBook::new("TEST".to_string());
+fn dashmap_ladder() -> DashMap<Price, Arc<PriceLevel>> {
+    let levels = DashMap::new();
+    for i in 0..LEVELS {
+        let price = BASE_PRICE + 1 + i;
+        levels.insert(price, Arc::new(PriceLevel::new(price)));
+    }
+    levels
+}
 
-        // Add liquidity
-        let sell_order = Order::new_limit("TEST".to_string(), Side::Sell, 10000, 100, None);
-        let events = book.add_limit_order(sell_order).unwrap();
-        assert_eq!(events.len(), 1);
+fn sorted_ladder() -> PriceLadder {
+    let ladder = PriceLadder::for_side(Side::Sell);
+    for i in 0..LEVELS {
+        ladder.get_or_insert(BASE_PRICE + 1 + i);
+    }
+    ladder
+}
 
-        // Match with market order
-        let buy_order = Order::new_market("TEST".to_string(), Side::Buy, 50, None);
-        let events = book.add_market_order(buy_order).unwrap();
-        assert_eq!(events.len(), 1);
+fn bench_best_price(c: &mut Criterion) {
+    let mut group = c.benchmark_group("best_ask");
+    let book = deep_book();
+    let dashmap = dashmap_ladder();
 
-        // Verify trade occurred
-        if let orderbook::types::MarketEvent::Trade { trade } = &events[0] {
-            assert_eq!(trade.price, 10000);
-            assert_eq!(trade.quantity, 50);
-        } else {
-            panic!("Expected trade event");
-        }
-    }
+    group.bench_function(BenchmarkId::new("ladder", LEVELS), |b| {
+        b.iter(|| black_box(book.best_ask()))
+    });
+    group.bench_function(BenchmarkId::new("dashmap_scan", LEVELS), |b| {
+        b.iter(|| black_box(dashmap.iter().map(|entry| *entry.key()).min()))
+    });
+    group.finish();
+}
 
-    #[test]
-    fn test_concurrent_trading() {
-        let book = Arc::new(OrderBook::new("TEST".to_string()));
-        let mut handles = vec![];
+fn bench_level_walk(c: &mut Criterion) {
+    // Cost of finding the first level to match against
+    let mut group = c.benchmark_group("first_match_level");
+    let ladder = sorted_ladder();
+    let dashmap = dashmap_ladder();
 
-        // Spawn multiple trading threads
-        for thread_id in 0..4 {
-            let book_clone = Arc::clone(&book);
-            let handle = thread::spawn(move || {
-                for i in 0..100 {
-                    let price = 10000 + (thread_id * 100) + i;
-                    let order = Order::new_limit("TEST".to_string(), Side::Buy, price, 100, None);
-                    book_clone.add_limit_order(order).unwrap();
-                }
-            });
-            handles.push(handle);
-        }
+    group.bench_function(BenchmarkId::new("ladder", LEVELS), |b| {
+        b.iter(|| black_box(ladder.best_level().map(|(price, _)| price)))
+    });
+    group.bench_function(BenchmarkId::new("dashmap_collect_sort", LEVELS), |b| {
+        b.iter(|| {
+            let mut prices: Vec<Price> = dashmap.iter().map(|entry| *entry.key()).collect();
+            prices.sort();
+            black_box(prices.first().copied())
+        })
+    });
+    group.finish();
+}
 
-        // Wait for completion
-        for handle in handles {
-            handle.join().unwrap();
-        }
+fn bench_matching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("match_at_depth");
+    let book = deep_book();
 
-        // Verify all orders were added
-        assert_eq!(book.total_orders(), 400);
-    }
+    // Each iteration takes one lot from the best ask and puts it back, so the
+    // book stays at full depth
+    group.bench_function(BenchmarkId::new("limit_cross", LEVELS), |b| {
+        b.iter(|| {
+            let buy = Order::new_limit("BENCH".to_string(), Side::Buy, BASE_PRICE + 1, 1, None);
+            black_box(book.add_limit_order(buy).unwrap());
+            let ask = Order::new_limit("BENCH".to_string(), Side::Sell, BASE_PRICE + 1, 1, None);
+            black_box(book.add_limit_order(ask).unwrap());
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("market", LEVELS), |b| {
+        b.iter(|| {
+            let buy = Order::new_market("BENCH".to_string(), Side::Buy, 1, None);
+            black_box(book.add_market_order(buy).unwrap());
+            let ask = Order::new_limit("BENCH".to_string(), Side::Sell, BASE_PRICE + 1, 1, None);
+            black_box(book.add_limit_order(ask).unwrap());
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("passive_add_cancel", LEVELS), |b| {
+        b.iter(|| {
+            let bid = Order::new_limit("BENCH".to_string(), Side::Buy, BASE_PRICE, 1, None);
+            let order_id = bid.id;
+            black_box(book.add_limit_order(bid).unwrap());
+            black_box(book.cancel_order(&order_id).unwrap());
+        })
+    });
+    group.finish();
 }
+
+criterion_group!(benches, bench_best_price, bench_level_walk, bench_matching);
+criterion_main!(benches);
@@ -0,0 +1,934 @@
+//! Load Generator
+//!
+//! Drives an in-process order book, or the order gateway of a running
+//! trading server, with a configurable mix of limit, market, cancel and
+//! modify commands priced around a mid price. Prints latency percentiles
+//! and throughput at the end, and can record the generated flow as a replay
+//! file for `Replayer`.
+
+use clap::{Parser, ValueEnum};
+use crossbeam::channel::{Receiver, TryRecvError};
+use hdrhistogram::Histogram;
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+use orderbook_trading_engine::{
+    gateway::protocol::{read_frame, write_frame},
+    gateway::{ClientMessage, OrderKind, ServerMessage},
+    orderbook::replay::{ReplayCommand, Replayer},
+    orderbook::types::*,
+    orderbook::{OrderBookError, OrderMessage},
+    OrderBook,
+};
+
+#[derive(Debug, Parser)]
+#[command(about = "Generate order flow and measure latency and throughput")]
+struct Args {
+    /// Where to send the flow
+    #[arg(long, value_enum, default_value_t = Target::InProcess)]
+    target: Target,
+
+    /// Gateway address for `--target gateway`
+    #[arg(long, default_value = "127.0.0.1:7000")]
+    addr: String,
+
+    #[arg(long, default_value = "TEST")]
+    symbol: String,
+
+    /// Worker threads, each with its own flow (and gateway session)
+    #[arg(long, default_value_t = 4)]
+    threads: usize,
+
+    /// Commands to send across all threads
+    #[arg(long, default_value_t = 100_000)]
+    commands: u64,
+
+    /// Stop after this many seconds even if commands are left
+    #[arg(long)]
+    duration: Option<u64>,
+
+    /// Target rate in commands per second across all threads; 0 sends as
+    /// fast as possible
+    #[arg(long, default_value_t = 0)]
+    rate: u64,
+
+    /// Relative weights of each command kind
+    #[arg(long, default_value = "limit=60,market=10,cancel=20,modify=10")]
+    mix: Mix,
+
+    /// Price the flow is centered on
+    #[arg(long, default_value_t = 10_000)]
+    mid: Price,
+
+    /// Width of the price distribution in ticks: the half-range of the
+    /// uniform distribution, or the standard deviation of the normal one
+    #[arg(long, default_value_t = 20)]
+    spread: u64,
+
+    #[arg(long, value_enum, default_value_t = PriceDistribution::Normal)]
+    distribution: PriceDistribution,
+
+    #[arg(long, default_value_t = 1)]
+    tick_size: Price,
+
+    /// Largest order quantity; quantities are uniform from 1
+    #[arg(long, default_value_t = 100)]
+    max_quantity: Quantity,
+
+    /// Seed of the first thread's flow; thread `n` uses `seed + n`
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+
+    /// Write every generated command to this replay file
+    #[arg(long)]
+    record: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Target {
+    /// An `OrderBook` in this process
+    InProcess,
+    /// A trading server's order gateway
+    Gateway,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum PriceDistribution {
+    Uniform,
+    Normal,
+}
+
+/// Command kinds, in the order `Mix` weights them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Limit,
+    Market,
+    Cancel,
+    Modify,
+}
+
+impl Kind {
+    const ALL: [Kind; 4] = [Kind::Limit, Kind::Market, Kind::Cancel, Kind::Modify];
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Limit => "limit",
+            Kind::Market => "market",
+            Kind::Cancel => "cancel",
+            Kind::Modify => "modify",
+        }
+    }
+
+    fn of(command: &ReplayCommand) -> Kind {
+        match command {
+            ReplayCommand::Market { .. } => Kind::Market,
+            ReplayCommand::Cancel { .. } => Kind::Cancel,
+            ReplayCommand::Modify { .. } => Kind::Modify,
+            _ => Kind::Limit,
+        }
+    }
+}
+
+/// Relative weights of limit, market, cancel and modify commands, written
+/// as `limit=60,market=10,cancel=20,modify=10`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Mix {
+    weights: [u32; 4],
+}
+
+impl Mix {
+    fn total(&self) -> u32 {
+        self.weights.iter().sum()
+    }
+
+    fn pick(&self, rng: &mut impl Rng) -> Kind {
+        let mut roll = rng.gen_range(0..self.total());
+        for (kind, weight) in Kind::ALL.into_iter().zip(self.weights) {
+            if roll < weight {
+                return kind;
+            }
+            roll -= weight;
+        }
+        Kind::Limit
+    }
+}
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = [0; 4];
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let (name, weight) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected kind=weight, got {:?}", part))?;
+            let index = Kind::ALL
+                .iter()
+                .position(|kind| kind.name() == name.trim())
+                .ok_or_else(|| format!("unknown command kind {:?}", name))?;
+            weights[index] = weight
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid weight {:?}", weight))?;
+        }
+
+        let mix = Mix { weights };
+        if mix.total() == 0 {
+            return Err("at least one weight must be positive".to_string());
+        }
+        Ok(mix)
+    }
+}
+
+/// Random order flow of one thread
+///
+/// Orders are named `t<thread>-<n>` so that cancels and modifies in a
+/// recording refer to them the way `Replayer` expects.
+struct FlowGenerator {
+    rng: StdRng,
+    thread: usize,
+    next_order: u64,
+    /// Orders that may still be resting
+    open: Vec<String>,
+    mix: Mix,
+    mid: Price,
+    spread: u64,
+    distribution: PriceDistribution,
+    tick_size: Price,
+    max_quantity: Quantity,
+}
+
+impl FlowGenerator {
+    fn new(args: &Args, thread: usize) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(args.seed.wrapping_add(thread as u64)),
+            thread,
+            next_order: 0,
+            open: Vec::new(),
+            mix: args.mix,
+            mid: args.mid,
+            spread: args.spread,
+            distribution: args.distribution,
+            tick_size: args.tick_size.max(1),
+            max_quantity: args.max_quantity.max(1),
+        }
+    }
+
+    fn next_command(&mut self) -> ReplayCommand {
+        let mut kind = self.mix.pick(&mut self.rng);
+        if matches!(kind, Kind::Cancel | Kind::Modify) && self.open.is_empty() {
+            kind = Kind::Limit;
+        }
+
+        let side = if self.rng.gen_bool(0.5) {
+            Side::Buy
+        } else {
+            Side::Sell
+        };
+        match kind {
+            Kind::Limit => {
+                let client_id = self.new_client_id();
+                self.open.push(client_id.clone());
+                ReplayCommand::Limit {
+                    side,
+                    price: self.price(),
+                    quantity: self.quantity(),
+                    client_id: Some(client_id),
+                }
+            }
+            Kind::Market => ReplayCommand::Market {
+                side,
+                quantity: self.quantity(),
+                client_id: Some(self.new_client_id()),
+            },
+            Kind::Cancel => {
+                let index = self.rng.gen_range(0..self.open.len());
+                ReplayCommand::Cancel {
+                    client_id: self.open.swap_remove(index),
+                }
+            }
+            Kind::Modify => {
+                let index = self.rng.gen_range(0..self.open.len());
+                ReplayCommand::Modify {
+                    client_id: self.open[index].clone(),
+                    quantity: self.quantity(),
+                }
+            }
+        }
+    }
+
+    /// Stop picking `client_id` for cancels and modifies
+    fn closed(&mut self, client_id: &str) {
+        if let Some(index) = self.open.iter().position(|open| open == client_id) {
+            self.open.swap_remove(index);
+        }
+    }
+
+    fn new_client_id(&mut self) -> String {
+        self.next_order += 1;
+        format!("t{}-{}", self.thread, self.next_order)
+    }
+
+    fn price(&mut self) -> Price {
+        let spread = self.spread as f64;
+        let offset = match self.distribution {
+            PriceDistribution::Uniform => self.rng.gen_range(-spread..=spread),
+            PriceDistribution::Normal => {
+                // Box-Muller transform
+                let u1: f64 = self.rng.gen_range(f64::EPSILON..1.0);
+                let u2: f64 = self.rng.gen();
+                (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos() * spread
+            }
+        };
+        let ticks = (self.mid / self.tick_size) as i64 + offset.round() as i64;
+        ticks.max(1) as Price * self.tick_size
+    }
+
+    fn quantity(&mut self) -> Quantity {
+        self.rng.gen_range(1..=self.max_quantity)
+    }
+}
+
+/// Spaces commands to reach a target rate
+struct Pacer {
+    interval: Option<Duration>,
+    next: Instant,
+}
+
+impl Pacer {
+    fn new(rate_per_thread: f64) -> Self {
+        Self {
+            interval: (rate_per_thread > 0.0)
+                .then(|| Duration::from_secs_f64(1.0 / rate_per_thread)),
+            next: Instant::now(),
+        }
+    }
+
+    /// Get how long to wait before sending the next command
+    fn delay(&mut self) -> Duration {
+        let Some(interval) = self.interval else {
+            return Duration::ZERO;
+        };
+        let now = Instant::now();
+        let delay = self.next.saturating_duration_since(now);
+        self.next = self.next.max(now) + interval;
+        delay
+    }
+}
+
+/// Latency histogram and command counts of one or more threads
+struct Stats {
+    latency: Histogram<u64>,
+    sent: [u64; 4],
+    rejected: [u64; 4],
+}
+
+impl Stats {
+    fn new() -> Self {
+        Self {
+            // 1ns to one minute at three significant figures
+            latency: Histogram::new_with_bounds(1, 60_000_000_000, 3)
+                .expect("valid histogram bounds"),
+            sent: [0; 4],
+            rejected: [0; 4],
+        }
+    }
+
+    fn record(&mut self, kind: Kind, latency: Duration, accepted: bool) {
+        let index = kind as usize;
+        self.sent[index] += 1;
+        if !accepted {
+            self.rejected[index] += 1;
+        }
+        self.latency
+            .saturating_record(latency.as_nanos().max(1) as u64);
+    }
+
+    fn merge(&mut self, other: &Stats) {
+        self.latency
+            .add(&other.latency)
+            .expect("histograms share bounds");
+        for index in 0..4 {
+            self.sent[index] += other.sent[index];
+            self.rejected[index] += other.rejected[index];
+        }
+    }
+
+    fn report(&self, elapsed: Duration) {
+        let total: u64 = self.sent.iter().sum();
+        println!(
+            "{} commands in {:.2}s: {:.0} commands/s",
+            total,
+            elapsed.as_secs_f64(),
+            total as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+        );
+        for kind in Kind::ALL {
+            let index = kind as usize;
+            println!(
+                "  {:<7} {:>10} sent {:>10} rejected",
+                kind.name(),
+                self.sent[index],
+                self.rejected[index]
+            );
+        }
+
+        let micros = |nanos: u64| nanos as f64 / 1_000.0;
+        println!("latency (us):");
+        for (label, quantile) in [
+            ("p50", 0.5),
+            ("p90", 0.9),
+            ("p99", 0.99),
+            ("p99.9", 0.999),
+            ("p99.99", 0.9999),
+        ] {
+            println!(
+                "  {:<7} {:>10.1}",
+                label,
+                micros(self.latency.value_at_quantile(quantile))
+            );
+        }
+        println!("  {:<7} {:>10.1}", "max", micros(self.latency.max()));
+        println!("  {:<7} {:>10.1}", "mean", self.latency.mean() / 1_000.0);
+    }
+}
+
+/// Replay file shared by all threads
+///
+/// Threads finish commands out of order, so each command is stamped with a
+/// global sequence number as it is submitted and lines are written in
+/// sequence order. Commands in flight together on different threads may
+/// still have reached the book in a different order.
+struct Recorder {
+    next_seq: AtomicU64,
+    file: Option<Mutex<RecordFile>>,
+}
+
+/// Replay file and the commands waiting for an earlier one to be recorded
+struct RecordFile {
+    writer: BufWriter<File>,
+    next_seq: u64,
+    waiting: BTreeMap<u64, ReplayCommand>,
+}
+
+impl Recorder {
+    fn new(path: Option<&Path>) -> io::Result<Self> {
+        let file = match path {
+            Some(path) => Some(Mutex::new(RecordFile {
+                writer: BufWriter::new(File::create(path)?),
+                next_seq: 0,
+                waiting: BTreeMap::new(),
+            })),
+            None => None,
+        };
+        Ok(Self {
+            next_seq: AtomicU64::new(0),
+            file,
+        })
+    }
+
+    /// Take the sequence number of a command about to be submitted
+    fn stamp(&self) -> u64 {
+        self.next_seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Record the command stamped `seq`, writing every command whose turn
+    /// has come
+    fn record(&self, seq: u64, command: &ReplayCommand) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut file = file.lock();
+        let file = &mut *file;
+        file.waiting.insert(seq, command.clone());
+        while let Some(command) = file.waiting.remove(&file.next_seq) {
+            write_command(&mut file.writer, &command)?;
+            file.next_seq += 1;
+        }
+        Ok(())
+    }
+
+    /// Write the commands still waiting and flush
+    ///
+    /// Commands wait here only if an earlier stamped command was never
+    /// recorded, e.g. because its gateway session failed.
+    fn flush(&self) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut file = file.lock();
+        let file = &mut *file;
+        for command in std::mem::take(&mut file.waiting).into_values() {
+            write_command(&mut file.writer, &command)?;
+        }
+        file.writer.flush()
+    }
+}
+
+fn write_command(writer: &mut impl Write, command: &ReplayCommand) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, command)?;
+    writer.write_all(b"\n")
+}
+
+/// Stop picking resting orders of this thread's flow that the book's order
+/// feed shows filled or deleted, whichever thread's command did it
+///
+/// `names` maps the ids of this thread's resting orders to their names.
+fn prune_closed(
+    flow: &mut FlowGenerator,
+    names: &mut HashMap<OrderId, String>,
+    book: &OrderBook,
+    feed: &mut Receiver<OrderMessage>,
+) {
+    loop {
+        let order_id = match feed.try_recv() {
+            Ok(OrderMessage::Execute {
+                order_id,
+                remaining_qty: 0,
+                ..
+            })
+            | Ok(OrderMessage::Delete { order_id, .. }) => order_id,
+            Ok(_) => continue,
+            Err(TryRecvError::Empty) => return,
+            // Fell behind the feed; stale names are dropped when a modify
+            // of them fails
+            Err(TryRecvError::Disconnected) => {
+                *feed = book.subscribe_orders();
+                return;
+            }
+        };
+        if let Some(name) = names.remove(&order_id) {
+            flow.closed(&name);
+        }
+    }
+}
+
+/// Split `total` commands evenly over `threads`
+fn share(total: u64, threads: usize, thread: usize) -> u64 {
+    let threads = threads as u64;
+    total / threads + u64::from((thread as u64) < total % threads)
+}
+
+fn run_in_process(args: &Args, recorder: &Recorder, deadline: Option<Instant>) -> Stats {
+    let book = OrderBook::new(args.symbol.clone());
+    let rate_per_thread = args.rate as f64 / args.threads as f64;
+
+    let thread_stats: Vec<Stats> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..args.threads)
+            .map(|thread| {
+                let book = &book;
+                scope.spawn(move || {
+                    let mut flow = FlowGenerator::new(args, thread);
+                    let mut replayer = Replayer::new(book);
+                    let mut pacer = Pacer::new(rate_per_thread);
+                    let mut stats = Stats::new();
+                    let mut feed = book.subscribe_orders();
+                    let mut names = HashMap::new();
+
+                    for _ in 0..share(args.commands, args.threads, thread) {
+                        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                            break;
+                        }
+                        std::thread::sleep(pacer.delay());
+                        prune_closed(&mut flow, &mut names, book, &mut feed);
+
+                        let command = flow.next_command();
+                        let kind = Kind::of(&command);
+                        let seq = recorder.stamp();
+                        let started = Instant::now();
+                        let result = replayer.apply(command.clone());
+                        let latency = started.elapsed();
+
+                        // Limit orders that fill on entry never rest
+                        if let (ReplayCommand::Limit { client_id, .. }, Ok(events)) =
+                            (&command, &result)
+                        {
+                            let rested = events.iter().find_map(|event| match event {
+                                MarketEvent::OrderAdded { order } => Some(order.id),
+                                _ => None,
+                            });
+                            match (rested, client_id) {
+                                (Some(order_id), Some(client_id)) => {
+                                    names.insert(order_id, client_id.clone());
+                                }
+                                (None, Some(client_id)) => flow.closed(client_id),
+                                _ => {}
+                            }
+                        }
+                        if let Err(OrderBookError::OrderNotFound) = result {
+                            if let ReplayCommand::Modify { client_id, .. } = &command {
+                                flow.closed(client_id);
+                            }
+                        }
+
+                        stats.record(kind, latency, result.is_ok());
+                        if let Err(e) = recorder.record(seq, &command) {
+                            eprintln!("Failed to record command: {}", e);
+                        }
+                    }
+                    stats
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("load generator thread panicked"))
+            .collect()
+    });
+
+    let mut stats = Stats::new();
+    for thread in &thread_stats {
+        stats.merge(thread);
+    }
+    let book_stats = book.get_stats();
+    println!(
+        "book: {} resting orders, {} trades, {} traded",
+        book_stats.total_orders, book_stats.total_trades, book_stats.total_volume
+    );
+    stats
+}
+
+async fn run_gateway(
+    args: Arc<Args>,
+    recorder: Arc<Recorder>,
+    deadline: Option<Instant>,
+) -> io::Result<Stats> {
+    let sessions: Vec<_> = (0..args.threads)
+        .map(|thread| {
+            let args = Arc::clone(&args);
+            let recorder = Arc::clone(&recorder);
+            tokio::spawn(async move { run_session(&args, &recorder, deadline, thread).await })
+        })
+        .collect();
+
+    let mut stats = Stats::new();
+    for session in sessions {
+        let session_stats = session.await.map_err(io::Error::other)??;
+        stats.merge(&session_stats);
+    }
+    Ok(stats)
+}
+
+/// Send one thread's flow over its own gateway session, timing each
+/// command until its ack or reject
+async fn run_session(
+    args: &Args,
+    recorder: &Recorder,
+    deadline: Option<Instant>,
+    thread: usize,
+) -> io::Result<Stats> {
+    let stream = TcpStream::connect(&args.addr).await?;
+    stream.set_nodelay(true)?;
+    let (mut reader, mut writer) = stream.into_split();
+
+    let client_id = format!("load-{}-{}", std::process::id(), thread);
+    write_frame(&mut writer, &ClientMessage::Logon { client_id }.encode()).await?;
+    match next_message(&mut reader).await? {
+        ServerMessage::LogonAccepted { .. } => {}
+        other => {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("logon failed: {:?}", other),
+            ))
+        }
+    }
+
+    let mut flow = FlowGenerator::new(args, thread);
+    let mut pacer = Pacer::new(args.rate as f64 / args.threads as f64);
+    let mut stats = Stats::new();
+    // Gateway order ids of the flow's orders, and the reverse
+    let mut order_ids: HashMap<String, OrderId> = HashMap::new();
+    let mut client_ids: HashMap<OrderId, String> = HashMap::new();
+
+    for request_id in 1..=share(args.commands, args.threads, thread) {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break;
+        }
+        tokio::time::sleep(pacer.delay()).await;
+
+        let command = flow.next_command();
+        let kind = Kind::of(&command);
+        let (name, message) = match &command {
+            ReplayCommand::Limit {
+                side,
+                price,
+                quantity,
+                client_id,
+            } => (
+                client_id.clone(),
+                ClientMessage::NewOrder {
+                    request_id,
+                    symbol: args.symbol.clone(),
+                    side: *side,
+                    kind: OrderKind::Limit,
+                    price: *price,
+                    stop_price: 0,
+                    quantity: *quantity,
+                    time_in_force: TimeInForce::GoodTillCancel,
+                },
+            ),
+            ReplayCommand::Market {
+                side,
+                quantity,
+                client_id,
+            } => (
+                client_id.clone(),
+                ClientMessage::NewOrder {
+                    request_id,
+                    symbol: args.symbol.clone(),
+                    side: *side,
+                    kind: OrderKind::Market,
+                    price: 0,
+                    stop_price: 0,
+                    quantity: *quantity,
+                    time_in_force: TimeInForce::GoodTillCancel,
+                },
+            ),
+            ReplayCommand::Cancel { client_id } => (
+                None,
+                ClientMessage::Cancel {
+                    request_id,
+                    order_id: order_ids.remove(client_id).unwrap_or_default(),
+                },
+            ),
+            ReplayCommand::Modify {
+                client_id,
+                quantity,
+            } => (
+                None,
+                ClientMessage::Modify {
+                    request_id,
+                    order_id: order_ids.get(client_id).copied().unwrap_or_default(),
+                    quantity: *quantity,
+                },
+            ),
+            _ => unreachable!("the flow generator only creates limit, market, cancel and modify"),
+        };
+
+        let seq = recorder.stamp();
+        let started = Instant::now();
+        write_frame(&mut writer, &message.encode()).await?;
+        let accepted = loop {
+            match next_message(&mut reader).await? {
+                ServerMessage::Ack {
+                    request_id: acked,
+                    order_id,
+                } if acked == request_id => {
+                    if let Some(name) = &name {
+                        order_ids.insert(name.clone(), order_id);
+                        client_ids.insert(order_id, name.clone());
+                    }
+                    break true;
+                }
+                ServerMessage::Reject {
+                    request_id: rejected,
+                    ..
+                } if rejected == request_id => break false,
+                // A successful cancel is answered by its report
+                ServerMessage::Cancelled {
+                    request_id: cancelled,
+                    order_id,
+                    ..
+                } if cancelled == request_id => {
+                    client_ids.remove(&order_id);
+                    break true;
+                }
+                // Orders that left the book can no longer be cancelled
+                ServerMessage::Fill {
+                    order_id,
+                    leaves_quantity: 0,
+                    ..
+                }
+                | ServerMessage::Cancelled { order_id, .. } => {
+                    if let Some(name) = client_ids.remove(&order_id) {
+                        order_ids.remove(&name);
+                        flow.closed(&name);
+                    }
+                }
+                _ => {}
+            }
+        };
+        stats.record(kind, started.elapsed(), accepted);
+        recorder.record(seq, &command)?;
+    }
+
+    Ok(stats)
+}
+
+async fn next_message(reader: &mut tokio::net::tcp::OwnedReadHalf) -> io::Result<ServerMessage> {
+    match read_frame(reader).await? {
+        Some(payload) => ServerMessage::decode(&payload),
+        None => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "gateway closed the session",
+        )),
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt().with_env_filter("warn").init();
+
+    let args = Args::parse();
+    if args.threads == 0 {
+        return Err("--threads must be at least 1".into());
+    }
+
+    let recorder = Recorder::new(args.record.as_deref())?;
+    let started = Instant::now();
+    let deadline = args
+        .duration
+        .map(|seconds| started + Duration::from_secs(seconds));
+
+    let stats = match args.target {
+        Target::InProcess => run_in_process(&args, &recorder, deadline),
+        Target::Gateway => {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(args.threads)
+                .enable_all()
+                .build()?;
+            let recorder = Arc::new(recorder);
+            let stats =
+                runtime.block_on(run_gateway(Arc::new(args), Arc::clone(&recorder), deadline))?;
+            recorder.flush()?;
+            stats.report(started.elapsed());
+            return Ok(());
+        }
+    };
+
+    recorder.flush()?;
+    stats.report(started.elapsed());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mix_parsing() {
+        let mix: Mix = "limit=60, market=10,cancel=20,modify=10".parse().unwrap();
+        assert_eq!(mix.weights, [60, 10, 20, 10]);
+        assert_eq!("market=1".parse::<Mix>().unwrap().weights, [0, 1, 0, 0]);
+        assert!("limit=0".parse::<Mix>().is_err());
+        assert!("stop=5".parse::<Mix>().is_err());
+        assert!("limit".parse::<Mix>().is_err());
+    }
+
+    #[test]
+    fn test_flow_is_reproducible() {
+        let args = Args::parse_from(["load_generator", "--seed", "7", "--tick-size", "5"]);
+        let flow = |thread| {
+            let mut flow = FlowGenerator::new(&args, thread);
+            (0..1_000)
+                .map(|_| serde_json::to_string(&flow.next_command()).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(flow(0), flow(0));
+        assert_ne!(flow(0), flow(1));
+
+        // Cancels and modifies only name limit orders the flow created, and
+        // prices stay on the tick grid
+        let mut created = std::collections::HashSet::new();
+        for line in flow(0) {
+            match serde_json::from_str(&line).unwrap() {
+                ReplayCommand::Limit {
+                    price, client_id, ..
+                } => {
+                    assert_eq!(price % 5, 0);
+                    created.insert(client_id.unwrap());
+                }
+                ReplayCommand::Cancel { client_id } | ReplayCommand::Modify { client_id, .. } => {
+                    assert!(created.contains(&client_id));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_recording_follows_submission_order() {
+        let path =
+            std::env::temp_dir().join(format!("load-generator-{}.jsonl", std::process::id()));
+        let recorder = Recorder::new(Some(&path)).unwrap();
+        let cancel = |name: &str| ReplayCommand::Cancel {
+            client_id: name.to_string(),
+        };
+
+        let seqs: Vec<u64> = (0..4).map(|_| recorder.stamp()).collect();
+        // Completed out of order; the third command is never recorded
+        recorder.record(seqs[1], &cancel("b")).unwrap();
+        recorder.record(seqs[0], &cancel("a")).unwrap();
+        recorder.record(seqs[3], &cancel("d")).unwrap();
+        recorder.flush().unwrap();
+
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let expected: Vec<String> = ["a", "b", "d"]
+            .map(|name| serde_json::to_string(&cancel(name)).unwrap())
+            .to_vec();
+        assert_eq!(recorded.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_orders_filled_by_other_threads_are_pruned() {
+        let args = Args::parse_from(["load_generator", "--mix", "limit=1"]);
+        let book = OrderBook::new(args.symbol.clone());
+        let mut flow = FlowGenerator::new(&args, 0);
+        let mut feed = book.subscribe_orders();
+        let mut names = HashMap::new();
+
+        let mut replayer = Replayer::new(&book);
+        let mut resting = 0;
+        while resting < 3 {
+            let command = flow.next_command();
+            let ReplayCommand::Limit {
+                client_id: Some(client_id),
+                ..
+            } = &command
+            else {
+                unreachable!("the mix only has limit orders");
+            };
+            let events = replayer.apply(command.clone()).unwrap();
+            match events.iter().find_map(|event| match event {
+                MarketEvent::OrderAdded { order } => Some(order.id),
+                _ => None,
+            }) {
+                Some(order_id) => {
+                    names.insert(order_id, client_id.clone());
+                    resting += 1;
+                }
+                None => flow.closed(client_id),
+            }
+        }
+        assert_eq!(flow.open.len(), 3);
+
+        // Another thread's orders sweep both sides of the book; the sell
+        // also takes out what is left of the buy
+        let sweep = args.max_quantity * 3;
+        for (side, price, quantity) in
+            [(Side::Buy, args.mid * 2, sweep), (Side::Sell, 1, sweep * 2)]
+        {
+            book.add_limit_order(Order::new_limit(
+                args.symbol.clone(),
+                side,
+                price,
+                quantity,
+                None,
+            ))
+            .unwrap();
+        }
+        prune_closed(&mut flow, &mut names, &book, &mut feed);
+        assert!(flow.open.is_empty());
+        assert!(names.is_empty());
+    }
+}
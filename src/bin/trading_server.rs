@@ -9,7 +9,12 @@ use tokio::time::interval;
 use tracing::{error, info, warn};
 use tracing_subscriber;
 
-use orderbook_trading_engine::{orderbook::types::*, OrderBook};
+use orderbook_trading_engine::{
+    gateway::{FixAcceptor, FixConfig, Gateway},
+    orderbook::types::*,
+    orderbook::{Exchange, FsyncPolicy, InstrumentConfig},
+    OrderBook, OrderBookMetrics,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -18,29 +23,53 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     info!("Starting High-Performance Trading Server...");
 
-    // Create order books for multiple symbols
+    // Serve Prometheus metrics; the recorder has to be installed before any
+    // metrics are registered
+    let metrics_addr =
+        std::env::var("ORDERBOOK_METRICS_ADDR").unwrap_or_else(|_| "0.0.0.0:9090".to_string());
+    if let Err(e) = start_metrics_server(&metrics_addr).await {
+        error!("Failed to start metrics server: {}", e);
+    }
+
+    // List instruments for multiple symbols
     let symbols = vec!["AAPL", "GOOGL", "MSFT", "TSLA", "AMZN"];
-    let mut order_books = std::collections::HashMap::new();
+    let exchange = Arc::new(Exchange::new());
+
+    // Journal every book under ORDERBOOK_JOURNAL_DIR, if set, so a restart
+    // recovers the resting orders
+    let journal_dir = std::env::var_os("ORDERBOOK_JOURNAL_DIR").map(std::path::PathBuf::from);
+    if let Some(dir) = &journal_dir {
+        std::fs::create_dir_all(dir)?;
+    }
 
     for symbol in &symbols {
-        let book = Arc::new(OrderBook::new(symbol.to_string()));
-        order_books.insert(symbol.to_string(), book);
+        let config = InstrumentConfig::new(*symbol);
+        let book = OrderBook::new(symbol.to_string())
+            .with_metrics(Some(Arc::new(OrderBookMetrics::for_symbol(*symbol))));
+        match &journal_dir {
+            Some(dir) => exchange.add_instrument_with_journal(
+                config,
+                book,
+                dir.join(format!("{}.journal", symbol)),
+                FsyncPolicy::EveryN(64),
+            )?,
+            None => exchange.add_instrument_with_book(config, book)?,
+        };
         info!("Created order book for symbol: {}", symbol);
     }
 
     // Start market data simulation
-    for (symbol, book) in &order_books {
-        let book_clone = Arc::clone(book);
-        let symbol_clone = symbol.clone();
+    for book in exchange.books() {
+        let symbol = book.symbol.clone();
 
         tokio::spawn(async move {
-            info!("Starting market simulation for {}", symbol_clone);
-            simulate_market_activity(book_clone, symbol_clone).await;
+            info!("Starting market simulation for {}", symbol);
+            simulate_market_activity(book, symbol).await;
         });
     }
 
     // Start server statistics reporting
-    let order_books_clone = order_books.clone();
+    let exchange_clone = Arc::clone(&exchange);
     tokio::spawn(async move {
         let mut interval = interval(Duration::from_secs(10));
 
@@ -50,14 +79,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let mut total_orders = 0;
             let mut total_trades = 0;
 
-            for (symbol, book) in &order_books_clone {
+            for book in exchange_clone.books() {
                 let stats = book.get_stats();
                 total_orders += stats.total_orders;
                 total_trades += stats.total_trades;
 
                 info!(
                     "📊 {} | Orders: {} | Bid: {:?} | Ask: {:?} | Spread: {:?} | Trades: {}",
-                    symbol,
+                    book.symbol,
                     stats.total_orders,
                     stats.best_bid.map(|p| format_price(p)),
                     stats.best_ask.map(|p| format_price(p)),
@@ -70,15 +99,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "🚀 Server totals: {} orders, {} trades across {} symbols",
                 total_orders,
                 total_trades,
-                order_books_clone.len()
+                exchange_clone.len()
             );
         }
     });
 
-    // Start Prometheus metrics server
+    // Start the order-entry gateway
+    let gateway_addr =
+        std::env::var("ORDERBOOK_GATEWAY_ADDR").unwrap_or_else(|_| "0.0.0.0:7000".to_string());
+    let listener = tokio::net::TcpListener::bind(&gateway_addr).await?;
+    let gateway = Arc::new(Gateway::new(Arc::clone(&exchange)));
+    let gateway_clone = Arc::clone(&gateway);
+    tokio::spawn(async move {
+        if let Err(e) = gateway_clone.run(listener).await {
+            error!("Order gateway stopped: {}", e);
+        }
+    });
+
+    // Expire good-till-date orders as they come due, telling gateway
+    // clients about the stops that expire
+    let exchange_clone = Arc::clone(&exchange);
+    let gateway_clone = Arc::clone(&gateway);
+    tokio::spawn(async move {
+        let mut interval = interval(Duration::from_secs(1));
+
+        loop {
+            interval.tick().await;
+
+            for book in exchange_clone.books() {
+                match book.expire_orders() {
+                    Ok(events) => gateway_clone.report_expired(&events),
+                    Err(e) => warn!("Expiry sweep failed for {}: {}", book.symbol, e),
+                }
+            }
+        }
+    });
+
+    // FIX 4.4 sessions share the gateway's order routing
+    let fix_addr =
+        std::env::var("ORDERBOOK_FIX_ADDR").unwrap_or_else(|_| "0.0.0.0:9878".to_string());
+    let fix_listener = tokio::net::TcpListener::bind(&fix_addr).await?;
+    let acceptor = Arc::new(FixAcceptor::new(gateway, FixConfig::default()));
     tokio::spawn(async move {
-        if let Err(e) = start_metrics_server().await {
-            error!("Failed to start metrics server: {}", e);
+        if let Err(e) = acceptor.run(fix_listener).await {
+            error!("FIX acceptor stopped: {}", e);
         }
     });
 
@@ -90,11 +154,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Shutting down trading server...");
 
     // Print final statistics
-    for (symbol, book) in &order_books {
+    for book in exchange.books() {
         let stats = book.get_stats();
         info!(
             "Final stats for {}: {} orders, {} trades",
-            symbol, stats.total_orders, stats.total_trades
+            book.symbol, stats.total_orders, stats.total_trades
         );
     }
 
@@ -237,42 +301,30 @@ fn format_price(price_ticks: u64) -> String {
     format!("${:.2}", price_ticks as f64 / 100.0)
 }
 
-/// Start Prometheus metrics server
-async fn start_metrics_server() -> Result<(), Box<dyn std::error::Error>> {
-    use std::net::SocketAddr;
-
-    let addr: SocketAddr = "0.0.0.0:9090".parse()?;
-
-    info!(
-        "Starting Prometheus metrics server on http://{}/metrics",
-        addr
-    );
-
+/// Start the Prometheus metrics server, serving the global recorder at
+/// `http://<addr>/metrics`
+async fn start_metrics_server(addr: &str) -> Result<(), Box<dyn std::error::Error>> {
     #[cfg(feature = "prometheus")]
     {
-        use metrics_exporter_prometheus::PrometheusBuilder;
+        use orderbook_trading_engine::metrics::server::MetricsServer;
 
-        let builder = PrometheusBuilder::new();
-        let _handle = builder.install()?;
-
-        // In a real implementation, you'd start an HTTP server here
-        // For this example, we'll just log that it would be running
-        info!(
-            "Prometheus metrics would be available at http://{}/metrics",
-            addr
-        );
+        let server = MetricsServer::install()?;
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        tokio::spawn(async move {
+            if let Err(e) = server.run(listener).await {
+                error!("Metrics server failed: {}", e);
+            }
+        });
     }
 
     #[cfg(not(feature = "prometheus"))]
     {
         info!(
-            "Prometheus feature not enabled, metrics server disabled. Enable with --features prometheus"
+            "Prometheus feature not enabled, metrics server on {} disabled. Enable with --features prometheus",
+            addr
         );
     }
 
-    // Keep the server alive
-    std::future::pending::<()>().await;
-
     Ok(())
 }
 
@@ -0,0 +1,416 @@
+//! FIX 4.4 tag-value message codec
+//!
+//! Messages are `tag=value` fields separated by SOH (`0x01`). The standard
+//! header starts with BeginString (8), BodyLength (9) and MsgType (35), and
+//! every message ends with a three-digit CheckSum (10) over all preceding
+//! bytes. Only the fields the acceptor needs are named in [`tags`].
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+/// Field separator
+pub const SOH: u8 = 0x01;
+
+/// BeginString of every message
+pub const BEGIN_STRING: &str = "FIX.4.4";
+
+/// Largest accepted BodyLength
+pub const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// Field tags
+pub mod tags {
+    pub const AVG_PX: u32 = 6;
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECK_SUM: u32 = 10;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const CUM_QTY: u32 = 14;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const EXEC_ID: u32 = 17;
+    pub const LAST_PX: u32 = 31;
+    pub const LAST_QTY: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const PRICE: u32 = 44;
+    pub const REF_SEQ_NUM: u32 = 45;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const TRANSACT_TIME: u32 = 60;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const TIME_IN_FORCE: u32 = 59;
+    pub const STOP_PX: u32 = 99;
+    pub const CXL_REJ_REASON: u32 = 102;
+    pub const ORD_REJ_REASON: u32 = 103;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const EXPIRE_TIME: u32 = 126;
+    pub const RESET_SEQ_NUM_FLAG: u32 = 141;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const LEAVES_QTY: u32 = 151;
+    pub const REF_TAG_ID: u32 = 371;
+    pub const SESSION_REJECT_REASON: u32 = 373;
+    pub const CXL_REJ_RESPONSE_TO: u32 = 434;
+}
+
+/// MsgType values
+pub mod msg_type {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const LOGON: &str = "A";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+    pub const ORDER_CANCEL_REPLACE_REQUEST: &str = "G";
+}
+
+/// A FIX message without its BeginString, BodyLength and CheckSum fields
+///
+/// Decoded messages keep the remaining header fields (SenderCompID,
+/// MsgSeqNum, ...) in `fields`; when encoding, the session supplies them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixMessage {
+    msg_type: String,
+    fields: Vec<(u32, String)>,
+}
+
+impl FixMessage {
+    pub fn new(msg_type: &str) -> Self {
+        Self {
+            msg_type: msg_type.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn msg_type(&self) -> &str {
+        &self.msg_type
+    }
+
+    /// Append a field, builder style
+    pub fn with(mut self, tag: u32, value: impl ToString) -> Self {
+        self.set(tag, value);
+        self
+    }
+
+    /// Set a field, replacing any earlier value for the tag
+    pub fn set(&mut self, tag: u32, value: impl ToString) {
+        let value = value.to_string();
+        match self.fields.iter_mut().find(|(t, _)| *t == tag) {
+            Some(field) => field.1 = value,
+            None => self.fields.push((tag, value)),
+        }
+    }
+
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_u64(&self, tag: u32) -> Option<u64> {
+        self.get(tag).and_then(|value| value.parse().ok())
+    }
+
+    /// Check a Y/N flag field, which defaults to N
+    pub fn flag(&self, tag: u32) -> bool {
+        self.get(tag) == Some("Y")
+    }
+
+    /// Encode with a standard header for the given session and sequence
+    pub fn encode(&self, sender_comp_id: &str, target_comp_id: &str, msg_seq_num: u64) -> Vec<u8> {
+        let mut body = Vec::with_capacity(128);
+        put_field(&mut body, tags::MSG_TYPE, &self.msg_type);
+        put_field(&mut body, tags::SENDER_COMP_ID, sender_comp_id);
+        put_field(&mut body, tags::TARGET_COMP_ID, target_comp_id);
+        put_field(&mut body, tags::MSG_SEQ_NUM, &msg_seq_num.to_string());
+        put_field(&mut body, tags::SENDING_TIME, &utc_timestamp());
+        for (tag, value) in &self.fields {
+            if !is_header_tag(*tag) {
+                put_field(&mut body, *tag, value);
+            }
+        }
+
+        let mut message = Vec::with_capacity(body.len() + 32);
+        put_field(&mut message, tags::BEGIN_STRING, BEGIN_STRING);
+        put_field(&mut message, tags::BODY_LENGTH, &body.len().to_string());
+        message.extend_from_slice(&body);
+        let checksum = checksum(&message);
+        put_field(&mut message, tags::CHECK_SUM, &format!("{:03}", checksum));
+        message
+    }
+
+    /// Decode a complete message, validating BodyLength and CheckSum
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let trailer_start = bytes
+            .len()
+            .checked_sub(7)
+            .filter(|&start| {
+                start > 0 && bytes[start..].starts_with(b"10=") && bytes.last() == Some(&SOH)
+            })
+            .ok_or_else(|| invalid("missing CheckSum".to_string()))?;
+
+        let declared: u32 = parse(&bytes[trailer_start + 3..bytes.len() - 1])?;
+        let actual = checksum(&bytes[..trailer_start]);
+        if declared != actual as u32 {
+            return Err(invalid(format!(
+                "CheckSum {:03} does not match computed {:03}",
+                declared, actual
+            )));
+        }
+
+        let mut fields = bytes[..trailer_start - 1]
+            .split(|&b| b == SOH)
+            .map(parse_field);
+
+        match fields.next().transpose()? {
+            Some((tags::BEGIN_STRING, value)) if value == BEGIN_STRING => {}
+            _ => return Err(invalid(format!("BeginString must be {}", BEGIN_STRING))),
+        }
+        let body_length: usize = match fields.next().transpose()? {
+            Some((tags::BODY_LENGTH, value)) => parse(value.as_bytes())?,
+            _ => return Err(invalid("BodyLength must be the second field".to_string())),
+        };
+        let body_start = bytes
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == SOH)
+            .nth(1)
+            .map(|(i, _)| i + 1)
+            .unwrap_or(0);
+        if body_start + body_length != trailer_start {
+            return Err(invalid("BodyLength does not match message".to_string()));
+        }
+        let msg_type = match fields.next().transpose()? {
+            Some((tags::MSG_TYPE, value)) => value,
+            _ => return Err(invalid("MsgType must be the third field".to_string())),
+        };
+
+        Ok(Self {
+            msg_type,
+            fields: fields.collect::<io::Result<_>>()?,
+        })
+    }
+}
+
+/// Current time in FIX UTCTimestamp format
+pub fn utc_timestamp() -> String {
+    Utc::now().format("%Y%m%d-%H:%M:%S%.3f").to_string()
+}
+
+/// Parse a FIX UTCTimestamp, with or without milliseconds
+pub fn parse_utc_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    ["%Y%m%d-%H:%M:%S%.3f", "%Y%m%d-%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|time| time.and_utc())
+}
+
+/// Read one raw message, or `None` if the peer closed the connection
+/// cleanly between messages
+///
+/// Framing relies on BodyLength; the result still has to go through
+/// [`FixMessage::decode`].
+pub async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut message = Vec::with_capacity(256);
+    if reader.read_until(SOH, &mut message).await? == 0 {
+        return Ok(None);
+    }
+    if !message.starts_with(b"8=") {
+        return Err(invalid(
+            "message does not start with BeginString".to_string(),
+        ));
+    }
+
+    let length_start = message.len();
+    reader.read_until(SOH, &mut message).await?;
+    let length_field = &message[length_start..];
+    if !length_field.starts_with(b"9=") || length_field.last() != Some(&SOH) {
+        return Err(invalid("BodyLength must follow BeginString".to_string()));
+    }
+    let body_length: usize = parse(&length_field[2..length_field.len() - 1])?;
+    if body_length > MAX_BODY_LEN {
+        return Err(invalid(format!("BodyLength {} too large", body_length)));
+    }
+
+    // Body plus the "10=nnn<SOH>" trailer
+    let start = message.len();
+    message.resize(start + body_length + 7, 0);
+    reader.read_exact(&mut message[start..]).await?;
+    Ok(Some(message))
+}
+
+/// Convert a decimal price to ticks with `decimals` fractional digits
+///
+/// Returns `None` for malformed prices or prices off the tick grid.
+pub fn price_to_ticks(price: &str, decimals: u32) -> Option<u64> {
+    let (whole, fraction) = price.split_once('.').unwrap_or((price, ""));
+    let fraction = fraction.trim_end_matches('0');
+    if whole.is_empty() && fraction.is_empty()
+        || fraction.len() > decimals as usize
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let scale = 10u64.checked_pow(decimals)?;
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let fraction: u64 = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse::<u64>().ok()? * 10u64.pow(decimals - fraction.len() as u32)
+    };
+    whole.checked_mul(scale)?.checked_add(fraction)
+}
+
+/// Format ticks with `decimals` fractional digits
+pub fn ticks_to_price(ticks: u64, decimals: u32) -> String {
+    if decimals == 0 {
+        return ticks.to_string();
+    }
+    let scale = 10u64.pow(decimals);
+    format!(
+        "{}.{:0width$}",
+        ticks / scale,
+        ticks % scale,
+        width = decimals as usize
+    )
+}
+
+fn is_header_tag(tag: u32) -> bool {
+    matches!(
+        tag,
+        tags::BEGIN_STRING
+            | tags::BODY_LENGTH
+            | tags::MSG_TYPE
+            | tags::SENDER_COMP_ID
+            | tags::TARGET_COMP_ID
+            | tags::MSG_SEQ_NUM
+            | tags::SENDING_TIME
+            | tags::CHECK_SUM
+    )
+}
+
+fn put_field(buf: &mut Vec<u8>, tag: u32, value: &str) {
+    buf.extend_from_slice(tag.to_string().as_bytes());
+    buf.push(b'=');
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(SOH);
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn parse_field(field: &[u8]) -> io::Result<(u32, String)> {
+    let eq = field
+        .iter()
+        .position(|&b| b == b'=')
+        .ok_or_else(|| invalid("field without '='".to_string()))?;
+    let tag = parse(&field[..eq])?;
+    let value = std::str::from_utf8(&field[eq + 1..])
+        .map_err(|_| invalid(format!("tag {} is not UTF-8", tag)))?;
+    Ok((tag, value.to_string()))
+}
+
+fn parse<T: std::str::FromStr>(bytes: &[u8]) -> io::Result<T> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| {
+            invalid(format!(
+                "invalid number {:?}",
+                String::from_utf8_lossy(bytes)
+            ))
+        })
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let message = FixMessage::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tags::CL_ORD_ID, "ord-1")
+            .with(tags::SYMBOL, "AAPL")
+            .with(tags::SIDE, 1)
+            .with(tags::PRICE, "150.25");
+        let bytes = message.encode("CLIENT", "ENGINE", 7);
+
+        let text = String::from_utf8(bytes.clone())
+            .unwrap()
+            .replace('\x01', "|");
+        assert!(text.starts_with("8=FIX.4.4|9="));
+        assert!(text.contains("|35=D|49=CLIENT|56=ENGINE|34=7|52="));
+
+        let decoded = FixMessage::decode(&bytes).unwrap();
+        assert_eq!(decoded.msg_type(), "D");
+        assert_eq!(decoded.get(tags::CL_ORD_ID), Some("ord-1"));
+        assert_eq!(decoded.get_u64(tags::MSG_SEQ_NUM), Some(7));
+        assert_eq!(decoded.get(tags::SENDER_COMP_ID), Some("CLIENT"));
+
+        // Any corrupted byte fails the checksum
+        let mut corrupted = bytes;
+        let index = corrupted.len() / 2;
+        corrupted[index] ^= 0x20;
+        assert!(FixMessage::decode(&corrupted).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_message_framing() {
+        let first = FixMessage::new(msg_type::HEARTBEAT).encode("A", "B", 1);
+        let second = FixMessage::new(msg_type::TEST_REQUEST)
+            .with(tags::TEST_REQ_ID, "ping")
+            .encode("A", "B", 2);
+        let stream = [first.clone(), second.clone()].concat();
+
+        let mut reader = tokio::io::BufReader::new(&stream[..]);
+        assert_eq!(read_message(&mut reader).await.unwrap(), Some(first));
+        assert_eq!(read_message(&mut reader).await.unwrap(), Some(second));
+        assert_eq!(read_message(&mut reader).await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_price_conversion() {
+        assert_eq!(price_to_ticks("150.25", 2), Some(15025));
+        assert_eq!(price_to_ticks("150", 2), Some(15000));
+        assert_eq!(price_to_ticks("150.250", 2), Some(15025));
+        assert_eq!(price_to_ticks(".5", 2), Some(50));
+        assert_eq!(price_to_ticks("150.255", 2), None);
+        assert_eq!(price_to_ticks("-1", 2), None);
+        assert_eq!(price_to_ticks("", 2), None);
+        assert_eq!(ticks_to_price(15025, 2), "150.25");
+        assert_eq!(ticks_to_price(5, 2), "0.05");
+        assert_eq!(ticks_to_price(42, 0), "42");
+    }
+}
@@ -0,0 +1,1288 @@
+//! FIX 4.4 order-entry acceptor
+//!
+//! Each FIX session is a client of the [`Gateway`]: the counterparty's
+//! SenderCompID is the gateway client ID, NewOrderSingle,
+//! OrderCancelRequest and OrderCancelReplaceRequest become gateway requests,
+//! and the gateway's reports come back as ExecutionReport or
+//! OrderCancelReject messages.
+//!
+//! Sequence numbers and open orders are kept per counterparty across
+//! reconnects until a Logon with ResetSeqNumFlag (141=Y) resets the
+//! sequences. Execution reports are not stored for replay: a ResendRequest is
+//! answered with a SequenceReset-GapFill up to the current sequence number.
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, timeout, Instant};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::gateway::fix::{
+    msg_type, parse_utc_timestamp, price_to_ticks, read_message, tags, ticks_to_price,
+    utc_timestamp, FixMessage,
+};
+use crate::gateway::protocol::{ClientMessage, OrderKind, ServerMessage};
+use crate::gateway::server::Gateway;
+use crate::orderbook::error::OrderBookError;
+use crate::orderbook::types::{OrderId, Price, Quantity, Side, TimeInForce};
+
+/// SessionRejectReason (373) values used by the acceptor
+const REQUIRED_TAG_MISSING: u32 = 1;
+const VALUE_INCORRECT: u32 = 5;
+const INVALID_MSG_TYPE: u32 = 11;
+
+/// Acceptor settings
+#[derive(Debug, Clone)]
+pub struct FixConfig {
+    /// CompID of the acceptor; inbound TargetCompID must match it
+    pub comp_id: String,
+    /// Fractional digits of FIX prices; one engine tick is `10^-price_decimals`
+    pub price_decimals: u32,
+    /// How long a new connection may take to send its Logon
+    pub logon_timeout: Duration,
+}
+
+impl Default for FixConfig {
+    fn default() -> Self {
+        Self {
+            comp_id: "ORDERBOOK".to_string(),
+            price_decimals: 2,
+            logon_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// An order as the FIX counterparty sees it
+#[derive(Debug, Clone)]
+struct FixOrder {
+    cl_ord_id: String,
+    symbol: String,
+    side: Side,
+    ord_type: String,
+    order_qty: Quantity,
+    price: Option<Price>,
+    stop_price: Option<Price>,
+    cum_qty: Quantity,
+    leaves_qty: Quantity,
+    /// Sum of price * quantity over all fills, for AvgPx
+    notional: u128,
+}
+
+impl FixOrder {
+    /// OrdStatus of a live order
+    fn status(&self) -> &'static str {
+        if self.cum_qty > 0 {
+            "1"
+        } else {
+            "0"
+        }
+    }
+
+    fn avg_px(&self, decimals: u32) -> String {
+        if self.cum_qty == 0 {
+            return "0".to_string();
+        }
+        let avg_ticks = self.notional as f64 / self.cum_qty as f64;
+        format!(
+            "{:.*}",
+            decimals as usize + 4,
+            avg_ticks / 10f64.powi(decimals as i32)
+        )
+    }
+}
+
+/// Sequence numbers and open orders of one counterparty
+#[derive(Debug)]
+struct SessionState {
+    next_inbound: u64,
+    next_outbound: u64,
+    orders: HashMap<OrderId, FixOrder>,
+    cl_ord_ids: HashMap<String, OrderId>,
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        Self {
+            next_inbound: 1,
+            next_outbound: 1,
+            orders: HashMap::new(),
+            cl_ord_ids: HashMap::new(),
+        }
+    }
+}
+
+/// A gateway request waiting for its first report
+#[derive(Debug)]
+enum PendingRequest {
+    New(FixOrder),
+    Cancel {
+        cl_ord_id: String,
+        orig_cl_ord_id: String,
+    },
+    Replace {
+        cl_ord_id: String,
+        orig_cl_ord_id: String,
+        order_id: OrderId,
+        order_qty: Quantity,
+    },
+}
+
+/// FIX 4.4 acceptor in front of a [`Gateway`]
+#[derive(Debug)]
+pub struct FixAcceptor {
+    gateway: Arc<Gateway>,
+    config: FixConfig,
+    sessions: Mutex<HashMap<String, SessionState>>,
+}
+
+impl FixAcceptor {
+    pub fn new(gateway: Arc<Gateway>, config: FixConfig) -> Self {
+        Self {
+            gateway,
+            config,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Accept connections until the listener fails
+    pub async fn run(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        info!(
+            "FIX acceptor {} listening on {}",
+            self.config.comp_id,
+            listener.local_addr()?
+        );
+
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let acceptor = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(e) = acceptor.handle_connection(stream, peer).await {
+                    warn!("FIX session {} ended with error: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(&self, stream: TcpStream, peer: SocketAddr) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let (reader, mut writer) = stream.into_split();
+
+        // Read on a separate task so the session loop can select on it safely
+        let (inbound_tx, mut inbound) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            loop {
+                let result = read_message(&mut reader).await.transpose();
+                let done = !matches!(result, Some(Ok(_)));
+                if let Some(result) = result {
+                    if inbound_tx.send(result).is_err() {
+                        break;
+                    }
+                }
+                if done {
+                    break;
+                }
+            }
+        });
+
+        let logon = match timeout(self.config.logon_timeout, inbound.recv()).await {
+            Ok(Some(raw)) => FixMessage::decode(&raw?)?,
+            Ok(None) => return Ok(()),
+            Err(_) => {
+                warn!(
+                    "No Logon from {} within {:?}",
+                    peer, self.config.logon_timeout
+                );
+                return Ok(());
+            }
+        };
+        if logon.msg_type() != msg_type::LOGON {
+            warn!("First message from {} was not a Logon", peer);
+            return Ok(());
+        }
+
+        let comp_id = logon
+            .get(tags::SENDER_COMP_ID)
+            .unwrap_or_default()
+            .to_string();
+        let heartbeat = logon.get_u64(tags::HEART_BT_INT).filter(|&secs| secs > 0);
+        let msg_seq_num = logon.get_u64(tags::MSG_SEQ_NUM);
+        let reset = logon.flag(tags::RESET_SEQ_NUM_FLAG);
+
+        let problem = if comp_id.is_empty() {
+            Some("SenderCompID is required".to_string())
+        } else if logon.get(tags::TARGET_COMP_ID) != Some(self.config.comp_id.as_str()) {
+            Some(format!("TargetCompID must be {}", self.config.comp_id))
+        } else if heartbeat.is_none() {
+            Some("HeartBtInt must be a positive number of seconds".to_string())
+        } else if msg_seq_num.is_none() {
+            Some("MsgSeqNum is required".to_string())
+        } else if logon
+            .get(tags::ENCRYPT_METHOD)
+            .is_some_and(|method| method != "0")
+        {
+            Some("EncryptMethod must be 0".to_string())
+        } else if reset && msg_seq_num != Some(1) {
+            Some("MsgSeqNum must be 1 with ResetSeqNumFlag".to_string())
+        } else {
+            None
+        };
+        if let Some(text) = problem {
+            let logout = FixMessage::new(msg_type::LOGOUT).with(tags::TEXT, &text);
+            writer
+                .write_all(&logout.encode(&self.config.comp_id, &comp_id, 1))
+                .await?;
+            warn!("Rejected FIX logon from {}: {}", peer, text);
+            return Ok(());
+        }
+        let (heartbeat, msg_seq_num) = (heartbeat.unwrap_or(30), msg_seq_num.unwrap_or(1));
+
+        let (report_tx, mut reports) = mpsc::unbounded_channel();
+        if !self.gateway.register_session(&comp_id, report_tx.clone()) {
+            let logout = FixMessage::new(msg_type::LOGOUT)
+                .with(tags::TEXT, format!("{} is already logged on", comp_id));
+            writer
+                .write_all(&logout.encode(&self.config.comp_id, &comp_id, 1))
+                .await?;
+            return Ok(());
+        }
+
+        let mut state = self.sessions.lock().remove(&comp_id).unwrap_or_default();
+        if reset {
+            state.next_inbound = 1;
+            state.next_outbound = 1;
+        }
+
+        let mut session = Session {
+            acceptor: self,
+            comp_id: comp_id.clone(),
+            writer,
+            state,
+            heartbeat: Duration::from_secs(heartbeat),
+            last_sent: Instant::now(),
+            last_received: Instant::now(),
+            test_request_sent: false,
+            resend_requested: false,
+            next_request_id: 1,
+            pending: HashMap::new(),
+        };
+
+        let result = async {
+            if msg_seq_num < session.state.next_inbound {
+                let text = format!(
+                    "MsgSeqNum too low, expecting {} but received {}",
+                    session.state.next_inbound, msg_seq_num
+                );
+                session.logout(&text).await?;
+                return Ok(());
+            }
+
+            let mut reply = FixMessage::new(msg_type::LOGON)
+                .with(tags::ENCRYPT_METHOD, 0)
+                .with(tags::HEART_BT_INT, heartbeat);
+            if reset {
+                reply.set(tags::RESET_SEQ_NUM_FLAG, "Y");
+            }
+            session.send(reply).await?;
+            info!("FIX session {} logged on from {}", comp_id, peer);
+
+            if msg_seq_num > session.state.next_inbound {
+                session.request_resend().await?;
+            } else {
+                session.state.next_inbound += 1;
+            }
+
+            loop {
+                tokio::select! {
+                    raw = inbound.recv() => match raw {
+                        Some(raw) => {
+                            if !session.on_message(&raw?).await? {
+                                break;
+                            }
+                        }
+                        None => break,
+                    },
+                    Some(report) = reports.recv() => session.on_report(report).await?,
+                    _ = sleep_until(session.next_deadline()) => {
+                        if !session.on_timer().await? {
+                            break;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        .await;
+
+        // Return the state before unregistering, so a reconnect finds it
+        self.sessions.lock().insert(comp_id.clone(), session.state);
+        self.gateway.unregister_session(&comp_id, &report_tx);
+        info!("FIX session {} logged off", comp_id);
+
+        result
+    }
+}
+
+/// One logged-on FIX connection
+struct Session<'a> {
+    acceptor: &'a FixAcceptor,
+    comp_id: String,
+    writer: OwnedWriteHalf,
+    state: SessionState,
+    heartbeat: Duration,
+    last_sent: Instant,
+    last_received: Instant,
+    test_request_sent: bool,
+    resend_requested: bool,
+    next_request_id: u64,
+    pending: HashMap<u64, PendingRequest>,
+}
+
+impl Session<'_> {
+    async fn send(&mut self, message: FixMessage) -> io::Result<()> {
+        let seq = self.state.next_outbound;
+        self.state.next_outbound += 1;
+        self.write(&message, seq).await
+    }
+
+    async fn write(&mut self, message: &FixMessage, seq: u64) -> io::Result<()> {
+        let bytes = message.encode(&self.acceptor.config.comp_id, &self.comp_id, seq);
+        self.writer.write_all(&bytes).await?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    async fn logout(&mut self, text: &str) -> io::Result<()> {
+        warn!("Logging out FIX session {}: {}", self.comp_id, text);
+        self.send(FixMessage::new(msg_type::LOGOUT).with(tags::TEXT, text))
+            .await
+    }
+
+    async fn request_resend(&mut self) -> io::Result<()> {
+        if self.resend_requested {
+            return Ok(());
+        }
+        self.resend_requested = true;
+        let request = FixMessage::new(msg_type::RESEND_REQUEST)
+            .with(tags::BEGIN_SEQ_NO, self.state.next_inbound)
+            .with(tags::END_SEQ_NO, 0);
+        self.send(request).await
+    }
+
+    async fn reject_message(
+        &mut self,
+        ref_seq_num: u64,
+        ref_tag_id: Option<u32>,
+        reason: u32,
+        text: &str,
+    ) -> io::Result<()> {
+        let mut reject = FixMessage::new(msg_type::REJECT)
+            .with(tags::REF_SEQ_NUM, ref_seq_num)
+            .with(tags::SESSION_REJECT_REASON, reason)
+            .with(tags::TEXT, text);
+        if let Some(tag) = ref_tag_id {
+            reject.set(tags::REF_TAG_ID, tag);
+        }
+        self.send(reject).await
+    }
+
+    /// When the timer branch of the session loop should fire next
+    fn next_deadline(&self) -> Instant {
+        let silence_limit = if self.test_request_sent {
+            self.heartbeat * 2 + self.heartbeat / 2
+        } else {
+            self.heartbeat + self.heartbeat / 2
+        };
+        (self.last_sent + self.heartbeat).min(self.last_received + silence_limit)
+    }
+
+    /// Send heartbeats and detect a silent counterparty; `false` ends the session
+    async fn on_timer(&mut self) -> io::Result<bool> {
+        let now = Instant::now();
+        let silent = now - self.last_received;
+
+        if self.test_request_sent && silent >= self.heartbeat * 2 + self.heartbeat / 2 {
+            self.logout("heartbeat timeout").await?;
+            return Ok(false);
+        }
+        if !self.test_request_sent && silent >= self.heartbeat + self.heartbeat / 2 {
+            self.test_request_sent = true;
+            let request = FixMessage::new(msg_type::TEST_REQUEST).with(
+                tags::TEST_REQ_ID,
+                format!("TEST-{}", self.state.next_outbound),
+            );
+            self.send(request).await?;
+        }
+        if now - self.last_sent >= self.heartbeat {
+            self.send(FixMessage::new(msg_type::HEARTBEAT)).await?;
+        }
+        Ok(true)
+    }
+
+    /// Handle one inbound message; `false` ends the session
+    async fn on_message(&mut self, raw: &[u8]) -> io::Result<bool> {
+        let message = match FixMessage::decode(raw) {
+            Ok(message) => message,
+            Err(e) => {
+                // Garbled messages are dropped without consuming a sequence number
+                warn!("Ignoring garbled message from {}: {}", self.comp_id, e);
+                return Ok(true);
+            }
+        };
+        self.last_received = Instant::now();
+        self.test_request_sent = false;
+
+        if message.get(tags::SENDER_COMP_ID) != Some(self.comp_id.as_str())
+            || message.get(tags::TARGET_COMP_ID) != Some(self.acceptor.config.comp_id.as_str())
+        {
+            self.logout("CompID problem").await?;
+            return Ok(false);
+        }
+        let seq = match message.get_u64(tags::MSG_SEQ_NUM) {
+            Some(seq) => seq,
+            None => {
+                self.logout("MsgSeqNum is required").await?;
+                return Ok(false);
+            }
+        };
+
+        // SequenceReset-Reset applies regardless of MsgSeqNum
+        if message.msg_type() == msg_type::SEQUENCE_RESET && !message.flag(tags::GAP_FILL_FLAG) {
+            match message.get_u64(tags::NEW_SEQ_NO) {
+                Some(new_seq) if new_seq >= self.state.next_inbound => {
+                    self.state.next_inbound = new_seq;
+                    self.resend_requested = false;
+                }
+                Some(_) => {
+                    self.reject_message(
+                        seq,
+                        Some(tags::NEW_SEQ_NO),
+                        VALUE_INCORRECT,
+                        "NewSeqNo may not lower the expected sequence number",
+                    )
+                    .await?
+                }
+                None => {
+                    self.reject_message(
+                        seq,
+                        Some(tags::NEW_SEQ_NO),
+                        REQUIRED_TAG_MISSING,
+                        "NewSeqNo is required",
+                    )
+                    .await?
+                }
+            }
+            return Ok(true);
+        }
+
+        if seq < self.state.next_inbound {
+            if message.flag(tags::POSS_DUP_FLAG) {
+                debug!("Ignoring possible duplicate {} from {}", seq, self.comp_id);
+                return Ok(true);
+            }
+            let text = format!(
+                "MsgSeqNum too low, expecting {} but received {}",
+                self.state.next_inbound, seq
+            );
+            self.logout(&text).await?;
+            return Ok(false);
+        }
+        if seq > self.state.next_inbound {
+            if message.msg_type() == msg_type::LOGOUT {
+                self.send(FixMessage::new(msg_type::LOGOUT)).await?;
+                return Ok(false);
+            }
+            self.request_resend().await?;
+            return Ok(true);
+        }
+
+        self.state.next_inbound += 1;
+        self.resend_requested = false;
+
+        match message.msg_type() {
+            msg_type::HEARTBEAT | msg_type::REJECT => {}
+            msg_type::TEST_REQUEST => {
+                let mut heartbeat = FixMessage::new(msg_type::HEARTBEAT);
+                if let Some(id) = message.get(tags::TEST_REQ_ID) {
+                    heartbeat.set(tags::TEST_REQ_ID, id);
+                }
+                self.send(heartbeat).await?;
+            }
+            msg_type::RESEND_REQUEST => {
+                let begin = message.get_u64(tags::BEGIN_SEQ_NO).unwrap_or(1);
+                let gap_fill = FixMessage::new(msg_type::SEQUENCE_RESET)
+                    .with(tags::POSS_DUP_FLAG, "Y")
+                    .with(tags::GAP_FILL_FLAG, "Y")
+                    .with(tags::NEW_SEQ_NO, self.state.next_outbound);
+                self.write(&gap_fill, begin).await?;
+            }
+            msg_type::SEQUENCE_RESET => {
+                // Gap fill: skip ahead, never back
+                if let Some(new_seq) = message.get_u64(tags::NEW_SEQ_NO) {
+                    self.state.next_inbound = self.state.next_inbound.max(new_seq);
+                }
+            }
+            msg_type::LOGOUT => {
+                self.send(FixMessage::new(msg_type::LOGOUT)).await?;
+                return Ok(false);
+            }
+            msg_type::LOGON => {
+                self.reject_message(seq, None, VALUE_INCORRECT, "already logged on")
+                    .await?
+            }
+            msg_type::NEW_ORDER_SINGLE => self.on_new_order(&message, seq).await?,
+            msg_type::ORDER_CANCEL_REQUEST => self.on_cancel(&message, seq, false).await?,
+            msg_type::ORDER_CANCEL_REPLACE_REQUEST => self.on_cancel(&message, seq, true).await?,
+            other => {
+                let text = format!("unsupported MsgType {}", other);
+                self.reject_message(seq, Some(tags::MSG_TYPE), INVALID_MSG_TYPE, &text)
+                    .await?
+            }
+        }
+        Ok(true)
+    }
+
+    async fn on_new_order(&mut self, message: &FixMessage, seq: u64) -> io::Result<()> {
+        let required = [
+            tags::CL_ORD_ID,
+            tags::SYMBOL,
+            tags::SIDE,
+            tags::ORDER_QTY,
+            tags::ORD_TYPE,
+        ];
+        if let Some(&tag) = required.iter().find(|&&tag| message.get(tag).is_none()) {
+            let text = format!("tag {} is required", tag);
+            return self
+                .reject_message(seq, Some(tag), REQUIRED_TAG_MISSING, &text)
+                .await;
+        }
+
+        let side = match message.get(tags::SIDE) {
+            Some("1") => Side::Buy,
+            Some("2") => Side::Sell,
+            _ => {
+                return self
+                    .reject_message(
+                        seq,
+                        Some(tags::SIDE),
+                        VALUE_INCORRECT,
+                        "Side must be 1 or 2",
+                    )
+                    .await
+            }
+        };
+        let ord_type = message.get(tags::ORD_TYPE).unwrap_or_default();
+        let kind = match ord_type {
+            "1" => OrderKind::Market,
+            "2" => OrderKind::Limit,
+            "3" => OrderKind::Stop,
+            "4" => OrderKind::StopLimit,
+            "K" => OrderKind::MarketToLimit,
+            _ => {
+                return self
+                    .reject_message(
+                        seq,
+                        Some(tags::ORD_TYPE),
+                        VALUE_INCORRECT,
+                        "OrdType must be 1, 2, 3, 4 or K",
+                    )
+                    .await
+            }
+        };
+        let order_qty = match message.get_u64(tags::ORDER_QTY) {
+            Some(qty) => qty,
+            None => {
+                return self
+                    .reject_message(
+                        seq,
+                        Some(tags::ORDER_QTY),
+                        VALUE_INCORRECT,
+                        "OrderQty must be a whole number",
+                    )
+                    .await
+            }
+        };
+
+        let needs_price = matches!(kind, OrderKind::Limit | OrderKind::StopLimit);
+        let needs_stop = matches!(kind, OrderKind::Stop | OrderKind::StopLimit);
+        let price = match self.price_field(message, tags::PRICE, needs_price) {
+            Ok(price) => price,
+            Err(tag) => return self.reject_price(seq, tag).await,
+        };
+        let stop_price = match self.price_field(message, tags::STOP_PX, needs_stop) {
+            Ok(price) => price,
+            Err(tag) => return self.reject_price(seq, tag).await,
+        };
+        let time_in_force = match message.get(tags::TIME_IN_FORCE) {
+            None | Some("1") => TimeInForce::GoodTillCancel,
+            Some("0") => TimeInForce::Day,
+            Some("6") => match message.get(tags::EXPIRE_TIME).map(parse_utc_timestamp) {
+                Some(Some(expire_time)) => TimeInForce::GoodTillDate(expire_time),
+                Some(None) => {
+                    return self
+                        .reject_message(
+                            seq,
+                            Some(tags::EXPIRE_TIME),
+                            VALUE_INCORRECT,
+                            "ExpireTime must be a UTCTimestamp",
+                        )
+                        .await
+                }
+                None => {
+                    return self
+                        .reject_message(
+                            seq,
+                            Some(tags::EXPIRE_TIME),
+                            REQUIRED_TAG_MISSING,
+                            "ExpireTime is required when TimeInForce is 6",
+                        )
+                        .await
+                }
+            },
+            Some(_) => {
+                return self
+                    .reject_message(
+                        seq,
+                        Some(tags::TIME_IN_FORCE),
+                        VALUE_INCORRECT,
+                        "TimeInForce must be 0, 1 or 6",
+                    )
+                    .await
+            }
+        };
+
+        let order = FixOrder {
+            cl_ord_id: message.get(tags::CL_ORD_ID).unwrap_or_default().to_string(),
+            symbol: message.get(tags::SYMBOL).unwrap_or_default().to_string(),
+            side,
+            ord_type: ord_type.to_string(),
+            order_qty,
+            price: price.filter(|_| needs_price),
+            stop_price: stop_price.filter(|_| needs_stop),
+            cum_qty: 0,
+            leaves_qty: order_qty,
+            notional: 0,
+        };
+
+        if self.cl_ord_id_in_use(&order.cl_ord_id) {
+            let error = OrderBookError::DuplicateOrder;
+            let report = self.rejected_order(&order, error.code(), &error.to_string());
+            return self.send(report).await;
+        }
+
+        let request_id = self.next_request_id();
+        let request = ClientMessage::NewOrder {
+            request_id,
+            symbol: order.symbol.clone(),
+            side,
+            kind,
+            price: order.price.unwrap_or(0),
+            stop_price: order.stop_price.unwrap_or(0),
+            quantity: order_qty,
+            time_in_force,
+        };
+        self.pending.insert(request_id, PendingRequest::New(order));
+        self.acceptor.gateway.submit(&self.comp_id, request).await;
+        Ok(())
+    }
+
+    /// Handle OrderCancelRequest, or OrderCancelReplaceRequest if `replace`
+    async fn on_cancel(&mut self, message: &FixMessage, seq: u64, replace: bool) -> io::Result<()> {
+        let required: &[u32] = if replace {
+            &[tags::CL_ORD_ID, tags::ORIG_CL_ORD_ID, tags::ORDER_QTY]
+        } else {
+            &[tags::CL_ORD_ID, tags::ORIG_CL_ORD_ID]
+        };
+        if let Some(&tag) = required.iter().find(|&&tag| message.get(tag).is_none()) {
+            let text = format!("tag {} is required", tag);
+            return self
+                .reject_message(seq, Some(tag), REQUIRED_TAG_MISSING, &text)
+                .await;
+        }
+
+        let cl_ord_id = message.get(tags::CL_ORD_ID).unwrap_or_default().to_string();
+        let orig_cl_ord_id = message
+            .get(tags::ORIG_CL_ORD_ID)
+            .unwrap_or_default()
+            .to_string();
+
+        let order_id = match self.state.cl_ord_ids.get(&orig_cl_ord_id) {
+            Some(order_id) => *order_id,
+            None => {
+                let error = OrderBookError::OrderNotFound;
+                let reject = self.cancel_reject(
+                    None,
+                    &cl_ord_id,
+                    &orig_cl_ord_id,
+                    replace,
+                    error.code(),
+                    &error.to_string(),
+                );
+                return self.send(reject).await;
+            }
+        };
+        if self.cl_ord_id_in_use(&cl_ord_id) {
+            let error = OrderBookError::DuplicateOrder;
+            let reject = self.cancel_reject(
+                Some(order_id),
+                &cl_ord_id,
+                &orig_cl_ord_id,
+                replace,
+                error.code(),
+                &error.to_string(),
+            );
+            return self.send(reject).await;
+        }
+
+        let request_id = self.next_request_id();
+        if !replace {
+            self.pending.insert(
+                request_id,
+                PendingRequest::Cancel {
+                    cl_ord_id,
+                    orig_cl_ord_id,
+                },
+            );
+            let request = ClientMessage::Cancel {
+                request_id,
+                order_id,
+            };
+            self.acceptor.gateway.submit(&self.comp_id, request).await;
+            return Ok(());
+        }
+
+        let order = &self.state.orders[&order_id];
+        let order_qty = message.get_u64(tags::ORDER_QTY);
+        let new_price = message
+            .get(tags::PRICE)
+            .map(|price| price_to_ticks(price, self.acceptor.config.price_decimals));
+        let error = match (order_qty, new_price) {
+            (None, _) => Some(OrderBookError::InvalidQuantity),
+            (Some(qty), _) if qty <= order.cum_qty => Some(OrderBookError::InvalidQuantity),
+            (_, Some(price)) if price != order.price => Some(OrderBookError::InvalidPrice),
+            _ => None,
+        };
+        if let Some(error) = error {
+            let reject = self.cancel_reject(
+                Some(order_id),
+                &cl_ord_id,
+                &orig_cl_ord_id,
+                replace,
+                error.code(),
+                &error.to_string(),
+            );
+            return self.send(reject).await;
+        }
+
+        // The engine amends the open quantity; OrderQty includes what has filled
+        let order_qty = order_qty.unwrap_or_default();
+        let request = ClientMessage::Modify {
+            request_id,
+            order_id,
+            quantity: order_qty - order.cum_qty,
+        };
+        self.pending.insert(
+            request_id,
+            PendingRequest::Replace {
+                cl_ord_id,
+                orig_cl_ord_id,
+                order_id,
+                order_qty,
+            },
+        );
+        self.acceptor.gateway.submit(&self.comp_id, request).await;
+        Ok(())
+    }
+
+    /// Turn a gateway report into ExecutionReport or OrderCancelReject
+    async fn on_report(&mut self, report: ServerMessage) -> io::Result<()> {
+        match report {
+            ServerMessage::Ack {
+                request_id,
+                order_id,
+            } => match self.pending.remove(&request_id) {
+                Some(PendingRequest::New(order)) => {
+                    let report = self.execution_report(Some(order_id), &order, "0", "0");
+                    self.state
+                        .cl_ord_ids
+                        .insert(order.cl_ord_id.clone(), order_id);
+                    self.state.orders.insert(order_id, order);
+                    self.send(report).await?;
+                }
+                Some(PendingRequest::Replace {
+                    cl_ord_id,
+                    orig_cl_ord_id,
+                    order_id,
+                    order_qty,
+                }) => {
+                    let Some(order) = self.state.orders.get_mut(&order_id) else {
+                        return Ok(());
+                    };
+                    order.cl_ord_id = cl_ord_id.clone();
+                    order.order_qty = order_qty;
+                    order.leaves_qty = order_qty - order.cum_qty;
+                    let order = order.clone();
+
+                    self.state.cl_ord_ids.remove(&orig_cl_ord_id);
+                    self.state.cl_ord_ids.insert(cl_ord_id, order_id);
+                    let mut report =
+                        self.execution_report(Some(order_id), &order, "5", order.status());
+                    report.set(tags::ORIG_CL_ORD_ID, orig_cl_ord_id);
+                    self.send(report).await?;
+                }
+                _ => {}
+            },
+            ServerMessage::Reject {
+                request_id,
+                order_id,
+                code,
+                reason,
+            } => {
+                let message = match self.pending.remove(&request_id) {
+                    Some(PendingRequest::New(order)) => self.rejected_order(&order, code, &reason),
+                    Some(PendingRequest::Cancel {
+                        cl_ord_id,
+                        orig_cl_ord_id,
+                    }) => self.cancel_reject(
+                        order_id,
+                        &cl_ord_id,
+                        &orig_cl_ord_id,
+                        false,
+                        code,
+                        &reason,
+                    ),
+                    Some(PendingRequest::Replace {
+                        cl_ord_id,
+                        orig_cl_ord_id,
+                        ..
+                    }) => self.cancel_reject(
+                        order_id,
+                        &cl_ord_id,
+                        &orig_cl_ord_id,
+                        true,
+                        code,
+                        &reason,
+                    ),
+                    None => return Ok(()),
+                };
+                self.send(message).await?;
+            }
+            ServerMessage::Fill {
+                order_id,
+                trade_id,
+                price,
+                quantity,
+                leaves_quantity,
+            } => {
+                let Some(order) = self.state.orders.get_mut(&order_id) else {
+                    debug!("Fill for unknown order {} on {}", order_id, self.comp_id);
+                    return Ok(());
+                };
+                order.cum_qty += quantity;
+                order.leaves_qty = leaves_quantity;
+                order.notional += price as u128 * quantity as u128;
+                let order = order.clone();
+                if leaves_quantity == 0 {
+                    self.forget(&order_id);
+                }
+
+                let decimals = self.acceptor.config.price_decimals;
+                let status = if leaves_quantity == 0 { "2" } else { "1" };
+                let mut report = self.execution_report(Some(order_id), &order, "F", status);
+                report.set(tags::EXEC_ID, trade_id);
+                report.set(tags::LAST_PX, ticks_to_price(price, decimals));
+                report.set(tags::LAST_QTY, quantity);
+                self.send(report).await?;
+            }
+            ServerMessage::Cancelled {
+                request_id,
+                order_id,
+                ..
+            } => {
+                let Some(mut order) = self.forget(&order_id) else {
+                    return Ok(());
+                };
+                order.leaves_qty = 0;
+
+                let mut orig_cl_ord_id = None;
+                if let Some(PendingRequest::Cancel {
+                    cl_ord_id,
+                    orig_cl_ord_id: orig,
+                }) = self.pending.remove(&request_id)
+                {
+                    order.cl_ord_id = cl_ord_id;
+                    orig_cl_ord_id = Some(orig);
+                }
+
+                let mut report = self.execution_report(Some(order_id), &order, "4", "4");
+                if let Some(orig) = orig_cl_ord_id {
+                    report.set(tags::ORIG_CL_ORD_ID, orig);
+                }
+                self.send(report).await?;
+            }
+            ServerMessage::LogonAccepted { .. } | ServerMessage::LogonRejected { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn execution_report(
+        &self,
+        order_id: Option<OrderId>,
+        order: &FixOrder,
+        exec_type: &str,
+        ord_status: &str,
+    ) -> FixMessage {
+        let decimals = self.acceptor.config.price_decimals;
+        let mut report = FixMessage::new(msg_type::EXECUTION_REPORT)
+            .with(tags::ORDER_ID, order_id_field(order_id))
+            .with(tags::CL_ORD_ID, &order.cl_ord_id)
+            .with(tags::EXEC_ID, Uuid::new_v4())
+            .with(tags::EXEC_TYPE, exec_type)
+            .with(tags::ORD_STATUS, ord_status)
+            .with(tags::SYMBOL, &order.symbol)
+            .with(tags::SIDE, side_field(order.side))
+            .with(tags::ORD_TYPE, &order.ord_type)
+            .with(tags::ORDER_QTY, order.order_qty)
+            .with(tags::LEAVES_QTY, order.leaves_qty)
+            .with(tags::CUM_QTY, order.cum_qty)
+            .with(tags::AVG_PX, order.avg_px(decimals))
+            .with(tags::TRANSACT_TIME, utc_timestamp());
+        if let Some(price) = order.price {
+            report.set(tags::PRICE, ticks_to_price(price, decimals));
+        }
+        if let Some(stop_price) = order.stop_price {
+            report.set(tags::STOP_PX, ticks_to_price(stop_price, decimals));
+        }
+        report
+    }
+
+    /// ExecutionReport rejecting a new order with an `OrderBookError::code`
+    fn rejected_order(&self, order: &FixOrder, code: u16, text: &str) -> FixMessage {
+        let mut order = order.clone();
+        order.leaves_qty = 0;
+        let mut report = self.execution_report(None, &order, "8", "8");
+        report.set(tags::ORD_REJ_REASON, ord_rej_reason(code));
+        report.set(tags::TEXT, text);
+        report
+    }
+
+    fn cancel_reject(
+        &self,
+        order_id: Option<OrderId>,
+        cl_ord_id: &str,
+        orig_cl_ord_id: &str,
+        replace: bool,
+        code: u16,
+        text: &str,
+    ) -> FixMessage {
+        let status = order_id
+            .and_then(|id| self.state.orders.get(&id))
+            .map_or("8", FixOrder::status);
+        let cxl_rej_reason = if code == OrderBookError::OrderNotFound.code() {
+            1
+        } else {
+            99
+        };
+
+        FixMessage::new(msg_type::ORDER_CANCEL_REJECT)
+            .with(tags::ORDER_ID, order_id_field(order_id))
+            .with(tags::CL_ORD_ID, cl_ord_id)
+            .with(tags::ORIG_CL_ORD_ID, orig_cl_ord_id)
+            .with(tags::ORD_STATUS, status)
+            .with(tags::CXL_REJ_RESPONSE_TO, if replace { 2 } else { 1 })
+            .with(tags::CXL_REJ_REASON, cxl_rej_reason)
+            .with(tags::TEXT, text)
+    }
+
+    /// Parse an optional price field; `Err` carries the offending tag
+    fn price_field(
+        &self,
+        message: &FixMessage,
+        tag: u32,
+        required: bool,
+    ) -> Result<Option<Price>, u32> {
+        match message.get(tag) {
+            Some(price) => price_to_ticks(price, self.acceptor.config.price_decimals)
+                .map(Some)
+                .ok_or(tag),
+            None if required => Err(tag),
+            None => Ok(None),
+        }
+    }
+
+    async fn reject_price(&mut self, seq: u64, tag: u32) -> io::Result<()> {
+        let text = format!(
+            "tag {} must be a price with at most {} decimals",
+            tag, self.acceptor.config.price_decimals
+        );
+        self.reject_message(seq, Some(tag), VALUE_INCORRECT, &text)
+            .await
+    }
+
+    fn cl_ord_id_in_use(&self, cl_ord_id: &str) -> bool {
+        self.state.cl_ord_ids.contains_key(cl_ord_id)
+            || self.pending.values().any(|pending| match pending {
+                PendingRequest::New(order) => order.cl_ord_id == cl_ord_id,
+                PendingRequest::Cancel { cl_ord_id: id, .. }
+                | PendingRequest::Replace { cl_ord_id: id, .. } => id == cl_ord_id,
+            })
+    }
+
+    fn next_request_id(&mut self) -> u64 {
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        request_id
+    }
+
+    /// Drop a closed order from the session state
+    fn forget(&mut self, order_id: &OrderId) -> Option<FixOrder> {
+        let order = self.state.orders.remove(order_id)?;
+        self.state.cl_ord_ids.remove(&order.cl_ord_id);
+        Some(order)
+    }
+}
+
+fn order_id_field(order_id: Option<OrderId>) -> String {
+    order_id.map_or_else(|| "NONE".to_string(), |id| id.to_string())
+}
+
+fn side_field(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "1",
+        Side::Sell => "2",
+    }
+}
+
+/// OrdRejReason (103) for an `OrderBookError::code`
+fn ord_rej_reason(code: u16) -> u32 {
+    match code {
+        2 | 14 => 1,  // unknown symbol
+        7 => 6,       // duplicate order
+        11 | 20 => 3, // order exceeds limit or risk check
+        _ => 99,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::fix_initiator::FixInitiator;
+    use crate::orderbook::book::OrderBook;
+    use crate::orderbook::exchange::{Exchange, InstrumentConfig};
+
+    async fn start_acceptor() -> (Arc<OrderBook>, SocketAddr) {
+        let exchange = Arc::new(Exchange::new());
+        let book = exchange
+            .add_instrument(InstrumentConfig::new("TEST"))
+            .unwrap();
+        let gateway = Arc::new(Gateway::new(exchange));
+        let acceptor = Arc::new(FixAcceptor::new(gateway, FixConfig::default()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(acceptor.run(listener));
+        (book, addr)
+    }
+
+    fn limit(cl_ord_id: &str, side: &str, price: &str, quantity: u64) -> FixMessage {
+        FixMessage::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tags::CL_ORD_ID, cl_ord_id)
+            .with(tags::SYMBOL, "TEST")
+            .with(tags::SIDE, side)
+            .with(tags::ORD_TYPE, "2")
+            .with(tags::ORDER_QTY, quantity)
+            .with(tags::PRICE, price)
+            .with(tags::TRANSACT_TIME, utc_timestamp())
+    }
+
+    #[tokio::test]
+    async fn test_order_entry_and_execution_reports() {
+        let (book, addr) = start_acceptor().await;
+        let mut seller = FixInitiator::connect(addr, "SELLER", "ORDERBOOK", 30)
+            .await
+            .unwrap();
+        let mut buyer = FixInitiator::connect(addr, "BUYER", "ORDERBOOK", 30)
+            .await
+            .unwrap();
+
+        seller.send(limit("s-1", "2", "100.50", 100)).await.unwrap();
+        let new = seller.recv_app().await.unwrap();
+        assert_eq!(new.msg_type(), msg_type::EXECUTION_REPORT);
+        assert_eq!(new.get(tags::EXEC_TYPE), Some("0"));
+        assert_eq!(new.get(tags::CL_ORD_ID), Some("s-1"));
+        assert_eq!(book.best_ask(), Some(10_050));
+        let order_id = new.get(tags::ORDER_ID).unwrap().to_string();
+
+        buyer.send(limit("b-1", "1", "100.50", 40)).await.unwrap();
+        assert_eq!(
+            buyer.recv_app().await.unwrap().get(tags::EXEC_TYPE),
+            Some("0")
+        );
+        let fill = buyer.recv_app().await.unwrap();
+        assert_eq!(fill.get(tags::EXEC_TYPE), Some("F"));
+        assert_eq!(fill.get(tags::ORD_STATUS), Some("2"));
+        assert_eq!(fill.get(tags::LAST_PX), Some("100.50"));
+
+        let fill = seller.recv_app().await.unwrap();
+        assert_eq!(fill.get(tags::ORD_STATUS), Some("1"));
+        assert_eq!(fill.get(tags::CUM_QTY), Some("40"));
+        assert_eq!(fill.get(tags::LEAVES_QTY), Some("60"));
+        assert_eq!(fill.get(tags::AVG_PX), Some("100.500000"));
+
+        // Replace down to 70 total (30 open), then cancel under the new ClOrdID
+        let replace = FixMessage::new(msg_type::ORDER_CANCEL_REPLACE_REQUEST)
+            .with(tags::ORIG_CL_ORD_ID, "s-1")
+            .with(tags::CL_ORD_ID, "s-2")
+            .with(tags::ORDER_QTY, 70);
+        seller.send(replace).await.unwrap();
+        let replaced = seller.recv_app().await.unwrap();
+        assert_eq!(replaced.get(tags::EXEC_TYPE), Some("5"));
+        assert_eq!(replaced.get(tags::LEAVES_QTY), Some("30"));
+        assert_eq!(replaced.get(tags::ORDER_ID), Some(order_id.as_str()));
+
+        let cancel = FixMessage::new(msg_type::ORDER_CANCEL_REQUEST)
+            .with(tags::ORIG_CL_ORD_ID, "s-1")
+            .with(tags::CL_ORD_ID, "s-3");
+        seller.send(cancel).await.unwrap();
+        let reject = seller.recv_app().await.unwrap();
+        assert_eq!(reject.msg_type(), msg_type::ORDER_CANCEL_REJECT);
+        assert_eq!(reject.get(tags::CXL_REJ_RESPONSE_TO), Some("1"));
+
+        let cancel = FixMessage::new(msg_type::ORDER_CANCEL_REQUEST)
+            .with(tags::ORIG_CL_ORD_ID, "s-2")
+            .with(tags::CL_ORD_ID, "s-4");
+        seller.send(cancel).await.unwrap();
+        let cancelled = seller.recv_app().await.unwrap();
+        assert_eq!(cancelled.get(tags::EXEC_TYPE), Some("4"));
+        assert_eq!(cancelled.get(tags::CL_ORD_ID), Some("s-4"));
+        assert_eq!(cancelled.get(tags::ORIG_CL_ORD_ID), Some("s-2"));
+        assert_eq!(book.total_orders(), 0);
+
+        // Unknown symbols are rejected by the engine
+        let mut order = limit("b-2", "1", "1", 1);
+        order.set(tags::SYMBOL, "NOPE");
+        buyer.send(order).await.unwrap();
+        let rejected = buyer.recv_app().await.unwrap();
+        assert_eq!(rejected.get(tags::EXEC_TYPE), Some("8"));
+        assert_eq!(rejected.get(tags::ORD_REJ_REASON), Some("1"));
+
+        // A DAY order is cancelled unsolicited when the session closes
+        let mut order = limit("b-3", "1", "99", 10);
+        order.set(tags::TIME_IN_FORCE, "0");
+        buyer.send(order).await.unwrap();
+        assert_eq!(
+            buyer.recv_app().await.unwrap().get(tags::EXEC_TYPE),
+            Some("0")
+        );
+        book.close_session().unwrap();
+        let expired = buyer.recv_app().await.unwrap();
+        assert_eq!(expired.get(tags::EXEC_TYPE), Some("4"));
+        assert_eq!(expired.get(tags::CL_ORD_ID), Some("b-3"));
+        assert_eq!(book.total_orders(), 0);
+
+        seller.logout().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_level_messages() {
+        let (_book, addr) = start_acceptor().await;
+
+        // Wrong TargetCompID is refused with a Logout
+        let err = FixInitiator::connect(addr, "CLIENT", "ELSEWHERE", 30)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("TargetCompID"));
+
+        // The acceptor heartbeats an idle session and answers test requests
+        let mut client = FixInitiator::connect(addr, "CLIENT", "ORDERBOOK", 1)
+            .await
+            .unwrap();
+        assert_eq!(client.recv().await.unwrap().msg_type(), msg_type::HEARTBEAT);
+        client
+            .send(FixMessage::new(msg_type::TEST_REQUEST).with(tags::TEST_REQ_ID, "ping"))
+            .await
+            .unwrap();
+        let heartbeat = client.recv().await.unwrap();
+        assert_eq!(heartbeat.msg_type(), msg_type::HEARTBEAT);
+        assert_eq!(heartbeat.get(tags::TEST_REQ_ID), Some("ping"));
+
+        // Missing required tags get a session-level Reject
+        client
+            .send(FixMessage::new(msg_type::NEW_ORDER_SINGLE).with(tags::CL_ORD_ID, "x"))
+            .await
+            .unwrap();
+        let reject = client.recv_app().await.unwrap();
+        assert_eq!(reject.msg_type(), msg_type::REJECT);
+        assert_eq!(reject.get(tags::REF_TAG_ID), Some("55"));
+
+        // Good-till-date needs an ExpireTime
+        let mut order = limit("gtd", "1", "99", 10);
+        order.set(tags::TIME_IN_FORCE, "6");
+        client.send(order).await.unwrap();
+        let reject = client.recv_app().await.unwrap();
+        assert_eq!(reject.msg_type(), msg_type::REJECT);
+        assert_eq!(reject.get(tags::REF_TAG_ID), Some("126"));
+    }
+
+    #[tokio::test]
+    async fn test_sequence_gap_and_reset() {
+        let (book, addr) = start_acceptor().await;
+        let mut client = FixInitiator::connect(addr, "CLIENT", "ORDERBOOK", 30)
+            .await
+            .unwrap();
+
+        // Skipping 2..=4 makes the acceptor ask for them and drop the order
+        client.set_next_outbound(5);
+        client.send(limit("c-1", "1", "99", 10)).await.unwrap();
+        let resend = client.recv().await.unwrap();
+        assert_eq!(resend.msg_type(), msg_type::RESEND_REQUEST);
+        assert_eq!(resend.get(tags::BEGIN_SEQ_NO), Some("2"));
+        assert_eq!(book.total_orders(), 0);
+
+        // Gap fill 2..=4, then resend the order as seq 5
+        client.set_next_outbound(2);
+        let gap_fill = FixMessage::new(msg_type::SEQUENCE_RESET)
+            .with(tags::GAP_FILL_FLAG, "Y")
+            .with(tags::NEW_SEQ_NO, 5);
+        client.send(gap_fill).await.unwrap();
+        client.set_next_outbound(5);
+        client
+            .send(limit("c-1", "1", "99", 10).with(tags::POSS_DUP_FLAG, "Y"))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.recv_app().await.unwrap().get(tags::EXEC_TYPE),
+            Some("0")
+        );
+        assert_eq!(book.total_orders(), 1);
+
+        // Reconnecting without a reset continues the sequence; a stale
+        // MsgSeqNum is refused
+        client.logout().await.unwrap();
+        let err = FixInitiator::connect_at(addr, "CLIENT", "ORDERBOOK", 30, 3)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("MsgSeqNum too low"));
+
+        let mut client = FixInitiator::connect_at(addr, "CLIENT", "ORDERBOOK", 30, 7)
+            .await
+            .unwrap();
+        let cancel = FixMessage::new(msg_type::ORDER_CANCEL_REQUEST)
+            .with(tags::ORIG_CL_ORD_ID, "c-1")
+            .with(tags::CL_ORD_ID, "c-2");
+        client.send(cancel).await.unwrap();
+        assert_eq!(
+            client.recv_app().await.unwrap().get(tags::EXEC_TYPE),
+            Some("4")
+        );
+        assert_eq!(book.total_orders(), 0);
+    }
+}
@@ -0,0 +1,145 @@
+//! Minimal in-process FIX 4.4 initiator
+//!
+//! Enough of a FIX client to drive the [`FixAcceptor`](crate::gateway::FixAcceptor)
+//! from tests and tools: it logs on, stamps outbound headers, answers test
+//! requests and logs out. It does not validate inbound sequence numbers or
+//! send heartbeats on its own.
+
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+
+use crate::gateway::fix::{msg_type, read_message, tags, FixMessage};
+
+#[derive(Debug)]
+pub struct FixInitiator {
+    sender_comp_id: String,
+    target_comp_id: String,
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    next_outbound: u64,
+}
+
+impl FixInitiator {
+    /// Connect and log on with ResetSeqNumFlag, starting both sides at 1
+    pub async fn connect(
+        addr: SocketAddr,
+        sender_comp_id: &str,
+        target_comp_id: &str,
+        heartbeat_secs: u64,
+    ) -> io::Result<Self> {
+        Self::logon(addr, sender_comp_id, target_comp_id, heartbeat_secs, None).await
+    }
+
+    /// Connect and log on continuing an earlier session at `msg_seq_num`
+    pub async fn connect_at(
+        addr: SocketAddr,
+        sender_comp_id: &str,
+        target_comp_id: &str,
+        heartbeat_secs: u64,
+        msg_seq_num: u64,
+    ) -> io::Result<Self> {
+        Self::logon(
+            addr,
+            sender_comp_id,
+            target_comp_id,
+            heartbeat_secs,
+            Some(msg_seq_num),
+        )
+        .await
+    }
+
+    async fn logon(
+        addr: SocketAddr,
+        sender_comp_id: &str,
+        target_comp_id: &str,
+        heartbeat_secs: u64,
+        msg_seq_num: Option<u64>,
+    ) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+
+        let mut initiator = Self {
+            sender_comp_id: sender_comp_id.to_string(),
+            target_comp_id: target_comp_id.to_string(),
+            reader: BufReader::new(reader),
+            writer,
+            next_outbound: msg_seq_num.unwrap_or(1),
+        };
+
+        let mut logon = FixMessage::new(msg_type::LOGON)
+            .with(tags::ENCRYPT_METHOD, 0)
+            .with(tags::HEART_BT_INT, heartbeat_secs);
+        if msg_seq_num.is_none() {
+            logon.set(tags::RESET_SEQ_NUM_FLAG, "Y");
+        }
+        initiator.send(logon).await?;
+
+        let reply = initiator.recv().await?;
+        match reply.msg_type() {
+            msg_type::LOGON => Ok(initiator),
+            _ => Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!(
+                    "logon refused: {}",
+                    reply.get(tags::TEXT).unwrap_or(reply.msg_type())
+                ),
+            )),
+        }
+    }
+
+    /// Send a message with the next outbound sequence number, returning it
+    pub async fn send(&mut self, message: FixMessage) -> io::Result<u64> {
+        let seq = self.next_outbound;
+        let bytes = message.encode(&self.sender_comp_id, &self.target_comp_id, seq);
+        self.writer.write_all(&bytes).await?;
+        self.next_outbound += 1;
+        Ok(seq)
+    }
+
+    /// Override the next outbound sequence number
+    pub fn set_next_outbound(&mut self, seq: u64) {
+        self.next_outbound = seq;
+    }
+
+    /// Receive the next message of any type
+    pub async fn recv(&mut self) -> io::Result<FixMessage> {
+        match read_message(&mut self.reader).await? {
+            Some(raw) => FixMessage::decode(&raw),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "acceptor closed the connection",
+            )),
+        }
+    }
+
+    /// Receive the next message that is not a Heartbeat or TestRequest,
+    /// answering test requests on the way
+    pub async fn recv_app(&mut self) -> io::Result<FixMessage> {
+        loop {
+            let message = self.recv().await?;
+            match message.msg_type() {
+                msg_type::HEARTBEAT => {}
+                msg_type::TEST_REQUEST => {
+                    let mut heartbeat = FixMessage::new(msg_type::HEARTBEAT);
+                    if let Some(id) = message.get(tags::TEST_REQ_ID) {
+                        heartbeat.set(tags::TEST_REQ_ID, id);
+                    }
+                    self.send(heartbeat).await?;
+                }
+                _ => return Ok(message),
+            }
+        }
+    }
+
+    /// Log out and wait for the acceptor to close the connection
+    pub async fn logout(mut self) -> io::Result<()> {
+        self.send(FixMessage::new(msg_type::LOGOUT)).await?;
+        while self.recv_app().await?.msg_type() != msg_type::LOGOUT {}
+        while read_message(&mut self.reader).await?.is_some() {}
+        Ok(())
+    }
+}
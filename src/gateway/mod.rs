@@ -0,0 +1,19 @@
+//! Order-entry gateways
+//!
+//! Clients connect over TCP, log on with a client ID and exchange
+//! length-prefixed binary messages (see [`protocol`]) to enter, cancel and
+//! modify orders. Execution reports are routed back to the session that owns
+//! each order. FIX 4.4 counterparties connect through the [`FixAcceptor`],
+//! which sits in front of the same [`Gateway`].
+
+pub mod fix;
+pub mod fix_acceptor;
+pub mod fix_initiator;
+pub mod protocol;
+pub mod server;
+
+pub use fix::FixMessage;
+pub use fix_acceptor::{FixAcceptor, FixConfig};
+pub use fix_initiator::FixInitiator;
+pub use protocol::{ClientMessage, OrderKind, ServerMessage};
+pub use server::Gateway;
@@ -0,0 +1,584 @@
+//! Binary order-entry protocol
+//!
+//! Every message travels in a frame: a big-endian `u32` payload length
+//! followed by the payload. The first payload byte is the message type; the
+//! remaining fields are fixed-width big-endian integers, 16-byte UUIDs, and
+//! strings encoded as a `u16` byte length followed by UTF-8. A time in
+//! force is a byte (0 GTC, 1 DAY, 2 GTD) followed by the good-till-date
+//! expiry as a `u64` of milliseconds since the Unix epoch, 0 unless GTD.
+//!
+//! | Type   | Direction | Message            |
+//! |--------|-----------|--------------------|
+//! | `0x01` | client    | `Logon`            |
+//! | `0x02` | client    | `NewOrder`         |
+//! | `0x03` | client    | `Cancel`           |
+//! | `0x04` | client    | `Modify`           |
+//! | `0x05` | client    | `MassCancel`       |
+//! | `0x81` | server    | `LogonAccepted`    |
+//! | `0x82` | server    | `LogonRejected`    |
+//! | `0x83` | server    | `Ack`              |
+//! | `0x84` | server    | `Reject`           |
+//! | `0x85` | server    | `Fill`             |
+//! | `0x86` | server    | `Cancelled`        |
+
+use chrono::DateTime;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::orderbook::types::{OrderId, Price, Quantity, Side, TimeInForce};
+
+/// Largest accepted frame payload
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+/// Order kinds accepted by `NewOrder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderKind {
+    Limit,
+    Market,
+    Stop,
+    StopLimit,
+    MarketToLimit,
+}
+
+/// Messages sent by a client session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientMessage {
+    Logon {
+        client_id: String,
+    },
+    NewOrder {
+        request_id: u64,
+        symbol: String,
+        side: Side,
+        kind: OrderKind,
+        /// Limit price (limit and stop-limit orders)
+        price: Price,
+        /// Trigger price (stop and stop-limit orders)
+        stop_price: Price,
+        quantity: Quantity,
+        time_in_force: TimeInForce,
+    },
+    Cancel {
+        request_id: u64,
+        order_id: OrderId,
+    },
+    Modify {
+        request_id: u64,
+        order_id: OrderId,
+        quantity: Quantity,
+    },
+    /// Cancel every open order of the session, optionally for one symbol
+    MassCancel {
+        request_id: u64,
+        symbol: Option<String>,
+    },
+}
+
+/// Messages sent by the gateway
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerMessage {
+    LogonAccepted {
+        client_id: String,
+    },
+    LogonRejected {
+        reason: String,
+    },
+    /// Request accepted; `order_id` is nil for mass cancel completion
+    Ack {
+        request_id: u64,
+        order_id: OrderId,
+    },
+    /// Request rejected with an `OrderBookError::code`
+    Reject {
+        request_id: u64,
+        order_id: Option<OrderId>,
+        code: u16,
+        reason: String,
+    },
+    Fill {
+        order_id: OrderId,
+        trade_id: Uuid,
+        price: Price,
+        quantity: Quantity,
+        leaves_quantity: Quantity,
+    },
+    /// Order left the book without filling completely; `request_id` is 0
+    /// when no request of the owner's caused it (an unfilled remainder, an
+    /// expiry)
+    Cancelled {
+        request_id: u64,
+        order_id: OrderId,
+        remaining_quantity: Quantity,
+    },
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(64);
+        match self {
+            ClientMessage::Logon { client_id } => {
+                buf.push(0x01);
+                put_str(&mut buf, client_id);
+            }
+            ClientMessage::NewOrder {
+                request_id,
+                symbol,
+                side,
+                kind,
+                price,
+                stop_price,
+                quantity,
+                time_in_force,
+            } => {
+                buf.push(0x02);
+                buf.extend_from_slice(&request_id.to_be_bytes());
+                put_str(&mut buf, symbol);
+                buf.push(encode_side(*side));
+                buf.push(encode_kind(*kind));
+                buf.extend_from_slice(&price.to_be_bytes());
+                buf.extend_from_slice(&stop_price.to_be_bytes());
+                buf.extend_from_slice(&quantity.to_be_bytes());
+                put_time_in_force(&mut buf, *time_in_force);
+            }
+            ClientMessage::Cancel {
+                request_id,
+                order_id,
+            } => {
+                buf.push(0x03);
+                buf.extend_from_slice(&request_id.to_be_bytes());
+                buf.extend_from_slice(order_id.as_bytes());
+            }
+            ClientMessage::Modify {
+                request_id,
+                order_id,
+                quantity,
+            } => {
+                buf.push(0x04);
+                buf.extend_from_slice(&request_id.to_be_bytes());
+                buf.extend_from_slice(order_id.as_bytes());
+                buf.extend_from_slice(&quantity.to_be_bytes());
+            }
+            ClientMessage::MassCancel { request_id, symbol } => {
+                buf.push(0x05);
+                buf.extend_from_slice(&request_id.to_be_bytes());
+                put_str(&mut buf, symbol.as_deref().unwrap_or(""));
+            }
+        }
+        buf
+    }
+
+    pub fn decode(payload: &[u8]) -> io::Result<Self> {
+        let mut decoder = Decoder::new(payload);
+        let message = match decoder.u8()? {
+            0x01 => ClientMessage::Logon {
+                client_id: decoder.string()?,
+            },
+            0x02 => ClientMessage::NewOrder {
+                request_id: decoder.u64()?,
+                symbol: decoder.string()?,
+                side: decode_side(decoder.u8()?)?,
+                kind: decode_kind(decoder.u8()?)?,
+                price: decoder.u64()?,
+                stop_price: decoder.u64()?,
+                quantity: decoder.u64()?,
+                time_in_force: decoder.time_in_force()?,
+            },
+            0x03 => ClientMessage::Cancel {
+                request_id: decoder.u64()?,
+                order_id: decoder.uuid()?,
+            },
+            0x04 => ClientMessage::Modify {
+                request_id: decoder.u64()?,
+                order_id: decoder.uuid()?,
+                quantity: decoder.u64()?,
+            },
+            0x05 => {
+                let request_id = decoder.u64()?;
+                let symbol = decoder.string()?;
+                ClientMessage::MassCancel {
+                    request_id,
+                    symbol: (!symbol.is_empty()).then_some(symbol),
+                }
+            }
+            other => {
+                return Err(invalid(format!(
+                    "unknown client message type {:#04x}",
+                    other
+                )))
+            }
+        };
+        decoder.finish()?;
+        Ok(message)
+    }
+}
+
+impl ServerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(64);
+        match self {
+            ServerMessage::LogonAccepted { client_id } => {
+                buf.push(0x81);
+                put_str(&mut buf, client_id);
+            }
+            ServerMessage::LogonRejected { reason } => {
+                buf.push(0x82);
+                put_str(&mut buf, reason);
+            }
+            ServerMessage::Ack {
+                request_id,
+                order_id,
+            } => {
+                buf.push(0x83);
+                buf.extend_from_slice(&request_id.to_be_bytes());
+                buf.extend_from_slice(order_id.as_bytes());
+            }
+            ServerMessage::Reject {
+                request_id,
+                order_id,
+                code,
+                reason,
+            } => {
+                buf.push(0x84);
+                buf.extend_from_slice(&request_id.to_be_bytes());
+                buf.extend_from_slice(order_id.unwrap_or_else(Uuid::nil).as_bytes());
+                buf.extend_from_slice(&code.to_be_bytes());
+                put_str(&mut buf, reason);
+            }
+            ServerMessage::Fill {
+                order_id,
+                trade_id,
+                price,
+                quantity,
+                leaves_quantity,
+            } => {
+                buf.push(0x85);
+                buf.extend_from_slice(order_id.as_bytes());
+                buf.extend_from_slice(trade_id.as_bytes());
+                buf.extend_from_slice(&price.to_be_bytes());
+                buf.extend_from_slice(&quantity.to_be_bytes());
+                buf.extend_from_slice(&leaves_quantity.to_be_bytes());
+            }
+            ServerMessage::Cancelled {
+                request_id,
+                order_id,
+                remaining_quantity,
+            } => {
+                buf.push(0x86);
+                buf.extend_from_slice(&request_id.to_be_bytes());
+                buf.extend_from_slice(order_id.as_bytes());
+                buf.extend_from_slice(&remaining_quantity.to_be_bytes());
+            }
+        }
+        buf
+    }
+
+    pub fn decode(payload: &[u8]) -> io::Result<Self> {
+        let mut decoder = Decoder::new(payload);
+        let message = match decoder.u8()? {
+            0x81 => ServerMessage::LogonAccepted {
+                client_id: decoder.string()?,
+            },
+            0x82 => ServerMessage::LogonRejected {
+                reason: decoder.string()?,
+            },
+            0x83 => ServerMessage::Ack {
+                request_id: decoder.u64()?,
+                order_id: decoder.uuid()?,
+            },
+            0x84 => {
+                let request_id = decoder.u64()?;
+                let order_id = decoder.uuid()?;
+                ServerMessage::Reject {
+                    request_id,
+                    order_id: (!order_id.is_nil()).then_some(order_id),
+                    code: decoder.u16()?,
+                    reason: decoder.string()?,
+                }
+            }
+            0x85 => ServerMessage::Fill {
+                order_id: decoder.uuid()?,
+                trade_id: decoder.uuid()?,
+                price: decoder.u64()?,
+                quantity: decoder.u64()?,
+                leaves_quantity: decoder.u64()?,
+            },
+            0x86 => ServerMessage::Cancelled {
+                request_id: decoder.u64()?,
+                order_id: decoder.uuid()?,
+                remaining_quantity: decoder.u64()?,
+            },
+            other => {
+                return Err(invalid(format!(
+                    "unknown server message type {:#04x}",
+                    other
+                )))
+            }
+        };
+        decoder.finish()?;
+        Ok(message)
+    }
+}
+
+/// Read one frame payload, or `None` if the peer closed the connection
+/// cleanly between frames
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len == 0 || len > MAX_FRAME_LEN {
+        return Err(invalid(format!("invalid frame length {}", len)));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Ok(Some(payload))
+}
+
+/// Write one frame
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame).await
+}
+
+fn encode_side(side: Side) -> u8 {
+    match side {
+        Side::Buy => 1,
+        Side::Sell => 2,
+    }
+}
+
+fn decode_side(byte: u8) -> io::Result<Side> {
+    match byte {
+        1 => Ok(Side::Buy),
+        2 => Ok(Side::Sell),
+        other => Err(invalid(format!("invalid side {}", other))),
+    }
+}
+
+fn encode_kind(kind: OrderKind) -> u8 {
+    match kind {
+        OrderKind::Limit => 1,
+        OrderKind::Market => 2,
+        OrderKind::Stop => 3,
+        OrderKind::StopLimit => 4,
+        OrderKind::MarketToLimit => 5,
+    }
+}
+
+fn decode_kind(byte: u8) -> io::Result<OrderKind> {
+    match byte {
+        1 => Ok(OrderKind::Limit),
+        2 => Ok(OrderKind::Market),
+        3 => Ok(OrderKind::Stop),
+        4 => Ok(OrderKind::StopLimit),
+        5 => Ok(OrderKind::MarketToLimit),
+        other => Err(invalid(format!("invalid order kind {}", other))),
+    }
+}
+
+fn put_time_in_force(buf: &mut Vec<u8>, time_in_force: TimeInForce) {
+    let (code, expire_millis) = match time_in_force {
+        TimeInForce::GoodTillCancel => (0, 0),
+        TimeInForce::Day => (1, 0),
+        TimeInForce::GoodTillDate(expire_time) => (2, expire_time.timestamp_millis().max(0) as u64),
+    };
+    buf.push(code);
+    buf.extend_from_slice(&expire_millis.to_be_bytes());
+}
+
+/// Write a length-prefixed string, cut to the longest whole-character
+/// prefix that fits a `u16` length
+fn put_str(buf: &mut Vec<u8>, value: &str) {
+    let mut len = value.len().min(u16::MAX as usize);
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    let bytes = &value.as_bytes()[..len];
+    buf.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Cursor over a message payload
+struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("truncated message".to_string()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn uuid(&mut self) -> io::Result<Uuid> {
+        Ok(Uuid::from_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    fn time_in_force(&mut self) -> io::Result<TimeInForce> {
+        let code = self.u8()?;
+        let expire_millis = self.u64()?;
+        match code {
+            0 => Ok(TimeInForce::GoodTillCancel),
+            1 => Ok(TimeInForce::Day),
+            2 => i64::try_from(expire_millis)
+                .ok()
+                .and_then(DateTime::from_timestamp_millis)
+                .map(TimeInForce::GoodTillDate)
+                .ok_or_else(|| invalid(format!("invalid expire time {}", expire_millis))),
+            other => Err(invalid(format!("invalid time in force {}", other))),
+        }
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| invalid("string is not valid UTF-8".to_string()))
+    }
+
+    fn finish(&self) -> io::Result<()> {
+        if self.pos == self.buf.len() {
+            Ok(())
+        } else {
+            Err(invalid("trailing bytes after message".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_message_round_trip() {
+        let messages = vec![
+            ClientMessage::Logon {
+                client_id: "desk-1".to_string(),
+            },
+            ClientMessage::NewOrder {
+                request_id: 7,
+                symbol: "AAPL".to_string(),
+                side: Side::Sell,
+                kind: OrderKind::StopLimit,
+                price: 15_000,
+                stop_price: 14_900,
+                quantity: 100,
+                time_in_force: TimeInForce::GoodTillDate(
+                    DateTime::from_timestamp_millis(1_700_000_000_123).unwrap(),
+                ),
+            },
+            ClientMessage::NewOrder {
+                request_id: 8,
+                symbol: "AAPL".to_string(),
+                side: Side::Buy,
+                kind: OrderKind::Limit,
+                price: 15_000,
+                stop_price: 0,
+                quantity: 100,
+                time_in_force: TimeInForce::Day,
+            },
+            ClientMessage::Cancel {
+                request_id: 8,
+                order_id: Uuid::new_v4(),
+            },
+            ClientMessage::Modify {
+                request_id: 9,
+                order_id: Uuid::new_v4(),
+                quantity: 50,
+            },
+            ClientMessage::MassCancel {
+                request_id: 10,
+                symbol: None,
+            },
+        ];
+
+        for message in messages {
+            assert_eq!(ClientMessage::decode(&message.encode()).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn test_server_message_round_trip() {
+        let messages = vec![
+            ServerMessage::Reject {
+                request_id: 1,
+                order_id: None,
+                code: 2,
+                reason: "Invalid symbol".to_string(),
+            },
+            ServerMessage::Fill {
+                order_id: Uuid::new_v4(),
+                trade_id: Uuid::new_v4(),
+                price: 10_000,
+                quantity: 25,
+                leaves_quantity: 75,
+            },
+            ServerMessage::Cancelled {
+                request_id: 0,
+                order_id: Uuid::new_v4(),
+                remaining_quantity: 10,
+            },
+        ];
+
+        for message in messages {
+            assert_eq!(ServerMessage::decode(&message.encode()).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn test_long_strings_are_cut_on_char_boundaries() {
+        let message = ServerMessage::LogonRejected {
+            reason: "é".repeat(40_000),
+        };
+        match ServerMessage::decode(&message.encode()).unwrap() {
+            ServerMessage::LogonRejected { reason } => {
+                assert_eq!(reason, "é".repeat(u16::MAX as usize / 2))
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_malformed_payloads_are_rejected() {
+        assert!(ClientMessage::decode(&[0x7F]).is_err());
+        assert!(ClientMessage::decode(&[0x03, 0, 0]).is_err());
+
+        let mut logon = ClientMessage::Logon {
+            client_id: "a".to_string(),
+        }
+        .encode();
+        logon.push(0);
+        assert!(ClientMessage::decode(&logon).is_err());
+    }
+}
@@ -0,0 +1,1114 @@
+use crossbeam::channel::{Receiver, RecvTimeoutError};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::collections::HashSet;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::gateway::protocol::{read_frame, write_frame, ClientMessage, OrderKind, ServerMessage};
+use crate::orderbook::book::OrderBook;
+use crate::orderbook::error::OrderBookError;
+use crate::orderbook::exchange::Exchange;
+use crate::orderbook::market_data::OrderMessage;
+use crate::orderbook::types::{MarketEvent, Order, OrderId, Price, Quantity};
+
+// How often an idle report thread checks whether its gateway is gone
+const REPORT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Owner and open quantity of an order entered through the gateway
+#[derive(Debug, Clone)]
+struct OrderRoute {
+    client_id: String,
+    symbol: String,
+    leaves_quantity: Quantity,
+    /// Feed messages held back until the order's entry has been reported
+    deferred: Option<Vec<OrderMessage>>,
+    /// The owner's cancel is in flight and will report the outcome
+    cancelling: bool,
+    /// The book deleted the order while the owner's cancel was in flight
+    deleted: bool,
+    /// Waiting in the stop book, which the order feed does not cover
+    parked: bool,
+    /// An expiry sweep took the parked order out while it was being entered
+    expired: bool,
+}
+
+/// Orders entered through the gateway and the sessions that own them,
+/// shared with the threads reporting from the books' order feeds
+#[derive(Debug, Default)]
+struct Routing {
+    routes: DashMap<OrderId, OrderRoute>,
+    sessions: DashMap<String, UnboundedSender<ServerMessage>>,
+}
+
+/// TCP order-entry gateway
+///
+/// Each connection must log on with a client ID before sending orders; the
+/// ID is stamped into `Order::client_id` and owns the orders the session
+/// enters. Execution reports for an order go to whichever session is
+/// currently logged on with its owner's client ID.
+///
+/// The response to a session's own request reports what the request did to
+/// the order it entered (and any stops that released). Everything that
+/// happens to an order once it rests, whoever causes it (other sessions,
+/// expiry, an auction uncross), is reported from the book's level-3 order
+/// feed, which the gateway subscribes to before its first order for a
+/// symbol. A stop order that another participant's flow releases is
+/// reported from the moment it rests; one that expires untriggered is
+/// reported by `report_expired`.
+#[derive(Debug)]
+pub struct Gateway {
+    exchange: Arc<Exchange>,
+    routing: Arc<Routing>,
+    /// Symbols whose order feed has a report thread
+    feeds: DashMap<String, ()>,
+}
+
+impl Gateway {
+    pub fn new(exchange: Arc<Exchange>) -> Self {
+        Self {
+            exchange,
+            routing: Arc::new(Routing::default()),
+            feeds: DashMap::new(),
+        }
+    }
+
+    /// Accept connections until the listener fails
+    pub async fn run(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        info!("Order gateway listening on {}", listener.local_addr()?);
+
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let gateway = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(e) = gateway.handle_connection(stream, peer).await {
+                    warn!("Gateway session {} ended with error: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(
+        self: &Arc<Self>,
+        stream: TcpStream,
+        peer: SocketAddr,
+    ) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let (mut reader, mut writer) = stream.into_split();
+
+        // Logon must be the first message
+        let client_id = match read_frame(&mut reader).await? {
+            Some(payload) => match ClientMessage::decode(&payload)? {
+                ClientMessage::Logon { client_id } if !client_id.is_empty() => client_id,
+                _ => {
+                    let reject = ServerMessage::LogonRejected {
+                        reason: "first message must be a logon with a client ID".to_string(),
+                    };
+                    return write_frame(&mut writer, &reject.encode()).await;
+                }
+            },
+            None => return Ok(()),
+        };
+
+        let (sender, mut outbound) = mpsc::unbounded_channel();
+        let registered = self.register_session(&client_id, sender.clone());
+        if !registered {
+            let reject = ServerMessage::LogonRejected {
+                reason: format!("client {} is already logged on", client_id),
+            };
+            return write_frame(&mut writer, &reject.encode()).await;
+        }
+
+        info!("Client {} logged on from {}", client_id, peer);
+        let _ = sender.send(ServerMessage::LogonAccepted {
+            client_id: client_id.clone(),
+        });
+
+        let writer_task = tokio::spawn(async move {
+            while let Some(message) = outbound.recv().await {
+                if write_frame(&mut writer, &message.encode()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let result = async {
+            while let Some(payload) = read_frame(&mut reader).await? {
+                let message = ClientMessage::decode(&payload)?;
+                self.submit(&client_id, message).await;
+            }
+            Ok(())
+        }
+        .await;
+
+        self.unregister_session(&client_id, &sender);
+        drop(sender);
+        let _ = writer_task.await;
+        info!("Client {} logged off", client_id);
+
+        result
+    }
+
+    /// Attach a session that will receive reports for `client_id`'s orders
+    ///
+    /// Returns `false` if another live session is already logged on with the
+    /// same client ID.
+    pub fn register_session(
+        &self,
+        client_id: &str,
+        sender: UnboundedSender<ServerMessage>,
+    ) -> bool {
+        match self.routing.sessions.entry(client_id.to_string()) {
+            Entry::Occupied(mut entry) if entry.get().is_closed() => {
+                entry.insert(sender);
+                true
+            }
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(sender);
+                true
+            }
+        }
+    }
+
+    /// Detach a session, unless a newer session has replaced it
+    pub fn unregister_session(&self, client_id: &str, sender: &UnboundedSender<ServerMessage>) {
+        self.routing
+            .sessions
+            .remove_if(client_id, |_, registered| registered.same_channel(sender));
+    }
+
+    /// Process one message from a logged-on session on Tokio's blocking
+    /// pool, since matching waits on the book's lock and journal
+    pub async fn submit(self: &Arc<Self>, client_id: &str, message: ClientMessage) {
+        let gateway = Arc::clone(self);
+        let client_id = client_id.to_string();
+        let handled =
+            tokio::task::spawn_blocking(move || gateway.handle_message(&client_id, message));
+        if let Err(e) = handled.await {
+            warn!("Gateway request failed: {}", e);
+        }
+    }
+
+    /// Process one message from a logged-on session
+    ///
+    /// Blocks while the book matches; async callers should use `submit`.
+    pub fn handle_message(&self, client_id: &str, message: ClientMessage) {
+        debug!("Gateway message from {}: {:?}", client_id, message);
+
+        match message {
+            ClientMessage::Logon { .. } => {
+                self.send(
+                    client_id,
+                    ServerMessage::LogonRejected {
+                        reason: "already logged on".to_string(),
+                    },
+                );
+            }
+            ClientMessage::NewOrder {
+                request_id,
+                symbol,
+                side,
+                kind,
+                price,
+                stop_price,
+                quantity,
+                time_in_force,
+            } => {
+                let owner = Some(client_id.to_string());
+                let order = match kind {
+                    OrderKind::Limit => {
+                        Order::new_limit(symbol.clone(), side, price, quantity, owner)
+                    }
+                    OrderKind::Market => Order::new_market(symbol.clone(), side, quantity, owner),
+                    OrderKind::MarketToLimit => {
+                        Order::new_market_to_limit(symbol.clone(), side, quantity, owner)
+                    }
+                    OrderKind::Stop => {
+                        Order::new_stop(symbol.clone(), side, stop_price, quantity, owner)
+                    }
+                    OrderKind::StopLimit => Order::new_stop_limit(
+                        symbol.clone(),
+                        side,
+                        stop_price,
+                        price,
+                        quantity,
+                        owner,
+                    ),
+                }
+                .with_time_in_force(time_in_force);
+                let order_id = order.id;
+
+                // Register the route first so fills during matching find it,
+                // and hold back feed reports until the ack is out
+                self.watch(&symbol);
+                self.routing.routes.insert(
+                    order_id,
+                    OrderRoute {
+                        client_id: client_id.to_string(),
+                        symbol: symbol.clone(),
+                        leaves_quantity: quantity,
+                        deferred: Some(Vec::new()),
+                        cancelling: false,
+                        deleted: false,
+                        parked: matches!(kind, OrderKind::Stop | OrderKind::StopLimit),
+                        expired: false,
+                    },
+                );
+
+                let result = match kind {
+                    OrderKind::Limit => self.exchange.add_limit_order(order),
+                    OrderKind::Market | OrderKind::MarketToLimit => {
+                        self.exchange.add_market_order(order)
+                    }
+                    OrderKind::Stop | OrderKind::StopLimit => self.exchange.add_stop_order(order),
+                };
+
+                match result {
+                    Ok(events) => {
+                        self.send(
+                            client_id,
+                            ServerMessage::Ack {
+                                request_id,
+                                order_id,
+                            },
+                        );
+                        self.dispatch(request_id, order_id, &events);
+                        self.routing.release(&order_id);
+                    }
+                    Err(e) => {
+                        self.routing.routes.remove(&order_id);
+                        self.reject(client_id, request_id, Some(order_id), e);
+                    }
+                }
+            }
+            ClientMessage::Cancel {
+                request_id,
+                order_id,
+            } => match self.cancel_owned(client_id, &order_id) {
+                Ok(remaining_quantity) => self.send(
+                    client_id,
+                    ServerMessage::Cancelled {
+                        request_id,
+                        order_id,
+                        remaining_quantity,
+                    },
+                ),
+                Err(e) => self.reject(client_id, request_id, Some(order_id), e),
+            },
+            ClientMessage::Modify {
+                request_id,
+                order_id,
+                quantity,
+            } => match self.modify_owned(client_id, &order_id, quantity) {
+                Ok(()) => self.send(
+                    client_id,
+                    ServerMessage::Ack {
+                        request_id,
+                        order_id,
+                    },
+                ),
+                Err(e) => self.reject(client_id, request_id, Some(order_id), e),
+            },
+            ClientMessage::MassCancel { request_id, symbol } => {
+                let owned: Vec<OrderId> = self
+                    .routing
+                    .routes
+                    .iter()
+                    .filter(|route| {
+                        route.client_id == client_id
+                            && symbol.as_ref().is_none_or(|s| *s == route.symbol)
+                    })
+                    .map(|route| *route.key())
+                    .collect();
+
+                for order_id in owned {
+                    if let Ok(remaining_quantity) = self.cancel_owned(client_id, &order_id) {
+                        self.send(
+                            client_id,
+                            ServerMessage::Cancelled {
+                                request_id,
+                                order_id,
+                                remaining_quantity,
+                            },
+                        );
+                    }
+                }
+
+                self.send(
+                    client_id,
+                    ServerMessage::Ack {
+                        request_id,
+                        order_id: Uuid::nil(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Get number of open orders entered through the gateway
+    pub fn open_orders(&self) -> usize {
+        self.routing.routes.len()
+    }
+
+    /// Report the stop orders an expiry sweep took out of the stop book
+    ///
+    /// Pass the events from `OrderBook::expire_orders` or `close_session`.
+    /// Resting orders that expire are reported from the order feed, but
+    /// stops that never triggered are not on it.
+    pub fn report_expired(&self, events: &[MarketEvent]) {
+        for event in events {
+            let MarketEvent::OrderExpired {
+                order_id,
+                remaining_quantity,
+            } = event
+            else {
+                continue;
+            };
+            let routes = &self.routing.routes;
+            let Some(mut route) = routes.get_mut(order_id).filter(|route| route.parked) else {
+                continue;
+            };
+            // Still being entered: reported once the ack is out
+            if route.deferred.is_some() {
+                route.expired = true;
+                continue;
+            }
+            self.send(
+                &route.client_id,
+                ServerMessage::Cancelled {
+                    request_id: 0,
+                    order_id: *order_id,
+                    remaining_quantity: *remaining_quantity,
+                },
+            );
+            drop(route);
+            routes.remove(order_id);
+        }
+    }
+
+    /// Start reporting from `symbol`'s order feed, if not already
+    ///
+    /// Subscribing under the entry lock means no order for the symbol
+    /// reaches the book before the feed is watched.
+    fn watch(&self, symbol: &str) {
+        let Entry::Vacant(entry) = self.feeds.entry(symbol.to_string()) else {
+            return;
+        };
+        let Ok(book) = self.exchange.book(symbol) else {
+            return;
+        };
+
+        let messages = book.subscribe_orders();
+        let routing = Arc::downgrade(&self.routing);
+        let book = Arc::downgrade(&book);
+        let spawned = thread::Builder::new()
+            .name(format!("gateway-{}", symbol))
+            .spawn(move || report_from_feed(routing, book, messages));
+        match spawned {
+            Ok(_) => {
+                entry.insert(());
+            }
+            Err(e) => warn!("Cannot report from the {} order feed: {}", symbol, e),
+        }
+    }
+
+    /// Report what a request did to the orders it entered: the new order
+    /// and any stops it released
+    ///
+    /// Once an entered order rests, or waits in the stop book, the order
+    /// feed reports it, since other flow may already be trading with it.
+    fn dispatch(&self, request_id: u64, order_id: OrderId, events: &[MarketEvent]) {
+        let mut entered = vec![(request_id, order_id)];
+        // Entered orders that have not rested yet
+        let mut aggressing = HashSet::from([order_id]);
+
+        for event in events {
+            match event {
+                MarketEvent::Trade { trade } => {
+                    for filled in [trade.buyer_order_id, trade.seller_order_id] {
+                        if aggressing.contains(&filled) {
+                            self.report_fill(filled, trade.id, trade.price, trade.quantity);
+                        }
+                    }
+                }
+                MarketEvent::StopTriggered { order_id, .. } => {
+                    if *order_id == entered[0].1 {
+                        if let Some(mut route) = self.routing.routes.get_mut(order_id) {
+                            route.parked = false;
+                        }
+                    } else {
+                        entered.push((0, *order_id));
+                    }
+                    aggressing.insert(*order_id);
+                }
+                MarketEvent::OrderAdded { order } => {
+                    aggressing.remove(&order.id);
+                }
+                // Includes anything self-trade prevention took off the order
+                MarketEvent::OrderCancelled {
+                    order_id,
+                    remaining_quantity: quantity,
+                } if aggressing.contains(order_id) => {
+                    if let Some(mut route) = self.routing.routes.get_mut(order_id) {
+                        route.leaves_quantity = *quantity;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Whatever never rested was filled or cancelled outright
+        for (request_id, order_id) in entered {
+            if !aggressing.contains(&order_id) {
+                continue;
+            }
+            if let Some((_, route)) = self.routing.routes.remove(&order_id) {
+                self.send(
+                    &route.client_id,
+                    ServerMessage::Cancelled {
+                        request_id,
+                        order_id,
+                        remaining_quantity: route.leaves_quantity,
+                    },
+                );
+            }
+        }
+    }
+
+    fn report_fill(&self, order_id: OrderId, trade_id: Uuid, price: Price, quantity: Quantity) {
+        let (client_id, leaves_quantity) = match self.routing.routes.get_mut(&order_id) {
+            Some(mut route) => {
+                route.leaves_quantity = route.leaves_quantity.saturating_sub(quantity);
+                (route.client_id.clone(), route.leaves_quantity)
+            }
+            None => return,
+        };
+
+        if leaves_quantity == 0 {
+            self.routing.routes.remove(&order_id);
+        }
+
+        self.send(
+            &client_id,
+            ServerMessage::Fill {
+                order_id,
+                trade_id,
+                price,
+                quantity,
+                leaves_quantity,
+            },
+        );
+    }
+
+    fn cancel_owned(
+        &self,
+        client_id: &str,
+        order_id: &OrderId,
+    ) -> Result<Quantity, OrderBookError> {
+        let symbol = self.owned_symbol(client_id, order_id)?;
+        let routes = &self.routing.routes;
+        if let Some(mut route) = routes.get_mut(order_id) {
+            route.cancelling = true;
+        }
+
+        let result = match self.exchange.cancel_order(&symbol, order_id) {
+            Ok(MarketEvent::OrderCancelled {
+                remaining_quantity, ..
+            }) => Ok(remaining_quantity),
+            Ok(_) => Err(OrderBookError::InvalidOrderState),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(remaining_quantity) => {
+                routes.remove(order_id);
+                Ok(remaining_quantity)
+            }
+            // The book may have deleted the order (an expiry, say) while
+            // the cancel waited; that is still a cancel
+            Err(e) => match routes.remove_if(order_id, |_, route| route.deleted) {
+                Some((_, route)) => Ok(route.leaves_quantity),
+                None => {
+                    if let Some(mut route) = routes.get_mut(order_id) {
+                        route.cancelling = false;
+                    }
+                    Err(e)
+                }
+            },
+        }
+    }
+
+    fn modify_owned(
+        &self,
+        client_id: &str,
+        order_id: &OrderId,
+        quantity: Quantity,
+    ) -> Result<(), OrderBookError> {
+        let symbol = self.owned_symbol(client_id, order_id)?;
+        self.exchange
+            .modify_order_quantity(&symbol, order_id, quantity)?;
+
+        if let Some(mut route) = self.routing.routes.get_mut(order_id) {
+            route.leaves_quantity = quantity;
+        }
+        Ok(())
+    }
+
+    /// Get the symbol of `order_id`, if `client_id` owns it
+    fn owned_symbol(&self, client_id: &str, order_id: &OrderId) -> Result<String, OrderBookError> {
+        match self.routing.routes.get(order_id) {
+            Some(route) if route.client_id == client_id => Ok(route.symbol.clone()),
+            _ => Err(OrderBookError::OrderNotFound),
+        }
+    }
+
+    fn reject(
+        &self,
+        client_id: &str,
+        request_id: u64,
+        order_id: Option<OrderId>,
+        error: OrderBookError,
+    ) {
+        self.send(
+            client_id,
+            ServerMessage::Reject {
+                request_id,
+                order_id,
+                code: error.code(),
+                reason: error.to_string(),
+            },
+        );
+    }
+
+    fn send(&self, client_id: &str, message: ServerMessage) {
+        self.routing.send(client_id, message);
+    }
+}
+
+impl Routing {
+    fn send(&self, client_id: &str, message: ServerMessage) {
+        let sender = self
+            .sessions
+            .get(client_id)
+            .map(|entry| entry.value().clone());
+        match sender {
+            Some(sender) => {
+                if sender.send(message).is_err() {
+                    debug!("Dropping report for disconnected client {}", client_id);
+                }
+            }
+            None => debug!("No session for client {}", client_id),
+        }
+    }
+
+    /// Report a change to a resting order, unless it is not a gateway order
+    fn on_order_message(&self, message: OrderMessage) {
+        let order_id = match message {
+            OrderMessage::Add { order_id, .. }
+            | OrderMessage::Execute { order_id, .. }
+            | OrderMessage::Reduce { order_id, .. }
+            | OrderMessage::Delete { order_id, .. }
+            | OrderMessage::Replace { order_id, .. } => order_id,
+        };
+        let Some(mut route) = self.routes.get_mut(&order_id) else {
+            return;
+        };
+        if let Some(deferred) = &mut route.deferred {
+            deferred.push(message);
+            return;
+        }
+
+        let closed = self.apply(order_id, &mut route, message);
+        drop(route);
+        if closed {
+            self.routes.remove(&order_id);
+        }
+    }
+
+    /// Report the feed messages held back while `order_id` was being
+    /// entered, and report later ones as they arrive
+    fn release(&self, order_id: &OrderId) {
+        let Some(mut route) = self.routes.get_mut(order_id) else {
+            return;
+        };
+        let mut closed = false;
+        for message in route.deferred.take().unwrap_or_default() {
+            closed |= self.apply(*order_id, &mut route, message);
+        }
+        if route.parked && route.expired && !closed {
+            self.send(
+                &route.client_id,
+                ServerMessage::Cancelled {
+                    request_id: 0,
+                    order_id: *order_id,
+                    remaining_quantity: route.leaves_quantity,
+                },
+            );
+            closed = true;
+        }
+        drop(route);
+        if closed {
+            self.routes.remove(order_id);
+        }
+    }
+
+    /// Report one feed message for a gateway order, returning whether the
+    /// order has left the book
+    ///
+    /// Gateway orders are never icebergs, so the feed's remaining quantity
+    /// is the order's open quantity.
+    fn apply(&self, order_id: OrderId, route: &mut OrderRoute, message: OrderMessage) -> bool {
+        match message {
+            // The order has rested, less anything self-trade prevention
+            // took off it, or a released stop has
+            OrderMessage::Add { quantity, .. } => {
+                route.parked = false;
+                route.leaves_quantity = quantity;
+                false
+            }
+            OrderMessage::Execute {
+                price,
+                executed_qty,
+                remaining_qty,
+                trade_id,
+                ..
+            } => {
+                route.leaves_quantity = remaining_qty;
+                self.send(
+                    &route.client_id,
+                    ServerMessage::Fill {
+                        order_id,
+                        trade_id,
+                        price,
+                        quantity: executed_qty,
+                        leaves_quantity: remaining_qty,
+                    },
+                );
+                remaining_qty == 0
+            }
+            OrderMessage::Reduce { remaining_qty, .. } => {
+                route.leaves_quantity = remaining_qty;
+                false
+            }
+            OrderMessage::Replace { quantity, .. } => {
+                route.leaves_quantity = quantity;
+                false
+            }
+            // The owner's cancel reports itself
+            OrderMessage::Delete { .. } if route.cancelling => {
+                route.deleted = true;
+                false
+            }
+            OrderMessage::Delete { .. } => {
+                self.send(
+                    &route.client_id,
+                    ServerMessage::Cancelled {
+                        request_id: 0,
+                        order_id,
+                        remaining_quantity: route.leaves_quantity,
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    /// Forget orders that left `book` while its feed was disconnected;
+    /// what happened to them is unknown
+    fn reconcile(&self, book: &OrderBook) {
+        self.routes.retain(|order_id, route| {
+            let lost = route.symbol == book.symbol
+                && route.deferred.is_none()
+                && !book.contains_order(order_id);
+            if lost {
+                warn!(
+                    "Lost track of order {} for {} while resubscribing",
+                    order_id, route.client_id
+                );
+            }
+            !lost
+        });
+    }
+}
+
+/// Report from one book's order feed until the gateway or the book is gone
+///
+/// A feed that disconnects because the thread fell behind is resubscribed.
+fn report_from_feed(
+    routing: Weak<Routing>,
+    book: Weak<OrderBook>,
+    mut messages: Receiver<OrderMessage>,
+) {
+    loop {
+        match messages.recv_timeout(REPORT_POLL_INTERVAL) {
+            Ok(message) => match routing.upgrade() {
+                Some(routing) => routing.on_order_message(message),
+                None => return,
+            },
+            Err(RecvTimeoutError::Timeout) => {
+                if routing.strong_count() == 0 {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                let (Some(routing), Some(book)) = (routing.upgrade(), book.upgrade()) else {
+                    return;
+                };
+                warn!(
+                    "Gateway fell behind the {} order feed; resubscribing",
+                    book.symbol
+                );
+                messages = book.subscribe_orders();
+                routing.reconcile(&book);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::book::OrderBook;
+    use crate::orderbook::exchange::InstrumentConfig;
+    use crate::orderbook::types::{Side, TimeInForce};
+    use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+    use tokio::time::{timeout, Duration};
+
+    struct TestClient {
+        reader: OwnedReadHalf,
+        writer: OwnedWriteHalf,
+    }
+
+    impl TestClient {
+        async fn logon(addr: SocketAddr, client_id: &str) -> (Self, ServerMessage) {
+            let (reader, writer) = TcpStream::connect(addr).await.unwrap().into_split();
+            let mut client = Self { reader, writer };
+            client
+                .send(ClientMessage::Logon {
+                    client_id: client_id.to_string(),
+                })
+                .await;
+            let reply = client.recv().await;
+            (client, reply)
+        }
+
+        async fn send(&mut self, message: ClientMessage) {
+            write_frame(&mut self.writer, &message.encode())
+                .await
+                .unwrap();
+        }
+
+        async fn recv(&mut self) -> ServerMessage {
+            let payload = timeout(Duration::from_secs(5), read_frame(&mut self.reader))
+                .await
+                .expect("timed out waiting for gateway")
+                .unwrap()
+                .expect("gateway closed the connection");
+            ServerMessage::decode(&payload).unwrap()
+        }
+    }
+
+    fn limit(request_id: u64, side: Side, price: u64, quantity: u64) -> ClientMessage {
+        ClientMessage::NewOrder {
+            request_id,
+            symbol: "TEST".to_string(),
+            side,
+            kind: OrderKind::Limit,
+            price,
+            stop_price: 0,
+            quantity,
+            time_in_force: TimeInForce::GoodTillCancel,
+        }
+    }
+
+    async fn start_gateway() -> (Arc<Gateway>, Arc<OrderBook>, SocketAddr) {
+        let exchange = Arc::new(Exchange::new());
+        let book = exchange
+            .add_instrument(InstrumentConfig::new("TEST"))
+            .unwrap();
+        let gateway = Arc::new(Gateway::new(exchange));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(Arc::clone(&gateway).run(listener));
+        (gateway, book, addr)
+    }
+
+    #[tokio::test]
+    async fn test_order_entry_and_fills() {
+        let (_gateway, book, addr) = start_gateway().await;
+        let (mut seller, reply) = TestClient::logon(addr, "seller").await;
+        assert!(matches!(reply, ServerMessage::LogonAccepted { .. }));
+        let (mut buyer, _) = TestClient::logon(addr, "buyer").await;
+
+        seller.send(limit(1, Side::Sell, 10_000, 100)).await;
+        let resting_id = match seller.recv().await {
+            ServerMessage::Ack {
+                request_id: 1,
+                order_id,
+            } => order_id,
+            other => panic!("expected ack, got {:?}", other),
+        };
+
+        buyer.send(limit(1, Side::Buy, 10_000, 40)).await;
+        let buy_id = match buyer.recv().await {
+            ServerMessage::Ack { order_id, .. } => order_id,
+            other => panic!("expected ack, got {:?}", other),
+        };
+        assert!(matches!(
+            buyer.recv().await,
+            ServerMessage::Fill { order_id, quantity: 40, leaves_quantity: 0, .. } if order_id == buy_id
+        ));
+        assert!(matches!(
+            seller.recv().await,
+            ServerMessage::Fill { order_id, price: 10_000, quantity: 40, leaves_quantity: 60, .. }
+                if order_id == resting_id
+        ));
+
+        // Only the owner may cancel
+        buyer
+            .send(ClientMessage::Cancel {
+                request_id: 2,
+                order_id: resting_id,
+            })
+            .await;
+        assert!(matches!(
+            buyer.recv().await,
+            ServerMessage::Reject { code: 1, .. }
+        ));
+
+        seller
+            .send(ClientMessage::Modify {
+                request_id: 2,
+                order_id: resting_id,
+                quantity: 30,
+            })
+            .await;
+        assert!(matches!(
+            seller.recv().await,
+            ServerMessage::Ack { request_id: 2, .. }
+        ));
+        seller
+            .send(ClientMessage::Cancel {
+                request_id: 3,
+                order_id: resting_id,
+            })
+            .await;
+        assert!(matches!(
+            seller.recv().await,
+            ServerMessage::Cancelled {
+                request_id: 3,
+                remaining_quantity: 30,
+                ..
+            }
+        ));
+        assert_eq!(book.total_orders(), 0);
+
+        assert!(book.snapshot().asks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_and_mass_cancel() {
+        let (_gateway, book, addr) = start_gateway().await;
+        let (mut client, _) = TestClient::logon(addr, "desk").await;
+
+        // Duplicate logon for an active client is refused
+        let (_, reply) = TestClient::logon(addr, "desk").await;
+        assert!(matches!(reply, ServerMessage::LogonRejected { .. }));
+
+        client
+            .send(ClientMessage::NewOrder {
+                request_id: 1,
+                symbol: "NOPE".to_string(),
+                side: Side::Buy,
+                kind: OrderKind::Limit,
+                price: 10_000,
+                stop_price: 0,
+                quantity: 10,
+                time_in_force: TimeInForce::GoodTillCancel,
+            })
+            .await;
+        assert!(matches!(
+            client.recv().await,
+            ServerMessage::Reject {
+                request_id: 1,
+                code: 14,
+                ..
+            }
+        ));
+
+        for request_id in 2..5 {
+            client
+                .send(limit(request_id, Side::Buy, 9_000 + request_id, 10))
+                .await;
+            assert!(matches!(client.recv().await, ServerMessage::Ack { .. }));
+        }
+        assert_eq!(book.total_orders(), 3);
+
+        client
+            .send(ClientMessage::MassCancel {
+                request_id: 9,
+                symbol: None,
+            })
+            .await;
+        for _ in 0..3 {
+            assert!(matches!(
+                client.recv().await,
+                ServerMessage::Cancelled { request_id: 9, .. }
+            ));
+        }
+        assert!(matches!(
+            client.recv().await,
+            ServerMessage::Ack { request_id: 9, order_id } if order_id.is_nil()
+        ));
+        assert_eq!(book.total_orders(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fills_from_outside_the_gateway_are_reported() {
+        let (gateway, book, addr) = start_gateway().await;
+        let (mut maker, _) = TestClient::logon(addr, "maker").await;
+        maker.send(limit(1, Side::Sell, 10_000, 50)).await;
+        let resting_id = match maker.recv().await {
+            ServerMessage::Ack { order_id, .. } => order_id,
+            other => panic!("expected ack, got {:?}", other),
+        };
+
+        // Flow that bypasses the gateway still fills the gateway's order
+        for quantity in [20, 30] {
+            book.add_limit_order(Order::new_limit(
+                "TEST".to_string(),
+                Side::Buy,
+                10_000,
+                quantity,
+                None,
+            ))
+            .unwrap();
+        }
+        assert!(matches!(
+            maker.recv().await,
+            ServerMessage::Fill { order_id, quantity: 20, leaves_quantity: 30, .. }
+                if order_id == resting_id
+        ));
+        assert!(matches!(
+            maker.recv().await,
+            ServerMessage::Fill { order_id, quantity: 30, leaves_quantity: 0, .. }
+                if order_id == resting_id
+        ));
+        assert_eq!(gateway.open_orders(), 0);
+
+        // An order the book deletes on its own is reported as cancelled
+        maker.send(limit(2, Side::Sell, 10_100, 10)).await;
+        let resting_id = match maker.recv().await {
+            ServerMessage::Ack { order_id, .. } => order_id,
+            other => panic!("expected ack, got {:?}", other),
+        };
+        book.cancel_order(&resting_id).unwrap();
+        assert!(matches!(
+            maker.recv().await,
+            ServerMessage::Cancelled { request_id: 0, order_id, remaining_quantity: 10 }
+                if order_id == resting_id
+        ));
+        assert_eq!(gateway.open_orders(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_expired_orders_are_reported() {
+        let (gateway, book, addr) = start_gateway().await;
+        let (mut client, _) = TestClient::logon(addr, "desk").await;
+
+        let mut day_orders = Vec::new();
+        for (request_id, kind, stop_price, quantity) in [
+            (1, OrderKind::Limit, 0, 10),
+            (2, OrderKind::Stop, 10_500, 5),
+        ] {
+            client
+                .send(ClientMessage::NewOrder {
+                    request_id,
+                    symbol: "TEST".to_string(),
+                    side: Side::Buy,
+                    kind,
+                    price: 9_900,
+                    stop_price,
+                    quantity,
+                    time_in_force: TimeInForce::Day,
+                })
+                .await;
+            match client.recv().await {
+                ServerMessage::Ack { order_id, .. } => day_orders.push((order_id, quantity)),
+                other => panic!("expected ack, got {:?}", other),
+            }
+        }
+        assert_eq!(book.total_stop_orders(), 1);
+
+        // The resting order is reported from the order feed, the parked
+        // stop from the sweep's events
+        let events = book.close_session().unwrap();
+        assert_eq!(events.len(), 2);
+        gateway.report_expired(&events);
+
+        let mut reported = Vec::new();
+        for _ in 0..2 {
+            match client.recv().await {
+                ServerMessage::Cancelled {
+                    request_id: 0,
+                    order_id,
+                    remaining_quantity,
+                } => reported.push((order_id, remaining_quantity)),
+                other => panic!("expected unsolicited cancel, got {:?}", other),
+            }
+        }
+        reported.sort();
+        day_orders.sort();
+        assert_eq!(reported, day_orders);
+        assert_eq!(gateway.open_orders(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unfilled_market_remainder_is_cancelled() {
+        let (_gateway, _book, addr) = start_gateway().await;
+        let (mut maker, _) = TestClient::logon(addr, "maker").await;
+        let (mut taker, _) = TestClient::logon(addr, "taker").await;
+
+        maker.send(limit(1, Side::Sell, 10_000, 25)).await;
+        maker.recv().await;
+
+        taker
+            .send(ClientMessage::NewOrder {
+                request_id: 1,
+                symbol: "TEST".to_string(),
+                side: Side::Buy,
+                kind: OrderKind::Market,
+                price: 0,
+                stop_price: 0,
+                quantity: 100,
+                time_in_force: TimeInForce::GoodTillCancel,
+            })
+            .await;
+        assert!(matches!(taker.recv().await, ServerMessage::Ack { .. }));
+        assert!(matches!(
+            taker.recv().await,
+            ServerMessage::Fill {
+                quantity: 25,
+                leaves_quantity: 75,
+                ..
+            }
+        ));
+        assert!(matches!(
+            taker.recv().await,
+            ServerMessage::Cancelled {
+                request_id: 1,
+                remaining_quantity: 75,
+                ..
+            }
+        ));
+    }
+}
@@ -6,7 +6,8 @@
 //!
 //! # Features
 //!
-//! - **Lock-free Design**: Uses atomic operations and concurrent data structures
+//! - **Linearizable Matching**: Each book runs commands through a single-writer matching
+//!   lock, while market data reads stay lock-free on atomics
 //! - **High Performance**: Sub-microsecond order operations, millions of orders/second
 //! - **Price-Time Priority**: Maintains strict FIFO ordering within price levels
 //! - **Comprehensive Monitoring**: Built-in metrics with Prometheus and InfluxDB support
@@ -36,15 +37,17 @@
 //!
 //! The order book uses a two-level data structure:
 //!
-//! 1. **Price Levels**: `DashMap<Price, Arc<PriceLevel>>` for lock-free price level access
+//! 1. **Price Levels**: a sorted `PriceLadder` per side (`BTreeMap<Price, Arc<PriceLevel>>`)
+//!    with a cached best price, so best bid/ask is O(1) and matching walks levels in order
 //! 2. **Order Queues**: Within each price level, orders maintain time priority using `VecDeque`
 //!
 //! This design optimizes for:
 //! - Fast order insertion/cancellation
-//! - Efficient order matching
+//! - Efficient order matching without sorting the book
 //! - Minimal memory allocations
 //! - Cache-friendly data layout
 
+pub mod gateway;
 pub mod metrics;
 pub mod orderbook;
 pub mod utils;
@@ -56,7 +59,7 @@ pub use orderbook::{
     OrderBook,
 };
 
-// pub use metrics::OrderBookMetrics;
+pub use metrics::OrderBookMetrics;
 
 #[cfg(test)]
 mod integration_tests {
@@ -76,7 +79,7 @@ mod integration_tests {
         // Match with market order
         let buy_order = Order::new_market("TEST".to_string(), Side::Buy, 50, None);
         let events = book.add_market_order(buy_order).unwrap();
-        assert_eq!(events.len(), 1);
+        assert_eq!(events.len(), 2);
 
         // Verify trade occurred
         if let orderbook::types::MarketEvent::Trade { trade } = &events[0] {
@@ -113,4 +116,93 @@ mod integration_tests {
         // Verify all orders were added
         assert_eq!(book.total_orders(), 400);
     }
+
+    #[test]
+    fn test_concurrent_matching_conserves_quantity() {
+        use orderbook::types::MarketEvent;
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        const THREADS: u64 = 8;
+        const ORDERS_PER_THREAD: u64 = 2_000;
+
+        let book = Arc::new(OrderBook::new("TEST".to_string()));
+        let mut handles = vec![];
+
+        // Aggressive two-sided flow around a fixed mid so most orders cross
+        for thread_id in 0..THREADS {
+            let book_clone = Arc::clone(&book);
+            handles.push(thread::spawn(move || {
+                let mut rng = StdRng::seed_from_u64(thread_id);
+                let mut submitted = 0;
+                let mut traded = 0;
+                let mut cancelled = 0;
+                let mut resting_ids = Vec::new();
+
+                for _ in 0..ORDERS_PER_THREAD {
+                    let side = if rng.gen_bool(0.5) { Side::Buy } else { Side::Sell };
+                    let price = rng.gen_range(9_990..=10_010);
+                    let quantity = rng.gen_range(1..=100);
+                    submitted += quantity;
+
+                    let order = Order::new_limit("TEST".to_string(), side, price, quantity, None);
+                    let order_id = order.id;
+                    for event in book_clone.add_limit_order(order).unwrap() {
+                        match event {
+                            MarketEvent::Trade { trade } => traded += trade.quantity,
+                            MarketEvent::OrderAdded { .. } => resting_ids.push(order_id),
+                            _ => {}
+                        }
+                    }
+
+                    // Cancel an older order now and then; it may already be filled
+                    if rng.gen_bool(0.2) && !resting_ids.is_empty() {
+                        let index = rng.gen_range(0..resting_ids.len());
+                        let cancel_id = resting_ids.swap_remove(index);
+                        if let Ok(MarketEvent::OrderCancelled {
+                            remaining_quantity,
+                            ..
+                        }) = book_clone.cancel_order(&cancel_id)
+                        {
+                            cancelled += remaining_quantity;
+                        }
+                    }
+                }
+
+                (submitted, traded, cancelled)
+            }));
+        }
+
+        let (mut submitted, mut traded, mut cancelled) = (0, 0, 0);
+        for handle in handles {
+            let (s, t, c) = handle.join().unwrap();
+            submitted += s;
+            traded += t;
+            cancelled += c;
+        }
+
+        let snapshot = book.snapshot();
+        let resting: Quantity = snapshot
+            .bids
+            .iter()
+            .chain(snapshot.asks.iter())
+            .map(|level| level.quantity)
+            .sum();
+        let resting_orders: u32 = snapshot
+            .bids
+            .iter()
+            .chain(snapshot.asks.iter())
+            .map(|level| level.order_count)
+            .sum();
+
+        // Each trade consumes quantity from both an aggressor and a resting order
+        assert_eq!(submitted, 2 * traded + resting + cancelled);
+        assert_eq!(traded, book.get_stats().total_volume);
+        assert_eq!(resting_orders as usize, book.total_orders());
+
+        // The book must never be left crossed
+        if let (Some(bid), Some(ask)) = (book.best_bid(), book.best_ask()) {
+            assert!(bid < ask, "crossed book: bid {} >= ask {}", bid, ask);
+        }
+    }
 }
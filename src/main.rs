@@ -1,5 +1,9 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 mod metrics;
 mod orderbook;
+mod utils;
 
 fn main() {
     // Initialize metrics
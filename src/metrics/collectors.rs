@@ -1,12 +1,17 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tracing::info;
+
+use super::histogram::LatencyHistogram;
 
 /// Collects and aggregates latency statistics
+///
+/// Samples go into a fixed-size `LatencyHistogram`, so recording never
+/// allocates and memory does not grow with the sample count.
 #[derive(Debug)]
 pub struct LatencyCollector {
-    samples: Vec<Duration>,
+    histogram: LatencyHistogram,
     last_collection: Instant,
     collection_interval: Duration,
 }
@@ -14,59 +19,32 @@ pub struct LatencyCollector {
 impl LatencyCollector {
     pub fn new(collection_interval: Duration) -> Self {
         Self {
-            samples: Vec::new(),
+            histogram: LatencyHistogram::new(),
             last_collection: Instant::now(),
             collection_interval,
         }
     }
 
     /// Add a latency sample
-    pub fn record(&mut self, latency: Duration) {
-        self.samples.push(latency);
+    pub fn record(&self, latency: Duration) {
+        self.histogram.record(latency);
+    }
+
+    /// Get the histogram samples are recorded into, e.g. to merge another
+    /// thread's samples
+    pub fn histogram(&self) -> &LatencyHistogram {
+        &self.histogram
     }
 
     /// Collect and reset statistics if interval has passed
     pub fn collect(&mut self) -> Option<LatencyStatistics> {
         if self.last_collection.elapsed() >= self.collection_interval {
-            let stats = self.calculate_stats();
-            self.samples.clear();
             self.last_collection = Instant::now();
-            Some(stats)
+            Some(self.histogram.take_statistics())
         } else {
             None
         }
     }
-
-    fn calculate_stats(&self) -> LatencyStatistics {
-        if self.samples.is_empty() {
-            return LatencyStatistics::default();
-        }
-
-        let mut sorted_samples = self.samples.clone();
-        sorted_samples.sort();
-
-        let len = sorted_samples.len();
-        let min = sorted_samples[0];
-        let max = sorted_samples[len - 1];
-        let p50 = sorted_samples[len / 2];
-        let p95 = sorted_samples[(len as f64 * 0.95) as usize];
-        let p99 = sorted_samples[(len as f64 * 0.99) as usize];
-        let p999 = sorted_samples[(len as f64 * 0.999) as usize];
-
-        let total: Duration = sorted_samples.iter().sum();
-        let mean = total / len as u32;
-
-        LatencyStatistics {
-            count: len as u64,
-            min,
-            max,
-            mean,
-            p50,
-            p95,
-            p99,
-            p999,
-        }
-    }
 }
 
 /// Throughput collector for counting operations per second
@@ -129,10 +107,18 @@ impl ThroughputCollector {
 }
 
 /// System resource collector
+///
+/// Reads the process's usage from Linux `/proc`. CPU figures cover the
+/// interval since the previous collection (or since the collector was
+/// created) and, like `top`, can exceed 100% when several cores are busy.
+/// On other platforms every figure reads as zero.
 #[derive(Debug)]
 pub struct ResourceCollector {
     last_collection: Instant,
     collection_interval: Duration,
+    // CPU ticks at the last collection, for the process and each thread
+    last_cpu_ticks: u64,
+    last_thread_ticks: HashMap<u32, u64>,
 }
 
 impl ResourceCollector {
@@ -140,43 +126,195 @@ impl ResourceCollector {
         Self {
             last_collection: Instant::now(),
             collection_interval,
+            last_cpu_ticks: process_cpu_ticks().unwrap_or(0),
+            last_thread_ticks: thread_cpu_ticks()
+                .into_iter()
+                .map(|(tid, _, ticks)| (tid, ticks))
+                .collect(),
         }
     }
 
     /// Collect system resource statistics
     pub fn collect(&mut self) -> Option<ResourceStatistics> {
-        if self.last_collection.elapsed() >= self.collection_interval {
+        let elapsed = self.last_collection.elapsed();
+        if elapsed >= self.collection_interval {
             self.last_collection = Instant::now();
-            Some(self.get_resource_stats())
+            Some(self.get_resource_stats(elapsed))
         } else {
             None
         }
     }
 
-    fn get_resource_stats(&self) -> ResourceStatistics {
-        // In a real implementation, you would use system APIs
-        // For now, we'll return placeholder values
+    fn get_resource_stats(&mut self, elapsed: Duration) -> ResourceStatistics {
+        let cpu_percent =
+            |ticks: u64| ticks as f64 / CLOCK_TICKS_PER_SEC as f64 / elapsed.as_secs_f64() * 100.0;
+
+        let cpu_ticks = process_cpu_ticks().unwrap_or(self.last_cpu_ticks);
+        let cpu_usage_percent = cpu_percent(cpu_ticks.saturating_sub(self.last_cpu_ticks));
+        self.last_cpu_ticks = cpu_ticks;
+
+        // Threads that started during the interval count from zero
+        let mut thread_ticks = HashMap::new();
+        let threads = thread_cpu_ticks()
+            .into_iter()
+            .map(|(tid, name, ticks)| {
+                let last = self.last_thread_ticks.get(&tid).copied().unwrap_or(0);
+                thread_ticks.insert(tid, ticks);
+                ThreadCpuUsage {
+                    tid,
+                    name,
+                    cpu_usage_percent: cpu_percent(ticks.saturating_sub(last)),
+                }
+            })
+            .collect();
+        self.last_thread_ticks = thread_ticks;
+
+        let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+        let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+        let socket_inodes = socket_inodes();
+        let (tcp_connections, tcp_listening) = ["/proc/net/tcp", "/proc/net/tcp6"]
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .map(|table| count_tcp_sockets(&table, &socket_inodes))
+            .fold((0, 0), |(c, l), (connections, listening)| {
+                (c + connections, l + listening)
+            });
+
         ResourceStatistics {
-            cpu_usage_percent: 0.0,
-            memory_usage_bytes: 0,
-            memory_available_bytes: 0,
-            open_file_descriptors: 0,
-            network_connections: 0,
+            cpu_usage_percent,
+            memory_usage_bytes: kilobytes_field(&status, "VmRSS:").unwrap_or(0),
+            memory_available_bytes: kilobytes_field(&meminfo, "MemAvailable:").unwrap_or(0),
+            open_file_descriptors: open_file_descriptors(),
+            network_connections: tcp_connections,
+            listening_sockets: tcp_listening,
+            threads,
         }
     }
 }
 
+// USER_HZ, the unit of the CPU times in /proc/<pid>/stat; fixed at 100 by
+// the Linux ABI on every mainstream architecture
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+// TCP_LISTEN in the `st` column of /proc/net/tcp
+const TCP_LISTEN: &str = "0A";
+
+/// Get the process's user plus system CPU time, in clock ticks
+fn process_cpu_ticks() -> Option<u64> {
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    parse_stat(&stat).map(|(_, ticks)| ticks)
+}
+
+/// Get each thread's id, name and CPU time in clock ticks
+fn thread_cpu_ticks() -> Vec<(u32, String, u64)> {
+    let Ok(tasks) = fs::read_dir("/proc/self/task") else {
+        return Vec::new();
+    };
+
+    tasks
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let tid = entry.file_name().to_str()?.parse().ok()?;
+            // The thread may exit between listing and reading
+            let stat = fs::read_to_string(entry.path().join("stat")).ok()?;
+            let (name, ticks) = parse_stat(&stat)?;
+            Some((tid, name, ticks))
+        })
+        .collect()
+}
+
+/// Parse the command name and utime + stime out of a `/proc/<pid>/stat`
+/// line
+///
+/// The name is wrapped in parentheses and may itself contain spaces or
+/// parentheses, so fields are counted from the last `)`.
+fn parse_stat(stat: &str) -> Option<(String, u64)> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let name = stat.get(open + 1..close)?.to_string();
+
+    // Fields 14 and 15 of the line; the state (field 3) comes first here
+    let mut fields = stat.get(close + 1..)?.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some((name, utime + stime))
+}
+
+/// Get a `Key:   1234 kB` field of `/proc/self/status` or `/proc/meminfo`
+/// in bytes
+fn kilobytes_field(contents: &str, key: &str) -> Option<u64> {
+    let line = contents.lines().find(|line| line.starts_with(key))?;
+    let kilobytes: u64 = line[key.len()..].split_whitespace().next()?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+fn open_file_descriptors() -> u32 {
+    match fs::read_dir("/proc/self/fd") {
+        // Listing the directory holds a descriptor of its own
+        Ok(entries) => (entries.count() as u32).saturating_sub(1),
+        Err(_) => 0,
+    }
+}
+
+/// Get the inodes of the process's open sockets
+fn socket_inodes() -> HashSet<u64> {
+    let Ok(entries) = fs::read_dir("/proc/self/fd") else {
+        return HashSet::new();
+    };
+
+    entries
+        .filter_map(|entry| {
+            let target = fs::read_link(entry.ok()?.path()).ok()?;
+            let target = target.to_str()?;
+            target
+                .strip_prefix("socket:[")?
+                .strip_suffix(']')?
+                .parse()
+                .ok()
+        })
+        .collect()
+}
+
+/// Count the sockets in a `/proc/net/tcp` table that belong to this process
+/// (by inode), as (connections, listening)
+///
+/// The table covers the whole network namespace, hence the inode filter.
+fn count_tcp_sockets(table: &str, inodes: &HashSet<u64>) -> (u32, u32) {
+    let mut connections = 0;
+    let mut listening = 0;
+
+    for line in table.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (Some(state), Some(inode)) = (fields.get(3), fields.get(9)) else {
+            continue;
+        };
+        if !inode.parse().is_ok_and(|inode| inodes.contains(&inode)) {
+            continue;
+        }
+
+        if *state == TCP_LISTEN {
+            listening += 1;
+        } else {
+            connections += 1;
+        }
+    }
+
+    (connections, listening)
+}
+
 /// Aggregated latency statistics
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct LatencyStatistics {
     pub count: u64,
     pub min: Duration,
     pub max: Duration,
     pub mean: Duration,
     pub p50: Duration,
+    pub p90: Duration,
     pub p95: Duration,
     pub p99: Duration,
     pub p999: Duration,
+    pub p9999: Duration,
 }
 
 impl LatencyStatistics {
@@ -188,9 +326,11 @@ impl LatencyStatistics {
             max: self.max.as_micros() as f64,
             mean: self.mean.as_micros() as f64,
             p50: self.p50.as_micros() as f64,
+            p90: self.p90.as_micros() as f64,
             p95: self.p95.as_micros() as f64,
             p99: self.p99.as_micros() as f64,
             p999: self.p999.as_micros() as f64,
+            p9999: self.p9999.as_micros() as f64,
         }
     }
 }
@@ -202,13 +342,15 @@ pub struct LatencyMicros {
     pub max: f64,
     pub mean: f64,
     pub p50: f64,
+    pub p90: f64,
     pub p95: f64,
     pub p99: f64,
     pub p999: f64,
+    pub p9999: f64,
 }
 
 /// Throughput statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct ThroughputStatistics {
     pub operations: u64,
     pub rate: f64,
@@ -217,13 +359,26 @@ pub struct ThroughputStatistics {
 }
 
 /// System resource statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct ResourceStatistics {
     pub cpu_usage_percent: f64,
+    /// Resident set size of the process
     pub memory_usage_bytes: u64,
+    /// Memory available to new processes without swapping, system-wide
     pub memory_available_bytes: u64,
     pub open_file_descriptors: u32,
+    /// The process's TCP sockets other than listeners
     pub network_connections: u32,
+    pub listening_sockets: u32,
+    pub threads: Vec<ThreadCpuUsage>,
+}
+
+/// CPU usage of one of the process's threads over a collection interval
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ThreadCpuUsage {
+    pub tid: u32,
+    pub name: String,
+    pub cpu_usage_percent: f64,
 }
 
 #[cfg(test)]
@@ -249,6 +404,12 @@ mod tests {
         assert_eq!(stats.count, 3);
         assert_eq!(stats.min, Duration::from_micros(100));
         assert_eq!(stats.max, Duration::from_micros(300));
+        assert!(stats.p50 >= Duration::from_micros(200) && stats.p50 < Duration::from_micros(202));
+        assert_eq!(stats.p9999, Duration::from_micros(300));
+
+        // The next interval starts empty
+        thread::sleep(Duration::from_millis(101));
+        assert_eq!(collector.collect().unwrap().count, 0);
     }
 
     #[test]
@@ -270,4 +431,48 @@ mod tests {
         assert_eq!(stats.operations, 6);
         assert!(stats.rate > 0.0);
     }
+
+    #[test]
+    fn test_proc_parsing() {
+        let stat = "4242 (tokio (worker) 1) S 1 4242 4242 0 -1 4194560 900 0 0 0 \
+                    250 75 0 0 20 0 9 0 12345 1000000 500";
+        assert_eq!(
+            parse_stat(stat),
+            Some(("tokio (worker) 1".to_string(), 325))
+        );
+        assert_eq!(parse_stat("4242 (cut"), None);
+
+        let status = "Name:\ttrading_server\nVmPeak:\t  20480 kB\nVmRSS:\t   8192 kB\n";
+        assert_eq!(kilobytes_field(status, "VmRSS:"), Some(8 * 1024 * 1024));
+        assert_eq!(kilobytes_field(status, "MemAvailable:"), None);
+
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1B58 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 101 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:1B58 0100007F:D2F0 01 00000000:00000000 00:00000000 00000000  1000        0 102 1 0000000000000000 20 4 30 10 -1
+   2: 0100007F:1F90 0100007F:D2F2 01 00000000:00000000 00:00000000 00000000  1000        0 999 1 0000000000000000 20 4 30 10 -1";
+        let inodes = HashSet::from([101, 102]);
+        assert_eq!(count_tcp_sockets(table, &inodes), (1, 1));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_resource_collector() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut collector = ResourceCollector::new(Duration::from_millis(100));
+
+        // Keep this thread busy for the interval
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(110) {
+            std::hint::black_box(0u64);
+        }
+
+        let stats = collector.collect().unwrap();
+        assert!(stats.memory_usage_bytes > 0);
+        assert!(stats.memory_available_bytes > 0);
+        assert!(stats.open_file_descriptors > 0);
+        assert!(stats.listening_sockets >= 1);
+        assert!(!stats.threads.is_empty());
+        assert!(stats.cpu_usage_percent >= 0.0);
+        drop(listener);
+    }
 }
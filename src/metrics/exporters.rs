@@ -1,10 +1,8 @@
-use serde_json::json;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::time::interval;
-use tracing::{error, info, warn};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
 
 use super::collectors::{LatencyStatistics, ResourceStatistics, ThroughputStatistics};
+use super::OrderBookMetrics;
 
 /// InfluxDB exporter for time-series metrics
 pub struct InfluxDBExporter {
@@ -16,16 +14,13 @@ pub struct InfluxDBExporter {
 
 impl InfluxDBExporter {
     pub fn new(url: &str, token: &str, bucket: String, org: String) -> Self {
-        match influxdb2::Client::new(url, token) {
-            client => {
-                info!("InfluxDB exporter initialized for bucket: {}", bucket);
-                Self {
-                    client: Some(client),
-                    bucket,
-                    org,
-                    enabled: true,
-                }
-            }
+        let client = influxdb2::Client::new(url, &org, token);
+        info!("InfluxDB exporter initialized for bucket: {}", bucket);
+        Self {
+            client: Some(client),
+            bucket,
+            org,
+            enabled: true,
         }
     }
 
@@ -39,7 +34,13 @@ impl InfluxDBExporter {
     }
 
     /// Export latency statistics
-    pub async fn export_latency(&self, measurement: &str, symbol: &str, stats: &LatencyStatistics) {
+    pub async fn export_latency(
+        &self,
+        measurement: &str,
+        symbol: &str,
+        operation: &str,
+        stats: &LatencyStatistics,
+    ) {
         if !self.enabled || self.client.is_none() {
             return;
         }
@@ -51,45 +52,38 @@ impl InfluxDBExporter {
 
         let micros = stats.to_micros();
 
-        // Create data points for each percentile
-        let points = vec![
-            format!(
-                "{},symbol={},metric=count value={}i {}",
-                measurement, symbol, micros.count, timestamp
-            ),
-            format!(
-                "{},symbol={},metric=min value={} {}",
-                measurement, symbol, micros.min, timestamp
-            ),
-            format!(
-                "{},symbol={},metric=max value={} {}",
-                measurement, symbol, micros.max, timestamp
-            ),
-            format!(
-                "{},symbol={},metric=mean value={} {}",
-                measurement, symbol, micros.mean, timestamp
-            ),
-            format!(
-                "{},symbol={},metric=p50 value={} {}",
-                measurement, symbol, micros.p50, timestamp
-            ),
-            format!(
-                "{},symbol={},metric=p95 value={} {}",
-                measurement, symbol, micros.p95, timestamp
-            ),
-            format!(
-                "{},symbol={},metric=p99 value={} {}",
-                measurement, symbol, micros.p99, timestamp
-            ),
-            format!(
-                "{},symbol={},metric=p999 value={} {}",
-                measurement, symbol, micros.p999, timestamp
-            ),
-        ];
+        // Create data points for the count and each percentile
+        let mut points = vec![format!(
+            "{},symbol={},operation={},metric=count value={}i {}",
+            measurement, symbol, operation, micros.count, timestamp
+        )];
+        points.extend(
+            [
+                ("min", micros.min),
+                ("max", micros.max),
+                ("mean", micros.mean),
+                ("p50", micros.p50),
+                ("p90", micros.p90),
+                ("p95", micros.p95),
+                ("p99", micros.p99),
+                ("p999", micros.p999),
+                ("p9999", micros.p9999),
+            ]
+            .iter()
+            .map(|(metric, value)| {
+                format!(
+                    "{},symbol={},operation={},metric={} value={} {}",
+                    measurement, symbol, operation, metric, value, timestamp
+                )
+            }),
+        );
 
         if let Some(client) = &self.client {
             for point in points {
-                if let Err(e) = client.write(&self.bucket, &self.org, &point).await {
+                if let Err(e) = client
+                    .write_line_protocol(&self.org, &self.bucket, point)
+                    .await
+                {
                     error!("Failed to write latency metrics to InfluxDB: {}", e);
                 }
             }
@@ -129,7 +123,10 @@ impl InfluxDBExporter {
 
         if let Some(client) = &self.client {
             for point in points {
-                if let Err(e) = client.write(&self.bucket, &self.org, &point).await {
+                if let Err(e) = client
+                    .write_line_protocol(&self.org, &self.bucket, point)
+                    .await
+                {
                     error!("Failed to write throughput metrics to InfluxDB: {}", e);
                 }
             }
@@ -147,7 +144,7 @@ impl InfluxDBExporter {
             .unwrap()
             .as_nanos() as i64;
 
-        let points = vec![
+        let mut points = vec![
             format!(
                 "{},metric=cpu_usage value={} {}",
                 measurement, stats.cpu_usage_percent, timestamp
@@ -168,11 +165,28 @@ impl InfluxDBExporter {
                 "{},metric=network_connections value={}i {}",
                 measurement, stats.network_connections, timestamp
             ),
+            format!(
+                "{},metric=listening_sockets value={}i {}",
+                measurement, stats.listening_sockets, timestamp
+            ),
         ];
+        points.extend(stats.threads.iter().map(|thread| {
+            format!(
+                "{},metric=thread_cpu_usage,thread={},tid={} value={} {}",
+                measurement,
+                escape_tag(&thread.name),
+                thread.tid,
+                thread.cpu_usage_percent,
+                timestamp
+            )
+        }));
 
         if let Some(client) = &self.client {
             for point in points {
-                if let Err(e) = client.write(&self.bucket, &self.org, &point).await {
+                if let Err(e) = client
+                    .write_line_protocol(&self.org, &self.bucket, point)
+                    .await
+                {
                     error!("Failed to write resource metrics to InfluxDB: {}", e);
                 }
             }
@@ -180,6 +194,15 @@ impl InfluxDBExporter {
     }
 }
 
+/// Escape a tag value for the InfluxDB line protocol
+fn escape_tag(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
 /// Console exporter for development and debugging
 pub struct ConsoleExporter {
     enabled: bool,
@@ -202,14 +225,16 @@ impl ConsoleExporter {
 
         let micros = stats.to_micros();
         info!(
-            "📊 {} {} Latency | Count: {} | Min: {:.2}μs | P50: {:.2}μs | P95: {:.2}μs | P99: {:.2}μs | Max: {:.2}μs",
+            "📊 {} {} Latency | Count: {} | Min: {:.2}μs | P50: {:.2}μs | P90: {:.2}μs | P99: {:.2}μs | P99.9: {:.2}μs | P99.99: {:.2}μs | Max: {:.2}μs",
             operation,
             symbol,
             micros.count,
             micros.min,
             micros.p50,
-            micros.p95,
+            micros.p90,
             micros.p99,
+            micros.p999,
+            micros.p9999,
             micros.max
         );
     }
@@ -233,12 +258,35 @@ impl ConsoleExporter {
         }
 
         info!(
-            "💻 System Resources | CPU: {:.1}% | Memory: {} MB | FDs: {} | Connections: {}",
+            "💻 System Resources | CPU: {:.1}% | Memory: {} MB | Available: {} MB | FDs: {} | Connections: {} | Listening: {}",
             stats.cpu_usage_percent,
             stats.memory_usage_bytes / 1024 / 1024,
+            stats.memory_available_bytes / 1024 / 1024,
             stats.open_file_descriptors,
-            stats.network_connections
+            stats.network_connections,
+            stats.listening_sockets
         );
+
+        // Busiest threads first
+        let mut threads: Vec<_> = stats
+            .threads
+            .iter()
+            .filter(|thread| thread.cpu_usage_percent > 0.0)
+            .collect();
+        if threads.is_empty() {
+            return;
+        }
+        threads.sort_by(|a, b| b.cpu_usage_percent.total_cmp(&a.cpu_usage_percent));
+        let threads: Vec<String> = threads
+            .iter()
+            .map(|thread| {
+                format!(
+                    "{}[{}]: {:.1}%",
+                    thread.name, thread.tid, thread.cpu_usage_percent
+                )
+            })
+            .collect();
+        info!("💻 Thread CPU | {}", threads.join(" | "));
     }
 }
 
@@ -308,10 +356,12 @@ impl MetricsExporter {
     /// Export all metrics to all configured exporters
     pub async fn export_all(&self, snapshot: &MetricsSnapshot) {
         // Export to InfluxDB
-        for (symbol, latency) in &snapshot.latency_stats {
-            self.influxdb
-                .export_latency("orderbook_latency", symbol, latency)
-                .await;
+        for (symbol, operations) in &snapshot.latency_stats {
+            for (operation, latency) in operations {
+                self.influxdb
+                    .export_latency("orderbook_latency", symbol, operation, latency)
+                    .await;
+            }
         }
 
         for (symbol, throughput) in &snapshot.throughput_stats {
@@ -325,8 +375,10 @@ impl MetricsExporter {
             .await;
 
         // Export to console
-        for (symbol, latency) in &snapshot.latency_stats {
-            self.console.export_latency("OrderBook", symbol, latency);
+        for (symbol, operations) in &snapshot.latency_stats {
+            for (operation, latency) in operations {
+                self.console.export_latency(operation, symbol, latency);
+            }
         }
 
         for (symbol, throughput) in &snapshot.throughput_stats {
@@ -342,10 +394,12 @@ impl MetricsExporter {
 }
 
 /// Snapshot of all metrics at a point in time
-
+#[derive(serde::Serialize)]
 pub struct MetricsSnapshot {
     pub timestamp: u64,
-    pub latency_stats: std::collections::HashMap<String, LatencyStatistics>,
+    /// Latency by symbol, then by operation
+    pub latency_stats:
+        std::collections::HashMap<String, std::collections::HashMap<String, LatencyStatistics>>,
     pub throughput_stats: std::collections::HashMap<String, ThroughputStatistics>,
     pub resource_stats: ResourceStatistics,
 }
@@ -359,17 +413,20 @@ impl MetricsSnapshot {
                 .as_secs(),
             latency_stats: std::collections::HashMap::new(),
             throughput_stats: std::collections::HashMap::new(),
-            resource_stats: ResourceStatistics {
-                cpu_usage_percent: 0.0,
-                memory_usage_bytes: 0,
-                memory_available_bytes: 0,
-                open_file_descriptors: 0,
-                network_connections: 0,
-            },
+            resource_stats: ResourceStatistics::default(),
         }
     }
 }
 
+impl MetricsSnapshot {
+    /// Add the latency `metrics` recorded for `symbol` since the last
+    /// snapshot, starting a new interval
+    pub fn add_latency(&mut self, symbol: &str, metrics: &OrderBookMetrics) {
+        self.latency_stats
+            .insert(symbol.to_string(), metrics.take_latency_statistics());
+    }
+}
+
 impl Default for MetricsSnapshot {
     fn default() -> Self {
         Self::new()
@@ -391,9 +448,17 @@ mod tests {
 
     #[test]
     fn test_metrics_snapshot() {
-        let snapshot = MetricsSnapshot::new();
+        let mut snapshot = MetricsSnapshot::new();
         assert!(snapshot.timestamp > 0);
         assert!(snapshot.latency_stats.is_empty());
         assert!(snapshot.throughput_stats.is_empty());
+
+        let metrics = OrderBookMetrics::for_symbol("AAPL");
+        metrics.time_add_order(|| ());
+        snapshot.add_latency("AAPL", &metrics);
+        assert_eq!(snapshot.latency_stats["AAPL"]["add_order"].count, 1);
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert!(json["latency_stats"]["AAPL"]["add_order"]["p9999"].is_object());
     }
 }
@@ -0,0 +1,263 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::collectors::LatencyStatistics;
+
+// Each power of two is split into 2^SUB_BUCKET_BITS linear sub-buckets, so a
+// recorded value is reported to within 1/128 (0.8%) of its true value
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+/// Lock-free, fixed-memory latency histogram
+///
+/// Values are nanoseconds, counted in log-linear buckets covering the whole
+/// `u64` range: exact below 128ns, then 128 buckets per power of two.
+/// Recording is a handful of relaxed atomic operations, so any number of
+/// threads can record into one histogram while another reads it. Min and
+/// max are tracked exactly.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+    sum_nanos: AtomicU64,
+    min_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            sum_nanos: AtomicU64::new(0),
+            min_nanos: AtomicU64::new(u64::MAX),
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Record one latency sample
+    pub fn record(&self, latency: Duration) {
+        self.record_nanos(u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX));
+    }
+
+    /// Record one latency sample given in nanoseconds
+    pub fn record_nanos(&self, nanos: u64) {
+        self.buckets[bucket_index(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.min_nanos.fetch_min(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Add every sample recorded in `other`, e.g. a per-thread histogram
+    pub fn merge(&self, other: &LatencyHistogram) {
+        for (bucket, count) in self.buckets.iter().zip(other.buckets.iter()) {
+            let count = count.load(Ordering::Relaxed);
+            if count > 0 {
+                bucket.fetch_add(count, Ordering::Relaxed);
+            }
+        }
+        self.sum_nanos
+            .fetch_add(other.sum_nanos.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.min_nanos
+            .fetch_min(other.min_nanos.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.max_nanos
+            .fetch_max(other.max_nanos.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    /// Get the number of samples recorded since the last reset
+    pub fn count(&self) -> u64 {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Summarize the samples recorded since the last reset
+    pub fn statistics(&self) -> LatencyStatistics {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        summarize(
+            &counts,
+            self.sum_nanos.load(Ordering::Relaxed),
+            self.min_nanos.load(Ordering::Relaxed),
+            self.max_nanos.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Summarize the samples recorded since the last reset and start a new
+    /// interval
+    ///
+    /// A sample recorded concurrently lands in exactly one of the two
+    /// intervals, though its contribution to the sum, min or max may land in
+    /// the other.
+    pub fn take_statistics(&self) -> LatencyStatistics {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.swap(0, Ordering::Relaxed))
+            .collect();
+        summarize(
+            &counts,
+            self.sum_nanos.swap(0, Ordering::Relaxed),
+            self.min_nanos.swap(u64::MAX, Ordering::Relaxed),
+            self.max_nanos.swap(0, Ordering::Relaxed),
+        )
+    }
+
+    /// Discard every sample
+    pub fn reset(&self) {
+        self.take_statistics();
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn bucket_index(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS as u64 {
+        return nanos as usize;
+    }
+    let shift = 63 - nanos.leading_zeros() - SUB_BUCKET_BITS;
+    let sub_bucket = (nanos >> shift) as usize - SUB_BUCKETS;
+    (shift as usize + 1) * SUB_BUCKETS + sub_bucket
+}
+
+/// Get the largest value counted in bucket `index`
+fn bucket_upper_bound(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let shift = (index / SUB_BUCKETS - 1) as u32;
+    let lower = ((index % SUB_BUCKETS + SUB_BUCKETS) as u64) << shift;
+    lower + ((1u64 << shift) - 1)
+}
+
+fn summarize(counts: &[u64], sum: u64, min: u64, max: u64) -> LatencyStatistics {
+    let count: u64 = counts.iter().sum();
+    if count == 0 {
+        return LatencyStatistics::default();
+    }
+
+    // Each quantile is reported as the top of its bucket, clamped to the
+    // exact extremes
+    let quantile = |q: f64| {
+        let rank = ((q * count as f64).ceil() as u64).clamp(1, count);
+        let mut seen = 0;
+        let index = counts
+            .iter()
+            .position(|&n| {
+                seen += n;
+                seen >= rank
+            })
+            .unwrap_or(counts.len() - 1);
+        Duration::from_nanos(bucket_upper_bound(index).min(max).max(min))
+    };
+
+    LatencyStatistics {
+        count,
+        min: Duration::from_nanos(min),
+        max: Duration::from_nanos(max),
+        mean: Duration::from_nanos(sum / count),
+        p50: quantile(0.5),
+        p90: quantile(0.9),
+        p95: quantile(0.95),
+        p99: quantile(0.99),
+        p999: quantile(0.999),
+        p9999: quantile(0.9999),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_bucket_bounds() {
+        for nanos in [0, 1, 127, 128, 129, 1_000, 65_535, 1 << 40, u64::MAX] {
+            let index = bucket_index(nanos);
+            assert!(index < BUCKETS);
+            let upper = bucket_upper_bound(index);
+            assert!(upper >= nanos);
+            assert!(upper - nanos <= nanos / SUB_BUCKETS as u64);
+        }
+        assert_eq!(bucket_index(u64::MAX), BUCKETS - 1);
+        assert_eq!(bucket_upper_bound(BUCKETS - 1), u64::MAX);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let histogram = LatencyHistogram::new();
+        for micros in 1..=10_000 {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        let stats = histogram.statistics();
+        assert_eq!(stats.count, 10_000);
+        assert_eq!(stats.min, Duration::from_micros(1));
+        assert_eq!(stats.max, Duration::from_micros(10_000));
+        assert_eq!(stats.mean, Duration::from_nanos(5_000_500));
+
+        let within = |actual: Duration, micros: u64| {
+            let expected = micros as f64 * 1_000.0;
+            (actual.as_nanos() as f64 - expected).abs() <= expected / SUB_BUCKETS as f64
+        };
+        assert!(within(stats.p50, 5_000));
+        assert!(within(stats.p90, 9_000));
+        assert!(within(stats.p99, 9_900));
+        assert!(within(stats.p999, 9_990));
+        assert!(within(stats.p9999, 9_999));
+
+        // A single outlier is still the max
+        histogram.record(Duration::from_secs(3));
+        assert_eq!(histogram.statistics().max, Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_merge_and_reset() {
+        let total = LatencyHistogram::new();
+        let per_thread: Vec<_> = (0..4).map(|_| Arc::new(LatencyHistogram::new())).collect();
+
+        let handles: Vec<_> = per_thread
+            .iter()
+            .enumerate()
+            .map(|(i, histogram)| {
+                let histogram = Arc::clone(histogram);
+                thread::spawn(move || {
+                    for n in 0..1_000 {
+                        histogram.record_nanos(1_000 * (i as u64 + 1) + n);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        for histogram in &per_thread {
+            total.merge(histogram);
+        }
+
+        assert_eq!(total.count(), 4_000);
+        assert_eq!(total.statistics().min, Duration::from_nanos(1_000));
+        assert_eq!(total.statistics().max, Duration::from_nanos(4_999));
+
+        // Taking the statistics starts a new interval
+        assert_eq!(total.take_statistics().count, 4_000);
+        assert_eq!(total.count(), 0);
+        total.record_nanos(500);
+        let stats = total.take_statistics();
+        assert_eq!(
+            (stats.count, stats.min, stats.max),
+            (1, Duration::from_nanos(500), Duration::from_nanos(500))
+        );
+
+        per_thread[0].reset();
+        assert_eq!(per_thread[0].statistics().count, 0);
+    }
+}
@@ -1,4 +1,10 @@
-use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use metrics::{
+    counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Counter,
+    Gauge, Histogram, Label,
+};
+use crate::orderbook::OrderBookStats;
+use std::collections::HashMap;
+use std::fmt;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -7,10 +13,21 @@ use tracing::info;
 
 pub mod collectors;
 pub mod exporters;
+pub mod histogram;
+#[cfg(feature = "prometheus")]
+pub mod server;
+
+use collectors::LatencyStatistics;
+use histogram::LatencyHistogram;
 
 /// Metrics collector for order book operations
+///
+/// Metrics built with [`OrderBookMetrics::for_symbol`] carry a `symbol`
+/// label, so one recorder can hold the state of every book on the exchange.
 #[derive(Debug)]
 pub struct OrderBookMetrics {
+    symbol: Option<String>,
+
     // Latency tracking
     add_order_latency: LatencyTracker,
     cancel_order_latency: LatencyTracker,
@@ -31,13 +48,76 @@ pub struct OrderBookMetrics {
     // Volume tracking
     total_volume: AtomicU64,
     total_notional: AtomicU64,
+
+    series: Series,
+}
+
+/// Handles to every exported series, resolved once against the recorder
+/// installed when the metrics are built
+struct Series {
+    orders_added: Counter,
+    orders_cancelled: Counter,
+    orders_modified: Counter,
+    trades_executed: Counter,
+    volume: Counter,
+    notional: Counter,
+    orders_current: Gauge,
+    bid_levels: Gauge,
+    ask_levels: Gauge,
+    spread: Gauge,
+    best_bid: Gauge,
+    best_ask: Gauge,
+}
+
+impl Series {
+    fn new(labels: impl Fn(&[(&'static str, &str)]) -> Vec<Label>) -> Self {
+        let orders = |operation| {
+            counter!(
+                "orderbook_orders_total",
+                labels(&[("operation", operation)])
+            )
+        };
+        let levels = |side| gauge!("orderbook_levels_total", labels(&[("side", side)]));
+
+        Self {
+            orders_added: orders("add"),
+            orders_cancelled: orders("cancel"),
+            orders_modified: orders("modify"),
+            trades_executed: counter!("orderbook_trades_total", labels(&[("status", "executed")])),
+            volume: counter!("orderbook_volume_total", labels(&[])),
+            notional: counter!("orderbook_notional_total", labels(&[])),
+            orders_current: gauge!("orderbook_orders_current", labels(&[])),
+            bid_levels: levels("bid"),
+            ask_levels: levels("ask"),
+            spread: gauge!("orderbook_spread_ticks", labels(&[])),
+            best_bid: gauge!("orderbook_best_bid", labels(&[])),
+            best_ask: gauge!("orderbook_best_ask", labels(&[])),
+        }
+    }
+}
+
+impl fmt::Debug for Series {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Series").finish_non_exhaustive()
+    }
 }
 
 impl OrderBookMetrics {
     pub fn new() -> Self {
+        Self::build(None)
+    }
+
+    /// Metrics for a single book, labelled with its symbol
+    pub fn for_symbol(symbol: impl Into<String>) -> Self {
+        Self::build(Some(symbol.into()))
+    }
+
+    fn build(symbol: Option<String>) -> Self {
         // Register metric descriptions
         describe_counter!("orderbook_orders_total", "Total number of orders processed");
         describe_counter!("orderbook_trades_total", "Total number of trades executed");
+        describe_counter!("orderbook_volume_total", "Total quantity traded");
+        describe_counter!("orderbook_notional_total", "Total notional traded, in ticks");
         describe_histogram!(
             "orderbook_operation_duration_seconds",
             "Duration of order book operations"
@@ -51,12 +131,15 @@ impl OrderBookMetrics {
             "Current number of orders in the book"
         );
         describe_gauge!("orderbook_spread_ticks", "Current bid-ask spread in ticks");
-
-        Self {
-            add_order_latency: LatencyTracker::new("add_order"),
-            cancel_order_latency: LatencyTracker::new("cancel_order"),
-            modify_order_latency: LatencyTracker::new("modify_order"),
-            match_order_latency: LatencyTracker::new("match_order"),
+        describe_gauge!("orderbook_best_bid", "Current best bid price in ticks");
+        describe_gauge!("orderbook_best_ask", "Current best ask price in ticks");
+
+        let labels = |extra: &[(&'static str, &str)]| series_labels(symbol.as_deref(), extra);
+        let metrics = Self {
+            add_order_latency: LatencyTracker::new("add_order", symbol.as_deref()),
+            cancel_order_latency: LatencyTracker::new("cancel_order", symbol.as_deref()),
+            modify_order_latency: LatencyTracker::new("modify_order", symbol.as_deref()),
+            match_order_latency: LatencyTracker::new("match_order", symbol.as_deref()),
             orders_added: AtomicU64::new(0),
             orders_cancelled: AtomicU64::new(0),
             orders_modified: AtomicU64::new(0),
@@ -66,9 +149,42 @@ impl OrderBookMetrics {
             ask_levels: AtomicU64::new(0),
             total_volume: AtomicU64::new(0),
             total_notional: AtomicU64::new(0),
+            series: Series::new(labels),
+            symbol,
+        };
+        metrics.register();
+        metrics
+    }
+
+    /// Touch every series so a scrape shows them before the first update
+    fn register(&self) {
+        let series = &self.series;
+        for counter in [
+            &series.orders_added,
+            &series.orders_cancelled,
+            &series.orders_modified,
+            &series.trades_executed,
+            &series.volume,
+            &series.notional,
+        ] {
+            counter.increment(0);
+        }
+        for gauge in [
+            &series.orders_current,
+            &series.bid_levels,
+            &series.ask_levels,
+            &series.spread,
+            &series.best_bid,
+            &series.best_ask,
+        ] {
+            gauge.increment(0.0);
         }
     }
 
+    pub fn symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
     // Latency measurement methods
     pub fn time_add_order<F, R>(&self, f: F) -> R
     where
@@ -101,17 +217,17 @@ impl OrderBookMetrics {
     // Counter methods
     pub fn increment_orders_added(&self) {
         self.orders_added.fetch_add(1, Ordering::Relaxed);
-        counter!("orderbook_orders_total", "operation" => "add").increment(1);
+        self.series.orders_added.increment(1);
     }
 
     pub fn increment_orders_cancelled(&self) {
         self.orders_cancelled.fetch_add(1, Ordering::Relaxed);
-        counter!("orderbook_orders_total", "operation" => "cancel").increment(1);
+        self.series.orders_cancelled.increment(1);
     }
 
     pub fn increment_orders_modified(&self) {
         self.orders_modified.fetch_add(1, Ordering::Relaxed);
-        counter!("orderbook_orders_total", "operation" => "modify").increment(1);
+        self.series.orders_modified.increment(1);
     }
 
     pub fn increment_trades_executed(&self, quantity: u64, notional: u64) {
@@ -119,37 +235,37 @@ impl OrderBookMetrics {
         self.total_volume.fetch_add(quantity, Ordering::Relaxed);
         self.total_notional.fetch_add(notional, Ordering::Relaxed);
 
-        counter!("orderbook_trades_total", "status" => "executed").increment(1);
-        counter!("orderbook_volume_total",).increment(quantity);
-        counter!("orderbook_notional_total",).increment(notional);
+        self.series.trades_executed.increment(1);
+        self.series.volume.increment(quantity);
+        self.series.notional.increment(notional);
     }
 
     // Gauge methods
     pub fn set_total_orders(&self, count: u64) {
         self.total_orders.store(count, Ordering::Relaxed);
-        gauge!("orderbook_orders_current",).set(count as f64);
+        self.series.orders_current.set(count as f64);
     }
 
     pub fn set_bid_levels(&self, count: u64) {
         self.bid_levels.store(count, Ordering::Relaxed);
-        gauge!("orderbook_levels_total", "side" => "bid").set(count as f64);
+        self.series.bid_levels.set(count as f64);
     }
 
     pub fn set_ask_levels(&self, count: u64) {
         self.ask_levels.store(count, Ordering::Relaxed);
-        gauge!("orderbook_levels_total", "side" => "ask").set(count as f64);
+        self.series.ask_levels.set(count as f64);
     }
 
     pub fn set_spread(&self, spread_ticks: u64) {
-        gauge!("orderbook_spread_ticks",).set(spread_ticks as f64);
+        self.series.spread.set(spread_ticks as f64);
     }
 
     pub fn set_best_bid(&self, price: u64) {
-        gauge!("orderbook_best_bid",).set(price as f64);
+        self.series.best_bid.set(price as f64);
     }
 
     pub fn set_best_ask(&self, price: u64) {
-        gauge!("orderbook_best_ask",).set(price as f64);
+        self.series.best_ask.set(price as f64);
     }
 
     // Getters for current values
@@ -177,6 +293,18 @@ impl OrderBookMetrics {
         self.total_notional.load(Ordering::Relaxed)
     }
 
+    /// Refresh the book-state gauges from a stats snapshot
+    ///
+    /// The spread and best price gauges drop to 0 while a side is empty.
+    pub fn record_book_stats(&self, stats: &OrderBookStats) {
+        self.set_total_orders(stats.total_orders as u64);
+        self.set_bid_levels(stats.bid_levels as u64);
+        self.set_ask_levels(stats.ask_levels as u64);
+        self.set_spread(stats.spread.unwrap_or(0));
+        self.set_best_bid(stats.best_bid.unwrap_or(0));
+        self.set_best_ask(stats.best_ask.unwrap_or(0));
+    }
+
     pub fn get_latency_stats(&self) -> LatencyStats {
         LatencyStats {
             add_order: self.add_order_latency.get_stats(),
@@ -185,6 +313,44 @@ impl OrderBookMetrics {
             match_order: self.match_order_latency.get_stats(),
         }
     }
+
+    /// Get each operation's latency over the interval since the last call,
+    /// keyed by operation name, and start a new interval
+    pub fn take_latency_statistics(&self) -> HashMap<String, LatencyStatistics> {
+        self.trackers()
+            .into_iter()
+            .map(|tracker| {
+                (
+                    tracker.operation.clone(),
+                    tracker.histogram.take_statistics(),
+                )
+            })
+            .collect()
+    }
+
+    /// Discard every latency sample
+    pub fn reset_latency(&self) {
+        for tracker in self.trackers() {
+            tracker.histogram.reset();
+        }
+    }
+
+    /// Add the latency samples recorded in `other`, e.g. a per-thread
+    /// instance
+    pub fn merge_latency(&self, other: &OrderBookMetrics) {
+        for (tracker, other) in self.trackers().into_iter().zip(other.trackers()) {
+            tracker.histogram.merge(&other.histogram);
+        }
+    }
+
+    fn trackers(&self) -> [&LatencyTracker; 4] {
+        [
+            &self.add_order_latency,
+            &self.cancel_order_latency,
+            &self.modify_order_latency,
+            &self.match_order_latency,
+        ]
+    }
 }
 
 impl Default for OrderBookMetrics {
@@ -193,24 +359,33 @@ impl Default for OrderBookMetrics {
     }
 }
 
+/// Labels for one series: the book's symbol, if any, then `extra`
+fn series_labels(symbol: Option<&str>, extra: &[(&'static str, &str)]) -> Vec<Label> {
+    let mut labels = Vec::with_capacity(extra.len() + 1);
+    if let Some(symbol) = symbol {
+        labels.push(Label::new("symbol", symbol.to_string()));
+    }
+    for (key, value) in extra {
+        labels.push(Label::new(*key, value.to_string()));
+    }
+    labels
+}
+
 /// Latency tracker for individual operations
-#[derive(Debug)]
 struct LatencyTracker {
     operation: String,
-    samples: AtomicU64,
-    total_nanos: AtomicU64,
-    min_nanos: AtomicU64,
-    max_nanos: AtomicU64,
+    exported: Histogram,
+    histogram: LatencyHistogram,
 }
 
 impl LatencyTracker {
-    fn new(operation: &str) -> Self {
+    fn new(operation: &str, symbol: Option<&str>) -> Self {
+        let labels = series_labels(symbol, &[("operation", operation)]);
+
         Self {
             operation: operation.to_string(),
-            samples: AtomicU64::new(0),
-            total_nanos: AtomicU64::new(0),
-            min_nanos: AtomicU64::new(u64::MAX),
-            max_nanos: AtomicU64::new(0),
+            exported: histogram!("orderbook_operation_duration_seconds", labels),
+            histogram: LatencyHistogram::new(),
         }
     }
 
@@ -227,65 +402,40 @@ impl LatencyTracker {
     }
 
     fn record_latency(&self, duration: Duration) {
-        let nanos = duration.as_nanos() as u64;
-
-        self.samples.fetch_add(1, Ordering::Relaxed);
-        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
-
-        // Update min (with CAS loop)
-        let mut current_min = self.min_nanos.load(Ordering::Relaxed);
-        while nanos < current_min {
-            match self.min_nanos.compare_exchange_weak(
-                current_min,
-                nanos,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(new_min) => current_min = new_min,
-            }
-        }
-
-        // Update max (with CAS loop)
-        let mut current_max = self.max_nanos.load(Ordering::Relaxed);
-        while nanos > current_max {
-            match self.max_nanos.compare_exchange_weak(
-                current_max,
-                nanos,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(new_max) => current_max = new_max,
-            }
-        }
+        self.histogram.record(duration);
 
         // Record in metrics system
-        histogram!(
-            "orderbook_operation_duration_seconds",
-            duration.as_secs_f64(),
-            "operation" => self.operation.clone()
-        );
+        self.exported.record(duration.as_secs_f64());
     }
 
     fn get_stats(&self) -> OperationLatencyStats {
-        let samples = self.samples.load(Ordering::Relaxed);
-        let total = self.total_nanos.load(Ordering::Relaxed);
-        let min = self.min_nanos.load(Ordering::Relaxed);
-        let max = self.max_nanos.load(Ordering::Relaxed);
-
-        let avg = if samples > 0 { total / samples } else { 0 };
+        let stats = self.histogram.statistics();
+        let nanos = |d: Duration| d.as_nanos() as u64;
 
         OperationLatencyStats {
             operation: self.operation.clone(),
-            samples,
-            avg_nanos: avg,
-            min_nanos: if min == u64::MAX { 0 } else { min },
-            max_nanos: max,
+            samples: stats.count,
+            avg_nanos: nanos(stats.mean),
+            min_nanos: nanos(stats.min),
+            max_nanos: nanos(stats.max),
+            p50_nanos: nanos(stats.p50),
+            p90_nanos: nanos(stats.p90),
+            p99_nanos: nanos(stats.p99),
+            p999_nanos: nanos(stats.p999),
+            p9999_nanos: nanos(stats.p9999),
         }
     }
 }
 
+impl fmt::Debug for LatencyTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LatencyTracker")
+            .field("operation", &self.operation)
+            .field("histogram", &self.histogram)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LatencyStats {
     pub add_order: OperationLatencyStats,
@@ -301,6 +451,11 @@ pub struct OperationLatencyStats {
     pub avg_nanos: u64,
     pub min_nanos: u64,
     pub max_nanos: u64,
+    pub p50_nanos: u64,
+    pub p90_nanos: u64,
+    pub p99_nanos: u64,
+    pub p999_nanos: u64,
+    pub p9999_nanos: u64,
 }
 
 impl OperationLatencyStats {
@@ -315,6 +470,10 @@ impl OperationLatencyStats {
     pub fn max_micros(&self) -> f64 {
         self.max_nanos as f64 / 1_000.0
     }
+
+    pub fn p99_micros(&self) -> f64 {
+        self.p99_nanos as f64 / 1_000.0
+    }
 }
 
 /// Background metrics reporter
@@ -337,15 +496,19 @@ impl MetricsReporter {
             let stats = self.metrics.get_latency_stats();
 
             info!(
-                "OrderBook Metrics - Orders: +{} -{} ~{} | Trades: {} | Latency (μs): add={:.2} cancel={:.2} modify={:.2} match={:.2}",
+                "OrderBook Metrics - Orders: +{} -{} ~{} | Trades: {} | Latency avg/p99 (μs): add={:.2}/{:.2} cancel={:.2}/{:.2} modify={:.2}/{:.2} match={:.2}/{:.2}",
                 self.metrics.get_orders_added(),
                 self.metrics.get_orders_cancelled(),
                 self.metrics.get_orders_modified(),
                 self.metrics.get_trades_executed(),
                 stats.add_order.avg_micros(),
+                stats.add_order.p99_micros(),
                 stats.cancel_order.avg_micros(),
+                stats.cancel_order.p99_micros(),
                 stats.modify_order.avg_micros(),
-                stats.match_order.avg_micros()
+                stats.modify_order.p99_micros(),
+                stats.match_order.avg_micros(),
+                stats.match_order.p99_micros()
             );
         }
     }
@@ -390,6 +553,26 @@ mod tests {
         assert_eq!(stats.add_order.samples, 1);
         assert!(stats.add_order.avg_nanos > 0);
         assert!(stats.add_order.avg_micros() >= 1000.0); // At least 1ms
+        assert!(stats.add_order.p99_nanos >= stats.add_order.min_nanos);
+    }
+
+    #[test]
+    fn test_latency_intervals() {
+        let metrics = OrderBookMetrics::new();
+        let other = OrderBookMetrics::new();
+
+        metrics.time_cancel_order(|| ());
+        other.time_cancel_order(|| ());
+        other.time_match_order(|| ());
+        metrics.merge_latency(&other);
+
+        let interval = metrics.take_latency_statistics();
+        assert_eq!(interval["cancel_order"].count, 2);
+        assert_eq!(interval["match_order"].count, 1);
+        assert_eq!(interval["add_order"].count, 0);
+
+        // Taking the interval statistics resets them
+        assert_eq!(metrics.get_latency_stats().cancel_order.samples, 0);
     }
 
     #[test]
@@ -0,0 +1,89 @@
+use metrics_exporter_prometheus::{BuildError, PrometheusBuilder, PrometheusHandle};
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+// Largest request head we are prepared to read before answering
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Prometheus scrape endpoint for the global metrics recorder
+///
+/// `install` replaces the process-wide recorder, so call it before building
+/// any `OrderBookMetrics`: their series bind to whichever recorder is
+/// installed when they are created. `run` then serves the text exposition
+/// format at `/metrics` on a listener the caller has bound, which lets the
+/// caller pick port 0 and read the address back.
+#[derive(Clone)]
+pub struct MetricsServer {
+    handle: PrometheusHandle,
+}
+
+impl MetricsServer {
+    /// Install a Prometheus recorder as the global recorder
+    pub fn install() -> Result<Self, BuildError> {
+        let handle = PrometheusBuilder::new().install_recorder()?;
+        Ok(Self { handle })
+    }
+
+    /// Render every series in the Prometheus text format
+    pub fn render(&self) -> String {
+        self.handle.render()
+    }
+
+    /// Accept scrapes until the listener fails
+    pub async fn run(self, listener: TcpListener) -> io::Result<()> {
+        info!(
+            "Prometheus metrics available at http://{}/metrics",
+            listener.local_addr()?
+        );
+
+        let server = Arc::new(self);
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let server = Arc::clone(&server);
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_connection(stream).await {
+                    warn!("Metrics scrape from {} failed: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(&self, mut stream: TcpStream) -> io::Result<()> {
+        // Read the request head; the body of a GET is ignored
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 || request.len() + n > MAX_REQUEST_BYTES {
+                return Ok(());
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+
+        let request_line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+        let mut parts = request_line.split(|&b| b == b' ');
+        let (method, path) = (parts.next(), parts.next());
+
+        let (status, body) = match (method, path) {
+            (Some(b"GET"), Some(b"/metrics")) => ("200 OK", self.render()),
+            _ => ("404 Not Found", String::new()),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+}
+
+impl std::fmt::Debug for MetricsServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetricsServer").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,159 @@
+//! Call auction price determination
+//!
+//! While a book is in a call phase, orders accumulate without matching and
+//! the book may cross. The uncross executes every crossing order at one
+//! equilibrium price, chosen from the limit prices in the book by:
+//!
+//! 1. maximum executable volume,
+//! 2. then minimum imbalance between demand and supply at the price,
+//! 3. then market pressure: if every remaining price leaves a buy surplus the
+//!    highest is used, if every one leaves a sell surplus the lowest is used,
+//! 4. then the remaining price closest to the reference price (the lower one
+//!    on a tie).
+
+use serde::{Deserialize, Serialize};
+
+use crate::orderbook::types::{Price, Quantity, Side};
+
+/// Outcome of an uncross at a single price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Equilibrium {
+    pub price: Price,
+    /// Quantity that executes at `price`
+    pub matched_quantity: Quantity,
+    /// Crossing quantity left unmatched at `price`
+    pub imbalance_quantity: Quantity,
+    /// Side holding the unmatched quantity, if any
+    pub imbalance_side: Option<Side>,
+}
+
+/// Find the equilibrium price of a call auction
+///
+/// `bids` and `asks` hold the total open quantity at each limit price, in
+/// any order. Without a `reference_price` the midpoint of the remaining
+/// prices is used in its place. Returns `None` if the book does not cross.
+pub fn equilibrium(
+    bids: &[(Price, Quantity)],
+    asks: &[(Price, Quantity)],
+    reference_price: Option<Price>,
+) -> Option<Equilibrium> {
+    let mut prices: Vec<Price> = bids.iter().chain(asks).map(|&(price, _)| price).collect();
+    prices.sort_unstable();
+    prices.dedup();
+
+    let mut candidates: Vec<Equilibrium> = prices
+        .into_iter()
+        .filter_map(|price| {
+            let demand: Quantity = bids
+                .iter()
+                .filter(|&&(bid, _)| bid >= price)
+                .map(|&(_, quantity)| quantity)
+                .sum();
+            let supply: Quantity = asks
+                .iter()
+                .filter(|&&(ask, _)| ask <= price)
+                .map(|&(_, quantity)| quantity)
+                .sum();
+            let matched_quantity = demand.min(supply);
+
+            (matched_quantity > 0).then(|| Equilibrium {
+                price,
+                matched_quantity,
+                imbalance_quantity: demand.abs_diff(supply),
+                imbalance_side: match demand.cmp(&supply) {
+                    std::cmp::Ordering::Greater => Some(Side::Buy),
+                    std::cmp::Ordering::Less => Some(Side::Sell),
+                    std::cmp::Ordering::Equal => None,
+                },
+            })
+        })
+        .collect();
+
+    // Maximum executable volume, then minimum imbalance
+    let max_volume = candidates.iter().map(|c| c.matched_quantity).max()?;
+    candidates.retain(|c| c.matched_quantity == max_volume);
+    let min_imbalance = candidates.iter().map(|c| c.imbalance_quantity).min()?;
+    candidates.retain(|c| c.imbalance_quantity == min_imbalance);
+
+    // Market pressure; candidates are in ascending price order
+    if candidates
+        .iter()
+        .all(|c| c.imbalance_side == Some(Side::Buy))
+    {
+        return candidates.last().copied();
+    }
+    if candidates
+        .iter()
+        .all(|c| c.imbalance_side == Some(Side::Sell))
+    {
+        return candidates.first().copied();
+    }
+
+    // Reference price
+    let (lowest, highest) = (candidates.first()?.price, candidates.last()?.price);
+    let reference = reference_price.unwrap_or(lowest + (highest - lowest) / 2);
+    candidates
+        .into_iter()
+        .min_by_key(|c| c.price.abs_diff(reference))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_cross() {
+        assert_eq!(equilibrium(&[(99, 10)], &[(100, 10)], Some(100)), None);
+        assert_eq!(equilibrium(&[], &[(100, 10)], None), None);
+    }
+
+    #[test]
+    fn test_maximum_volume_and_minimum_imbalance() {
+        // 102 executes 30, every other price less
+        let bids = [(103, 10), (102, 20), (100, 30)];
+        let asks = [(99, 10), (102, 20), (104, 40)];
+        let result = equilibrium(&bids, &asks, Some(100)).unwrap();
+        assert_eq!(result.price, 102);
+        assert_eq!(result.matched_quantity, 30);
+        assert_eq!(result.imbalance_quantity, 0);
+        assert_eq!(result.imbalance_side, None);
+
+        // Every price executes 20; 101 and 102 leave the smaller imbalance,
+        // and the sell surplus takes the lower of them
+        let bids = [(102, 20), (100, 30)];
+        let asks = [(100, 20), (101, 5)];
+        let result = equilibrium(&bids, &asks, Some(102)).unwrap();
+        assert_eq!(result.price, 101);
+        assert_eq!(result.matched_quantity, 20);
+        assert_eq!(result.imbalance_quantity, 5);
+        assert_eq!(result.imbalance_side, Some(Side::Sell));
+    }
+
+    #[test]
+    fn test_market_pressure() {
+        // 100 and 101 both execute 10 with 5 more bid for
+        let bids = [(101, 15)];
+        let asks = [(100, 10)];
+        let result = equilibrium(&bids, &asks, Some(90)).unwrap();
+        assert_eq!(result.price, 101);
+        assert_eq!(result.imbalance_side, Some(Side::Buy));
+
+        let bids = [(101, 10)];
+        let asks = [(100, 15)];
+        let result = equilibrium(&bids, &asks, Some(110)).unwrap();
+        assert_eq!(result.price, 100);
+        assert_eq!(result.imbalance_side, Some(Side::Sell));
+    }
+
+    #[test]
+    fn test_reference_price() {
+        // Balanced at both limit prices
+        let bids = [(104, 10)];
+        let asks = [(100, 10)];
+        assert_eq!(equilibrium(&bids, &asks, Some(103)).unwrap().price, 104);
+        assert_eq!(equilibrium(&bids, &asks, Some(101)).unwrap().price, 100);
+        assert_eq!(equilibrium(&bids, &asks, Some(90)).unwrap().price, 100);
+        // The midpoint stands in for a missing reference price
+        assert_eq!(equilibrium(&bids, &asks, None).unwrap().price, 100);
+    }
+}
@@ -1,30 +1,96 @@
+use chrono::{DateTime, Utc};
+use crossbeam::channel::Receiver;
 use dashmap::DashMap;
+use parking_lot::Mutex;
+use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 
+use crate::metrics::OrderBookMetrics;
+use crate::orderbook::auction::{self, Equilibrium};
+use crate::orderbook::circuit_breaker::CircuitBreaker;
 use crate::orderbook::error::OrderBookError;
-use crate::orderbook::price_level::PriceLevel;
+use crate::orderbook::instrument::Instrument;
+use crate::orderbook::journal::{
+    FsyncPolicy, Journal, JournalCommand, JournalContents, JournalRecord,
+};
+use crate::orderbook::ladder::PriceLadder;
+use crate::orderbook::market_data::{
+    AuctionFeed, AuctionUpdate, DepthFeed, DepthUpdate, OrderFeed, OrderMessage, OrderSnapshot,
+};
+use crate::orderbook::matching::MatchingEngine;
+use crate::orderbook::operations::OrderOperations;
+use crate::orderbook::risk::{MarketContext, RiskCheck};
+use crate::orderbook::snapshot::{FullSnapshot, LevelSnapshot, SNAPSHOT_VERSION};
+use crate::orderbook::stop_book::StopBook;
 use crate::orderbook::types::{
-    BookSnapshot, MarketEvent, Order, OrderId, OrderLocation, OrderStatus, OrderType, Price,
-    PriceLevelInfo, Quantity, Side, Trade,
+    BookSnapshot, HaltReason, MarketEvent, Order, OrderId, OrderLocation, OrderStatus, OrderType,
+    PostOnly, Price, PriceLevelInfo, Quantity, SelfTradePrevention, Side, TimeInForce, Trade,
+    TradingPhase, TradingStatus,
 };
+use crate::utils::ids::{IdGenerator, RandomIds};
+use crate::utils::time::{ClockSource, SystemClock};
 
-/// High-performance lock-free order book
+/// High-performance order book
+///
+/// Mutating commands are serialized by the book's matching lock; read-only
+/// queries stay lock-free.
 #[derive(Debug)]
 pub struct OrderBook {
     pub symbol: String,
 
-    // Price levels: Price -> PriceLevel
-    // Using u64 keys for cache-friendly iteration
-    bids: DashMap<Price, Arc<PriceLevel>>, // Buy orders (highest price first)
-    asks: DashMap<Price, Arc<PriceLevel>>, // Sell orders (lowest price first)
+    // Tick size table, lot size and order size limits
+    instrument: Instrument,
+
+    // Default self-trade prevention mode (see `prevent_self_trade`)
+    stp_mode: Option<SelfTradePrevention>,
+
+    // Price bands (see `with_circuit_breaker`)
+    circuit_breaker: Option<CircuitBreaker>,
+
+    // Pre-trade risk pipeline (see `with_risk_check`)
+    risk_checks: Vec<Arc<dyn RiskCheck>>,
+
+    // Operation counters, latencies and book gauges (see `with_metrics`)
+    metrics: Option<Arc<OrderBookMetrics>>,
+
+    // Serializes all mutating commands, acting as a single-writer sequencer.
+    // An incoming order's match-then-rest path, including any stops it
+    // triggers, executes atomically against a consistent book, and commands
+    // take effect in the order they take the lock. Read-only queries see each
+    // value as of the last completed command that updated it; `snapshot`
+    // takes the lock, so it never observes a half-applied command.
+    matching_lock: Mutex<()>,
+
+    // Continuous trading or a call (see `start_call`)
+    phase: Mutex<TradingPhase>,
+
+    // Price levels: sorted ladders with O(1) best price
+    bids: PriceLadder, // Buy orders (highest price first)
+    asks: PriceLadder, // Sell orders (lowest price first)
 
     // Order tracking
     order_locations: DashMap<OrderId, OrderLocation>,
 
+    // Untriggered stop and stop-limit orders
+    stop_book: StopBook,
+
+    // Level-2 depth, level-3 order and auction feeds
+    depth_feed: DepthFeed,
+    order_feed: OrderFeed,
+    auction_feed: AuctionFeed,
+
+    // Write-ahead journal of accepted commands (see `record_command`)
+    journal: Option<Mutex<Journal>>,
+
+    // Identity and time sources (see `stamp_order`)
+    clock: Arc<dyn ClockSource>,
+    ids: Arc<dyn IdGenerator>,
+
     // Market state
     last_trade_price: AtomicU64,
+    reference_price: AtomicU64,
     sequence_number: AtomicU64,
 
     // Statistics
@@ -37,106 +103,343 @@ impl OrderBook {
         info!("Creating new order book for symbol: {}", symbol);
 
         Self {
+            instrument: Instrument::new(symbol.clone()),
+            stp_mode: None,
+            circuit_breaker: None,
+            risk_checks: Vec::new(),
+            metrics: None,
             symbol,
-            bids: DashMap::new(),
-            asks: DashMap::new(),
+            matching_lock: Mutex::new(()),
+            phase: Mutex::new(TradingPhase::Continuous),
+            bids: PriceLadder::for_side(Side::Buy),
+            asks: PriceLadder::for_side(Side::Sell),
             order_locations: DashMap::new(),
+            stop_book: StopBook::new(),
+            depth_feed: DepthFeed::new(),
+            order_feed: OrderFeed::new(),
+            auction_feed: AuctionFeed::new(),
+            journal: None,
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(RandomIds),
             last_trade_price: AtomicU64::new(0),
+            reference_price: AtomicU64::new(0),
             sequence_number: AtomicU64::new(0),
             total_trades: AtomicU64::new(0),
             total_volume: AtomicU64::new(0),
         }
     }
 
-    /// Add a limit order to the book
-    pub fn add_limit_order(&self, mut order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
-        debug!("Adding limit order: {:?}", order);
+    /// Use `clock` for trade and snapshot timestamps
+    pub fn with_clock(mut self, clock: Arc<dyn ClockSource>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Use `ids` for trade ids and orders stamped by `stamp_order`
+    pub fn with_id_generator(mut self, ids: Arc<dyn IdGenerator>) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Enforce `instrument`'s trading rules on incoming orders
+    pub fn with_instrument(mut self, instrument: Instrument) -> Result<Self, OrderBookError> {
+        self.check_symbol(&instrument.symbol)?;
+        self.instrument = instrument;
+        Ok(self)
+    }
+
+    /// Prevent self-trades with `mode` for orders that do not set their own
+    ///
+    /// An incoming order never trades with a resting order that has the same
+    /// STP key (`Order::stp_key`) when it carries an STP mode of its own or
+    /// the book has one.
+    pub fn with_self_trade_prevention(mut self, mode: SelfTradePrevention) -> Self {
+        self.stp_mode = Some(mode);
+        self
+    }
+
+    /// Stop matching at `circuit_breaker`'s price bands
+    ///
+    /// An incoming order stops matching at the first price outside the static
+    /// band around the reference price or the dynamic band around the last
+    /// trade price. The book then halts or enters a volatility auction,
+    /// reported as `TradingHalted`, and the rest of the order is handled as if
+    /// the book had run out of liquidity. The trip is journaled after the
+    /// command that caused it, so replay ends in the same trading phase.
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Run new orders through `check` after any checks already added
+    ///
+    /// New limit, market and stop orders, and quantity amends, pass through
+    /// every check in order before they are journaled; the first failure
+    /// rejects them with `RiskRejected`. Each check then sees the events of
+    /// every command the book accepts, which is how `AccountRisk` tracks open
+    /// orders and positions. Replayed commands are not checked, but checks
+    /// attached before `open_journal` or `restore_snapshot` see what they
+    /// restore, and a check attached to a book that already holds orders is
+    /// shown them as `OrderAdded`.
+    pub fn with_risk_check(mut self, check: Arc<dyn RiskCheck>) -> Self {
+        let resting: Vec<MarketEvent> = self
+            .open_orders()
+            .into_iter()
+            .map(|order| MarketEvent::OrderAdded { order })
+            .collect();
+        if !resting.is_empty() {
+            check.on_events(None, &resting);
+        }
+        self.risk_checks.push(check);
+        self
+    }
 
-        if order.symbol != self.symbol {
-            return Err(OrderBookError::InvalidSymbol);
+    /// Record operations and book state in `metrics`, if any
+    ///
+    /// Every accepted add, cancel and modify is counted and timed, along with
+    /// the matching step of each incoming order, and every trade adds to the
+    /// trade, volume and notional counters. After each command the book
+    /// refreshes the order, level, spread and best price gauges. Build the
+    /// metrics with `OrderBookMetrics::for_symbol` to label them with the
+    /// book's symbol. Without metrics the book only pays for an `Option`
+    /// check per command.
+    pub fn with_metrics(mut self, metrics: Option<Arc<OrderBookMetrics>>) -> Self {
+        self.metrics = metrics;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_book_stats(&self.get_stats());
         }
+        self
+    }
 
-        let mut events = Vec::new();
+    /// Get the metrics attached with `with_metrics`
+    pub fn metrics(&self) -> Option<&Arc<OrderBookMetrics>> {
+        self.metrics.as_ref()
+    }
 
-        // Try to match against opposite side first
-        let trades = self.match_order(&mut order)?;
+    /// Get the trading rules enforced on incoming orders
+    pub fn instrument(&self) -> &Instrument {
+        &self.instrument
+    }
 
-        // Add trade events
-        for trade in trades {
-            events.push(MarketEvent::Trade { trade });
+    /// Give `order` an id and timestamp from the book's sources
+    ///
+    /// Trade ids and event timestamps come from the same sources, so with
+    /// deterministic ones (`with_id_generator`, `with_clock`) the same
+    /// sequence of stamped commands produces a byte-identical `MarketEvent`
+    /// stream.
+    pub fn stamp_order(&self, mut order: Order) -> Order {
+        order.id = self.ids.next_id();
+        order.timestamp = self.clock.now();
+        order
+    }
+
+    /// Create an order book that journals every accepted command to `path`
+    ///
+    /// If the journal already exists, the book is recovered from it first and
+    /// new commands are appended after the last intact record. Matching is
+    /// deterministic, so replaying the journal in sequence order rebuilds the
+    /// book exactly, provided it is configured as it was when the journal was
+    /// written (see `open_journal`).
+    pub fn with_journal(
+        symbol: String,
+        path: impl AsRef<Path>,
+        policy: FsyncPolicy,
+    ) -> Result<Self, OrderBookError> {
+        Self::new(symbol).open_journal(path, policy)
+    }
+
+    /// Journal every accepted command to `path`, first replaying it into
+    /// this book if it already exists
+    ///
+    /// Call this after attaching the instrument, circuit breaker, self-trade
+    /// prevention and risk checks, so replay makes the decisions the live
+    /// book made and the risk checks see the replayed events.
+    pub fn open_journal(
+        mut self,
+        path: impl AsRef<Path>,
+        policy: FsyncPolicy,
+    ) -> Result<Self, OrderBookError> {
+        let path = path.as_ref();
+        if path.exists() {
+            let contents = Journal::read(path).map_err(journal_error)?;
+            self.check_symbol(&contents.header.symbol)?;
+            return self.replay_journal(path, contents, policy);
         }
 
-        // If order has remaining quantity, add to book
-        if order.remaining_quantity > 0 {
-            self.add_order_to_book(order.clone())?;
-            events.push(MarketEvent::OrderAdded { order });
+        let journal = Journal::create(path, &self.symbol, policy).map_err(journal_error)?;
+        self.journal = Some(Mutex::new(journal));
+        Ok(self)
+    }
+
+    /// Rebuild an order book by replaying the journal at `path`
+    ///
+    /// The recovered book keeps appending to the same journal, syncing every
+    /// record. A torn record at the tail (from a crash mid-write) is dropped.
+    pub fn recover_from(path: impl AsRef<Path>) -> Result<Self, OrderBookError> {
+        Self::recover_with_policy(path, FsyncPolicy::default())
+    }
+
+    /// Rebuild an order book from the journal at `path`, appending new
+    /// commands under `policy`
+    ///
+    /// The book has default settings; use `open_journal` to recover a book
+    /// with a circuit breaker, self-trade prevention or instrument rules.
+    pub fn recover_with_policy(
+        path: impl AsRef<Path>,
+        policy: FsyncPolicy,
+    ) -> Result<Self, OrderBookError> {
+        let path = path.as_ref();
+        let contents = Journal::read(path).map_err(journal_error)?;
+
+        Self::new(contents.header.symbol.clone()).replay_journal(path, contents, policy)
+    }
+
+    /// Replay `contents` into this book, then keep appending to `path`
+    fn replay_journal(
+        mut self,
+        path: &Path,
+        contents: JournalContents,
+        policy: FsyncPolicy,
+    ) -> Result<Self, OrderBookError> {
+        let mut next_sequence = 0;
+        for record in contents.records {
+            next_sequence = record.sequence + 1;
+            let order = match &record.command {
+                JournalCommand::AddLimit { order }
+                | JournalCommand::AddMarket { order }
+                | JournalCommand::AddStop { order }
+                    if !self.risk_checks.is_empty() =>
+                {
+                    Some(order.clone())
+                }
+                _ => None,
+            };
+            match self.apply_command(record.command) {
+                Ok(events) => self.report_risk(order.as_ref(), &events),
+                // Rejected the same way when it was first applied
+                Err(e) => debug!("Replayed command {} was rejected: {}", record.sequence, e),
+            }
+        }
+        self.sequence_number.store(next_sequence, Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_book_stats(&self.get_stats());
         }
 
-        Ok(events)
+        info!(
+            "Recovered {} from {}: {} orders, {} stop orders, next sequence {}",
+            self.symbol,
+            path.display(),
+            self.total_orders(),
+            self.total_stop_orders(),
+            next_sequence
+        );
+
+        let journal =
+            Journal::open_append(path, contents.valid_len, policy).map_err(journal_error)?;
+        self.journal = Some(Mutex::new(journal));
+        Ok(self)
     }
 
-    /// Add a market order (always executes immediately)
-    pub fn add_market_order(&self, mut order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+    /// Add a limit order to the book
+    pub fn add_limit_order(&self, order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+        debug!("Adding limit order: {:?}", order);
+
+        self.check_symbol(&order.symbol)?;
+        self.instrument.validate_order(&order)?;
+        self.check_time_in_force(&order)?;
+
+        let _guard = self.matching_lock.lock();
+        self.check_not_halted()?;
+        self.check_risk(&order)?;
+        self.record_command(|| JournalCommand::AddLimit {
+            order: order.clone(),
+        })?;
+
+        self.apply_new_order(order, |order| self.apply_limit_order(order))
+    }
+
+    /// Add a market or market-to-limit order (always executes immediately)
+    ///
+    /// With `MarketProtection` on the instrument, a market order trades no
+    /// further from the opposite best price than the protection allows. Its
+    /// final state is always reported: `OrderFilled` once it fills completely,
+    /// otherwise the unfilled remainder is cancelled (`OrderCancelled`). A
+    /// `MarketToLimit` order instead rests its remainder as a limit order at
+    /// the last price it traded at. A market order that finds nothing to trade
+    /// against fails with `NoLiquidity`.
+    pub fn add_market_order(&self, order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
         debug!("Adding market order: {:?}", order);
 
-        if order.symbol != self.symbol {
-            return Err(OrderBookError::InvalidSymbol);
-        }
+        self.check_symbol(&order.symbol)?;
 
-        if order.order_type != OrderType::Market {
+        if !matches!(
+            order.order_type,
+            OrderType::Market | OrderType::MarketToLimit
+        ) {
             return Err(OrderBookError::InvalidOrderType);
         }
+        self.instrument.validate_order(&order)?;
 
-        let mut events = Vec::new();
+        let _guard = self.matching_lock.lock();
+        self.check_not_halted()?;
+        self.check_risk(&order)?;
+        self.record_command(|| JournalCommand::AddMarket {
+            order: order.clone(),
+        })?;
 
-        // Market orders must execute immediately
-        let trades = self.execute_market_order(&mut order)?;
+        self.apply_new_order(order, |order| self.apply_market_order(order))
+    }
 
-        if trades.is_empty() {
-            return Err(OrderBookError::NoLiquidity);
-        }
+    /// Add a stop or stop-limit order
+    ///
+    /// The order waits in the stop book until the last trade price reaches its
+    /// stop price, then enters the book as a market (stop) or limit
+    /// (stop-limit) order. A stop whose trigger is already reached is released
+    /// immediately.
+    pub fn add_stop_order(&self, order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+        debug!("Adding stop order: {:?}", order);
 
-        // Add trade events
-        for trade in trades {
-            events.push(MarketEvent::Trade { trade });
-        }
+        self.check_symbol(&order.symbol)?;
 
-        Ok(events)
+        if order.stop_price().is_none() {
+            return Err(OrderBookError::InvalidOrderType);
+        }
+        OrderOperations::validate_order(&order)?;
+        self.instrument.validate_order(&order)?;
+        self.check_time_in_force(&order)?;
+
+        let _guard = self.matching_lock.lock();
+        self.check_not_halted()?;
+        self.check_risk(&order)?;
+        self.record_command(|| JournalCommand::AddStop {
+            order: order.clone(),
+        })?;
+
+        self.apply_new_order(order, |order| self.apply_stop_order(order))
     }
 
     /// Cancel an order
     pub fn cancel_order(&self, order_id: &OrderId) -> Result<MarketEvent, OrderBookError> {
         debug!("Cancelling order: {}", order_id);
 
-        let location = self
-            .order_locations
-            .remove(order_id)
-            .map(|(_, loc)| loc)
-            .ok_or(OrderBookError::OrderNotFound)?;
-
-        let price_levels = match location.side {
-            Side::Buy => &self.bids,
-            Side::Sell => &self.asks,
-        };
-
-        if let Some(level) = price_levels.get(&location.price) {
-            if let Some(mut order) = level.remove_order(order_id) {
-                let remaining_quantity = order.remaining_quantity;
-                order.cancel();
-
-                // Clean up empty price level
-                if level.is_empty() {
-                    price_levels.remove(&location.price);
-                }
+        let _guard = self.matching_lock.lock();
 
-                return Ok(MarketEvent::OrderCancelled {
-                    order_id: *order_id,
-                    remaining_quantity,
-                });
-            }
+        if !self.order_locations.contains_key(order_id) && !self.stop_book.contains(order_id) {
+            return Err(OrderBookError::OrderNotFound);
         }
+        self.record_command(|| JournalCommand::Cancel {
+            order_id: *order_id,
+        })?;
 
-        Err(OrderBookError::OrderNotFound)
+        let event = match &self.metrics {
+            Some(metrics) => metrics.time_cancel_order(|| self.apply_cancel(order_id))?,
+            None => self.apply_cancel(order_id)?,
+        };
+        self.report_risk(None, std::slice::from_ref(&event));
+        self.report_metrics(std::slice::from_ref(&event), |metrics| {
+            metrics.increment_orders_cancelled()
+        });
+        Ok(event)
     }
 
     /// Modify an order's quantity
@@ -147,41 +450,159 @@ impl OrderBook {
     ) -> Result<MarketEvent, OrderBookError> {
         debug!("Modifying order {} to quantity {}", order_id, new_quantity);
 
-        let location = self
-            .order_locations
-            .get(order_id)
-            .map(|entry| entry.value().clone())
-            .ok_or(OrderBookError::OrderNotFound)?;
+        self.instrument.validate_quantity(new_quantity)?;
 
-        let price_levels = match location.side {
-            Side::Buy => &self.bids,
-            Side::Sell => &self.asks,
-        };
+        let _guard = self.matching_lock.lock();
 
-        if let Some(level) = price_levels.get(&location.price) {
-            if level
-                .modify_order_quantity(order_id, new_quantity)
-                .is_some()
-            {
-                return Ok(MarketEvent::OrderModified {
-                    order_id: *order_id,
-                    new_price: None,
-                    new_quantity: Some(new_quantity),
-                });
+        if !self.order_locations.contains_key(order_id) {
+            return Err(OrderBookError::OrderNotFound);
+        }
+        self.check_not_halted()?;
+        self.check_amend_risk(order_id, new_quantity)?;
+        self.record_command(|| JournalCommand::ModifyQuantity {
+            order_id: *order_id,
+            new_quantity,
+        })?;
+
+        let event = match &self.metrics {
+            Some(metrics) => {
+                metrics.time_modify_order(|| self.apply_modify_quantity(order_id, new_quantity))?
             }
+            None => self.apply_modify_quantity(order_id, new_quantity)?,
+        };
+        self.report_risk(None, std::slice::from_ref(&event));
+        self.report_metrics(std::slice::from_ref(&event), |metrics| {
+            metrics.increment_orders_modified()
+        });
+        Ok(event)
+    }
+
+    /// Expire every good-till-date order whose expiry time has passed
+    ///
+    /// DAY and good-till-date orders rest like any other order until a sweep
+    /// removes them. Meant to be called from a timer; each expired order is
+    /// reported as `OrderExpired`. Nothing is journaled when no order is due.
+    pub fn expire_orders(&self) -> Result<Vec<MarketEvent>, OrderBookError> {
+        self.sweep_expired(false)
+    }
+
+    /// Close the trading session, expiring DAY orders along with any
+    /// good-till-date orders that are due
+    pub fn close_session(&self) -> Result<Vec<MarketEvent>, OrderBookError> {
+        self.sweep_expired(true)
+    }
+
+    /// Start an opening (`PreOpen`) or closing (`PreClose`) call
+    ///
+    /// Only a book in continuous trading can enter a call. Limit orders then
+    /// rest without matching, so the book may cross, and market orders are
+    /// refused. Every change to the book during the call publishes the
+    /// indicative uncross on the auction feed (`subscribe_auction`).
+    pub fn start_call(&self, phase: TradingPhase) -> Result<(), OrderBookError> {
+        let _guard = self.matching_lock.lock();
+
+        if !phase.is_call() || self.trading_phase() != TradingPhase::Continuous {
+            return Err(OrderBookError::InvalidOrderState);
         }
+        self.record_command(|| JournalCommand::StartCall { phase })?;
 
-        Err(OrderBookError::OrderNotFound)
+        self.apply_start_call(phase);
+        Ok(())
+    }
+
+    /// End the call: execute every crossing order at the equilibrium price
+    /// and resume continuous trading
+    ///
+    /// The equilibrium (see [`auction`]) uses the last trade price as the
+    /// reference price. Self-trade prevention does not apply to the uncross.
+    /// Ending a volatility auction reports `TradingResumed`.
+    pub fn uncross(&self) -> Result<Vec<MarketEvent>, OrderBookError> {
+        let _guard = self.matching_lock.lock();
+
+        if !self.trading_phase().is_call() {
+            return Err(OrderBookError::InvalidOrderState);
+        }
+        self.record_command(|| JournalCommand::Uncross)?;
+
+        let events = self.apply_uncross();
+        self.report_risk(None, &events);
+        self.report_metrics(&events, |_| {});
+        Ok(events)
+    }
+
+    /// Get the current trading phase
+    pub fn trading_phase(&self) -> TradingPhase {
+        *self.phase.lock()
+    }
+
+    /// Get the price and volume the book would uncross at now
+    pub fn indicative_uncross(&self) -> Option<Equilibrium> {
+        let _guard = self.matching_lock.lock();
+        self.equilibrium()
+    }
+
+    /// Halt trading until `resume` is called
+    ///
+    /// While halted, new orders and quantity changes are refused with
+    /// `TradingNotAllowed`; cancels are accepted.
+    pub fn halt(&self) -> Result<MarketEvent, OrderBookError> {
+        let _guard = self.matching_lock.lock();
+
+        if self.trading_phase() == TradingPhase::Halted {
+            return Err(OrderBookError::InvalidOrderState);
+        }
+        self.record_command(|| JournalCommand::Halt)?;
+
+        Ok(self.apply_halt(TradingPhase::Halted, HaltReason::Admin))
+    }
+
+    /// Resume continuous trading after a halt, uncrossing any orders that
+    /// cross, and report `TradingResumed`
+    pub fn resume(&self) -> Result<Vec<MarketEvent>, OrderBookError> {
+        let _guard = self.matching_lock.lock();
+
+        if self.trading_phase() != TradingPhase::Halted {
+            return Err(OrderBookError::InvalidOrderState);
+        }
+        self.record_command(|| JournalCommand::Resume)?;
+
+        let events = self.apply_uncross();
+        self.report_risk(None, &events);
+        self.report_metrics(&events, |_| {});
+        Ok(events)
+    }
+
+    /// Set the reference price of the static price band
+    ///
+    /// Otherwise the reference price is the last uncross price.
+    pub fn set_reference_price(&self, price: Price) -> Result<(), OrderBookError> {
+        if price == 0 {
+            return Err(OrderBookError::InvalidPrice);
+        }
+
+        let _guard = self.matching_lock.lock();
+        self.record_command(|| JournalCommand::SetReferencePrice { price })?;
+
+        self.reference_price.store(price, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Get the reference price of the static price band
+    pub fn reference_price(&self) -> Option<Price> {
+        match self.reference_price.load(Ordering::Relaxed) {
+            0 => None,
+            price => Some(price),
+        }
     }
 
     /// Get current best bid price
     pub fn best_bid(&self) -> Option<Price> {
-        self.bids.iter().map(|entry| *entry.key()).max()
+        self.bids.best_price()
     }
 
     /// Get current best ask price
     pub fn best_ask(&self) -> Option<Price> {
-        self.asks.iter().map(|entry| *entry.key()).min()
+        self.asks.best_price()
     }
 
     /// Get current spread
@@ -202,58 +623,188 @@ impl OrderBook {
         }
     }
 
-    /// Generate order book snapshot
-    pub fn snapshot(&self) -> BookSnapshot {
-        let mut bids: Vec<_> = self
-            .bids
-            .iter()
-            .map(|entry| {
-                let price = *entry.key();
-                let level = entry.value();
-                let (quantity, order_count) = level.get_depth_info();
-                PriceLevelInfo {
-                    price,
-                    quantity,
-                    order_count,
-                }
-            })
-            .collect();
+    /// Subscribe to level-2 depth updates
+    ///
+    /// Subscribe before taking a `snapshot`; updates up to the snapshot's
+    /// `sequence` are already reflected in it (see `L2BookBuilder`).
+    pub fn subscribe_depth(&self) -> Receiver<DepthUpdate> {
+        self.depth_feed.subscribe()
+    }
 
-        let mut asks: Vec<_> = self
-            .asks
-            .iter()
-            .map(|entry| {
-                let price = *entry.key();
-                let level = entry.value();
-                let (quantity, order_count) = level.get_depth_info();
-                PriceLevelInfo {
-                    price,
-                    quantity,
-                    order_count,
-                }
-            })
-            .collect();
+    /// Subscribe to level-3 (market by order) messages
+    ///
+    /// Subscribe before taking an `order_snapshot`; messages up to the
+    /// snapshot's `sequence` are already reflected in it (see `L3BookBuilder`).
+    pub fn subscribe_orders(&self) -> Receiver<OrderMessage> {
+        self.order_feed.subscribe()
+    }
+
+    /// Subscribe to trading phase changes and indicative uncross updates
+    pub fn subscribe_auction(&self) -> Receiver<AuctionUpdate> {
+        self.auction_feed.subscribe()
+    }
 
-        // Sort bids by price descending (highest first)
-        bids.sort_by(|a, b| b.price.cmp(&a.price));
+    /// Capture every resting order in queue order, tagged with the L3 feed
+    /// sequence it reflects
+    pub fn order_snapshot(&self) -> OrderSnapshot {
+        let _guard = self.matching_lock.lock();
 
-        // Sort asks by price ascending (lowest first)
-        asks.sort_by(|a, b| a.price.cmp(&b.price));
+        OrderSnapshot {
+            symbol: self.symbol.clone(),
+            sequence: self.order_feed.last_sequence(),
+            bids: Self::displayed_levels(&self.bids),
+            asks: Self::displayed_levels(&self.asks),
+        }
+    }
+
+    /// Generate order book snapshot
+    pub fn snapshot(&self) -> BookSnapshot {
+        let _guard = self.matching_lock.lock();
+
+        // Ladders already iterate best price first
+        let depth = |ladder: &PriceLadder| -> Vec<PriceLevelInfo> {
+            ladder
+                .levels()
+                .into_iter()
+                .map(|(price, level)| {
+                    let (quantity, order_count) = level.get_depth_info();
+                    PriceLevelInfo {
+                        price,
+                        quantity,
+                        order_count,
+                    }
+                })
+                .collect()
+        };
+        let bids = depth(&self.bids);
+        let asks = depth(&self.asks);
 
         BookSnapshot {
             symbol: self.symbol.clone(),
-            timestamp: chrono::Utc::now(),
+            timestamp: self.clock.now(),
+            sequence: self.depth_feed.last_sequence(),
             bids,
             asks,
             last_trade_price: self.last_trade_price(),
         }
     }
 
+    /// Capture every resting and stop order, in queue order, with the
+    /// market state and counters needed to restore the book exactly
+    pub fn full_snapshot(&self) -> FullSnapshot {
+        let _guard = self.matching_lock.lock();
+
+        FullSnapshot {
+            version: SNAPSHOT_VERSION,
+            symbol: self.symbol.clone(),
+            timestamp: self.clock.now(),
+            sequence_number: self.sequence_number(),
+            last_trade_price: self.last_trade_price(),
+            total_trades: self.total_trades.load(Ordering::Relaxed),
+            total_volume: self.total_volume.load(Ordering::Relaxed),
+            trading_phase: self.trading_phase(),
+            reference_price: self.reference_price(),
+            bids: Self::level_snapshots(&self.bids),
+            asks: Self::level_snapshots(&self.asks),
+            stop_orders: self.stop_book.orders(),
+        }
+    }
+
+    /// Rebuild an order book from a full snapshot
+    ///
+    /// The book has default settings; use `restore_snapshot` to restore a
+    /// book with instrument rules or risk checks.
+    pub fn from_snapshot(snapshot: FullSnapshot) -> Result<Self, OrderBookError> {
+        Self::new(snapshot.symbol.clone()).restore_snapshot(snapshot)
+    }
+
+    /// Load the orders, market state and counters in `snapshot` into this
+    /// empty book
+    ///
+    /// Orders are re-queued in snapshot order, so time priority within each
+    /// level is preserved. Call this after attaching the instrument and risk
+    /// checks: every restored order must pass the instrument's rules, and the
+    /// risk checks see the restored orders as added.
+    pub fn restore_snapshot(self, snapshot: FullSnapshot) -> Result<Self, OrderBookError> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(OrderBookError::SystemError(format!(
+                "unsupported snapshot version {}",
+                snapshot.version
+            )));
+        }
+        self.check_symbol(&snapshot.symbol)?;
+
+        let mut restored = Vec::new();
+        for level in snapshot.bids.into_iter().chain(snapshot.asks) {
+            for order in level.orders {
+                if order.symbol != self.symbol || order.price != level.price {
+                    return Err(OrderBookError::InvalidOrderState);
+                }
+                self.instrument.validate_order(&order)?;
+                self.add_order_to_book(order.clone())?;
+                restored.push(MarketEvent::OrderAdded { order });
+            }
+        }
+        for order in snapshot.stop_orders {
+            if order.symbol != self.symbol {
+                return Err(OrderBookError::InvalidOrderState);
+            }
+            self.instrument.validate_order(&order)?;
+            self.stop_book.add_order(order.clone());
+            restored.push(MarketEvent::OrderAdded { order });
+        }
+        // Only once every order is known to be valid, as the checks may be
+        // shared with other books
+        if !restored.is_empty() {
+            self.report_risk(None, &restored);
+        }
+
+        self.last_trade_price
+            .store(snapshot.last_trade_price.unwrap_or(0), Ordering::Relaxed);
+        self.sequence_number
+            .store(snapshot.sequence_number, Ordering::Relaxed);
+        self.total_trades
+            .store(snapshot.total_trades, Ordering::Relaxed);
+        self.total_volume
+            .store(snapshot.total_volume, Ordering::Relaxed);
+        *self.phase.lock() = snapshot.trading_phase;
+        self.reference_price
+            .store(snapshot.reference_price.unwrap_or(0), Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_book_stats(&self.get_stats());
+        }
+
+        info!(
+            "Restored {} from snapshot: {} orders, {} stop orders, next sequence {}",
+            self.symbol,
+            self.total_orders(),
+            self.total_stop_orders(),
+            snapshot.sequence_number
+        );
+
+        Ok(self)
+    }
+
     /// Get total number of orders in the book
     pub fn total_orders(&self) -> usize {
         self.order_locations.len()
     }
 
+    /// Get number of untriggered stop orders
+    pub fn total_stop_orders(&self) -> usize {
+        self.stop_book.len()
+    }
+
+    /// Check whether an order is resting in the book or waiting as a stop
+    pub fn contains_order(&self, order_id: &OrderId) -> bool {
+        self.order_locations.contains_key(order_id) || self.stop_book.contains(order_id)
+    }
+
+    /// Get the sequence number the next accepted command will receive
+    pub fn sequence_number(&self) -> u64 {
+        self.sequence_number.load(Ordering::Relaxed)
+    }
+
     /// Get statistics
     pub fn get_stats(&self) -> OrderBookStats {
         OrderBookStats {
@@ -272,134 +823,844 @@ impl OrderBook {
 
     // Private helper methods
 
-    fn match_order(&self, order: &mut Order) -> Result<Vec<Trade>, OrderBookError> {
-        let mut trades = Vec::new();
-        let opposite_side = match order.side {
-            Side::Buy => &self.asks,
-            Side::Sell => &self.bids,
+    fn check_symbol(&self, symbol: &str) -> Result<(), OrderBookError> {
+        if symbol != self.symbol {
+            return Err(OrderBookError::SymbolMismatch {
+                expected: self.symbol.clone(),
+                received: symbol.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Refuse new orders and amendments while trading is halted
+    fn check_not_halted(&self) -> Result<(), OrderBookError> {
+        if self.trading_phase() == TradingPhase::Halted {
+            return Err(OrderBookError::TradingNotAllowed {
+                symbol: self.symbol.clone(),
+                status: TradingStatus::Halted,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reject good-till-date orders that would already have expired
+    fn check_time_in_force(&self, order: &Order) -> Result<(), OrderBookError> {
+        if let TimeInForce::GoodTillDate(expire_time) = order.time_in_force {
+            if expire_time <= self.clock.now() {
+                return Err(OrderBookError::InvalidOrderState);
+            }
+        }
+        Ok(())
+    }
+
+    fn sweep_expired(&self, session_closed: bool) -> Result<Vec<MarketEvent>, OrderBookError> {
+        let _guard = self.matching_lock.lock();
+
+        let as_of = self.clock.now();
+        let expired = self.expired_orders(as_of, session_closed);
+        if expired.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.record_command(|| JournalCommand::Expire {
+            as_of,
+            session_closed,
+        })?;
+
+        info!("Expiring {} orders in {}", expired.len(), self.symbol);
+        let events = self.apply_expire(&expired);
+        self.report_risk(None, &events);
+        self.report_metrics(&events, |_| {});
+        Ok(events)
+    }
+
+    /// Run `order` through the risk checks (see `with_risk_check`)
+    fn check_risk(&self, order: &Order) -> Result<(), OrderBookError> {
+        if self.risk_checks.is_empty() {
+            return Ok(());
+        }
+
+        let market = self.market_context();
+        for check in &self.risk_checks {
+            check
+                .check(order, &market)
+                .map_err(OrderBookError::RiskRejected)?;
+        }
+        Ok(())
+    }
+
+    /// Run the amend of resting order `order_id` to `new_quantity` through
+    /// the risk checks
+    fn check_amend_risk(
+        &self,
+        order_id: &OrderId,
+        new_quantity: Quantity,
+    ) -> Result<(), OrderBookError> {
+        if self.risk_checks.is_empty() {
+            return Ok(());
+        }
+
+        let mut order = self
+            .resting_order(order_id)
+            .ok_or(OrderBookError::OrderNotFound)?;
+        let previous = order.remaining_quantity;
+        order.resize(new_quantity);
+
+        let market = self.market_context();
+        for check in &self.risk_checks {
+            check
+                .check_amend(&order, previous, &market)
+                .map_err(OrderBookError::RiskRejected)?;
+        }
+        Ok(())
+    }
+
+    fn market_context(&self) -> MarketContext {
+        MarketContext {
+            best_bid: self.best_bid(),
+            best_ask: self.best_ask(),
+        }
+    }
+
+    fn resting_order(&self, order_id: &OrderId) -> Option<Order> {
+        let location = self.order_locations.get(order_id)?.value().clone();
+        self.side_ladder(location.side)
+            .get(location.price)?
+            .get_order(order_id)
+    }
+
+    /// Apply an accepted new order with `apply` and report the outcome to
+    /// the risk checks and metrics
+    fn apply_new_order(
+        &self,
+        order: Order,
+        apply: impl FnOnce(Order) -> Result<Vec<MarketEvent>, OrderBookError>,
+    ) -> Result<Vec<MarketEvent>, OrderBookError> {
+        let apply = |order: Order| {
+            if self.risk_checks.is_empty() {
+                return apply(order);
+            }
+
+            let accepted = order.clone();
+            let events = apply(order)?;
+            self.report_risk(Some(&accepted), &events);
+            Ok(events)
         };
 
-        // Get sorted prices for matching
-        let mut prices: Vec<Price> = opposite_side.iter().map(|entry| *entry.key()).collect();
+        let Some(metrics) = &self.metrics else {
+            return apply(order);
+        };
+        let events = metrics.time_add_order(|| apply(order))?;
+        self.report_metrics(&events, |metrics| metrics.increment_orders_added());
+        Ok(events)
+    }
 
-        // Sort prices for optimal matching
-        match order.side {
-            Side::Buy => prices.sort(), // Buy orders match against lowest ask prices first
-            Side::Sell => prices.sort_by(|a, b| b.cmp(a)), // Sell orders match against highest bid prices first
+    fn report_risk(&self, order: Option<&Order>, events: &[MarketEvent]) {
+        for check in &self.risk_checks {
+            check.on_events(order, events);
         }
+    }
 
-        for price in prices {
-            if order.remaining_quantity == 0 {
-                break;
+    /// Record an accepted command with `record`, count its trades and
+    /// refresh the book gauges (see `with_metrics`)
+    fn report_metrics(&self, events: &[MarketEvent], record: impl FnOnce(&OrderBookMetrics)) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+
+        record(metrics);
+        for event in events {
+            if let MarketEvent::Trade { trade } = event {
+                metrics.increment_trades_executed(
+                    trade.quantity,
+                    trade.price.saturating_mul(trade.quantity),
+                );
             }
+        }
+        metrics.record_book_stats(&self.get_stats());
+    }
 
-            // Check if we can match at this price
-            let can_match = match order.side {
-                Side::Buy => order.price >= price,  // Buy order price >= ask price
-                Side::Sell => order.price <= price, // Sell order price <= bid price
+    /// Run the matching step `execute`, timed when metrics are attached
+    fn time_match<R>(&self, execute: impl FnOnce() -> R) -> R {
+        match &self.metrics {
+            Some(metrics) => metrics.time_match_order(execute),
+            None => execute(),
+        }
+    }
+
+    /// Get the resting and stop orders whose time in force has run out
+    fn expired_orders(&self, as_of: DateTime<Utc>, session_closed: bool) -> Vec<OrderId> {
+        self.open_orders()
+            .into_iter()
+            .filter(|order| order.is_expired(as_of, session_closed))
+            .map(|order| order.id)
+            .collect()
+    }
+
+    /// Get every resting and untriggered stop order
+    fn open_orders(&self) -> Vec<Order> {
+        self.bids
+            .levels()
+            .into_iter()
+            .chain(self.asks.levels())
+            .flat_map(|(_, level)| level.get_all_orders())
+            .chain(self.stop_book.orders())
+            .collect()
+    }
+
+    /// Write an accepted command ahead to the journal, if one is attached,
+    /// and take its sequence number
+    ///
+    /// The command is written before it is applied, so a failed write
+    /// rejects it, and a command the journal refuses does not use up a
+    /// sequence number. Must be called under the matching lock.
+    fn record_command(
+        &self,
+        command: impl FnOnce() -> JournalCommand,
+    ) -> Result<u64, OrderBookError> {
+        let sequence = self.sequence_number();
+
+        if let Some(journal) = &self.journal {
+            let record = JournalRecord {
+                sequence,
+                command: command(),
             };
+            journal.lock().append(&record).map_err(journal_error)?;
+        }
 
-            if !can_match {
-                break; // No more matches possible
+        self.sequence_number.store(sequence + 1, Ordering::Relaxed);
+        Ok(sequence)
+    }
+
+    /// Apply a journaled command during recovery
+    fn apply_command(&self, command: JournalCommand) -> Result<Vec<MarketEvent>, OrderBookError> {
+        match command {
+            JournalCommand::AddLimit { order } => self.apply_limit_order(order),
+            JournalCommand::AddMarket { order } => self.apply_market_order(order),
+            JournalCommand::AddStop { order } => self.apply_stop_order(order),
+            JournalCommand::Cancel { order_id } => self.apply_cancel(&order_id).map(|e| vec![e]),
+            JournalCommand::ModifyQuantity {
+                order_id,
+                new_quantity,
+            } => self
+                .apply_modify_quantity(&order_id, new_quantity)
+                .map(|e| vec![e]),
+            JournalCommand::Expire {
+                as_of,
+                session_closed,
+            } => Ok(self.apply_expire(&self.expired_orders(as_of, session_closed))),
+            JournalCommand::StartCall { phase } => {
+                self.apply_start_call(phase);
+                Ok(Vec::new())
+            }
+            JournalCommand::Uncross | JournalCommand::Resume => Ok(self.apply_uncross()),
+            JournalCommand::Halt => Ok(vec![
+                self.apply_halt(TradingPhase::Halted, HaltReason::Admin)
+            ]),
+            JournalCommand::SetReferencePrice { price } => {
+                self.reference_price.store(price, Ordering::Relaxed);
+                Ok(Vec::new())
+            }
+            JournalCommand::PriceBandBreach {
+                phase,
+                price,
+                low,
+                high,
+            } => {
+                // Replaying the order that tripped the breaker normally
+                // tripped it again already
+                if self.trading_phase() == phase {
+                    return Ok(Vec::new());
+                }
+                warn!(
+                    "{} replayed a price band breach its circuit breaker did not trip",
+                    self.symbol
+                );
+                let reason = HaltReason::PriceBand { price, low, high };
+                Ok(vec![self.apply_halt(phase, reason)])
+            }
+        }
+    }
+
+    fn apply_limit_order(&self, mut order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+        if self.trading_phase().is_call() {
+            // Orders accumulate without matching until the uncross
+            let mut events = Vec::new();
+            self.rest_order(order, &mut events)?;
+            self.publish_indicative();
+            return Ok(events);
+        }
+
+        if let Some(post_only) = order.post_only {
+            self.apply_post_only(&mut order, post_only)?;
+        }
+        let mut events = self.time_match(|| self.execute_limit_order(order))?;
+        self.release_triggered_stops(&mut events);
+        // The rest of an order that tripped a volatility auction joins the call
+        self.publish_indicative();
+
+        Ok(events)
+    }
+
+    /// Keep a post-only order from taking liquidity: reject it if it would
+    /// cross, or reprice it one tick behind the opposite best if it slides
+    fn apply_post_only(
+        &self,
+        order: &mut Order,
+        post_only: PostOnly,
+    ) -> Result<(), OrderBookError> {
+        let opposite = self.side_ladder(order.side.opposite());
+        let best = match opposite.best_price() {
+            Some(best) if opposite.is_within(best, order.price) => best,
+            _ => return Ok(()),
+        };
+
+        match post_only {
+            PostOnly::Reject => Err(OrderBookError::PostOnlyWouldCross),
+            PostOnly::Slide => {
+                let tick_table = &self.instrument.tick_table;
+                let price = match order.side {
+                    Side::Buy => tick_table.tick_below(best),
+                    Side::Sell => tick_table.tick_above(best),
+                };
+                order.price = price.ok_or(OrderBookError::PostOnlyCannotSlide)?;
+                Ok(())
             }
+        }
+    }
+
+    fn apply_market_order(&self, mut order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+        // Market orders must execute immediately, which a call never allows
+        if self.trading_phase().is_call() {
+            return Err(OrderBookError::InvalidOrderState);
+        }
+        let mut events = Vec::new();
+        self.time_match(|| self.execute_market_order(&mut order, &mut events))?;
 
-            if let Some(level) = opposite_side.get(&price) {
-                let available_quantity = level.total_quantity();
-                if available_quantity == 0 {
-                    continue;
+        if events.is_empty() {
+            return Err(OrderBookError::NoLiquidity);
+        }
+
+        self.complete_market_order(order, 0, &mut events)?;
+        self.release_triggered_stops(&mut events);
+        self.publish_indicative();
+
+        Ok(events)
+    }
+
+    /// Report the final state of a market order that has finished matching,
+    /// resting a market-to-limit remainder (see `add_market_order`)
+    ///
+    /// `first_event` is where the order's own events start in `events`.
+    fn complete_market_order(
+        &self,
+        mut order: Order,
+        first_event: usize,
+        events: &mut Vec<MarketEvent>,
+    ) -> Result<(), OrderBookError> {
+        if order.status == OrderStatus::Filled {
+            events.push(MarketEvent::OrderFilled { order_id: order.id });
+            return Ok(());
+        }
+        // Already cancelled by self-trade prevention
+        if order.is_complete() {
+            return Ok(());
+        }
+
+        let last_price = events[first_event..]
+            .iter()
+            .rev()
+            .find_map(|event| match event {
+                MarketEvent::Trade { trade }
+                    if trade.buyer_order_id == order.id || trade.seller_order_id == order.id =>
+                {
+                    Some(trade.price)
                 }
+                _ => None,
+            });
+
+        match last_price {
+            Some(price) if order.order_type == OrderType::MarketToLimit => {
+                order.order_type = OrderType::Limit;
+                order.price = price;
+                self.rest_order(order, events)
+            }
+            _ => {
+                Self::cancel_incoming(&mut order, events);
+                Ok(())
+            }
+        }
+    }
 
-                let match_quantity = order.remaining_quantity.min(available_quantity);
-                let fills = level.take_quantity(match_quantity);
+    fn apply_stop_order(&self, order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+        let stop_price = order.stop_price().ok_or(OrderBookError::InvalidOrderType)?;
+        let mut events = Vec::new();
 
-                for (mut matched_order, fill_quantity) in fills {
-                    // Create trade
-                    let (buyer_id, seller_id) = match order.side {
-                        Side::Buy => (order.id, matched_order.id),
-                        Side::Sell => (matched_order.id, order.id),
-                    };
+        match self.last_trade_price() {
+            Some(last_price)
+                if !self.trading_phase().is_call()
+                    && StopBook::is_triggered(order.side, stop_price, last_price) =>
+            {
+                self.release_stop(order, last_price, &mut events);
+                self.release_triggered_stops(&mut events);
+            }
+            _ => {
+                self.stop_book.add_order(order.clone());
+                events.push(MarketEvent::OrderAdded { order });
+            }
+        }
 
-                    let trade = Trade::new(
-                        self.symbol.clone(),
-                        buyer_id,
-                        seller_id,
-                        price,
-                        fill_quantity,
-                    );
+        Ok(events)
+    }
+
+    fn apply_cancel(&self, order_id: &OrderId) -> Result<MarketEvent, OrderBookError> {
+        if !self.order_locations.contains_key(order_id) {
+            return self.cancel_stop_order(order_id);
+        }
+
+        let mut order = self
+            .remove_resting_order(order_id)
+            .ok_or(OrderBookError::OrderNotFound)?;
+        let remaining_quantity = order.remaining_quantity;
+        order.cancel();
+        self.publish_indicative();
+
+        Ok(MarketEvent::OrderCancelled {
+            order_id: *order_id,
+            remaining_quantity,
+        })
+    }
+
+    fn apply_expire(&self, expired: &[OrderId]) -> Vec<MarketEvent> {
+        let events = expired
+            .iter()
+            .filter_map(|order_id| {
+                let mut order = self
+                    .remove_resting_order(order_id)
+                    .or_else(|| self.stop_book.cancel_order(order_id))?;
+                let remaining_quantity = order.remaining_quantity;
+                order.expire();
+
+                Some(MarketEvent::OrderExpired {
+                    order_id: *order_id,
+                    remaining_quantity,
+                })
+            })
+            .collect();
+        self.publish_indicative();
+
+        events
+    }
+
+    fn apply_start_call(&self, phase: TradingPhase) {
+        info!("{} entering {:?} call", self.symbol, phase);
+        *self.phase.lock() = phase;
+        self.publish_auction_update();
+    }
+
+    /// Stop continuous matching, moving the book into `phase`
+    fn apply_halt(&self, phase: TradingPhase, reason: HaltReason) -> MarketEvent {
+        warn!("{} entering {:?}: {:?}", self.symbol, phase, reason);
+        *self.phase.lock() = phase;
+        self.publish_auction_update();
+
+        MarketEvent::TradingHalted { phase, reason }
+    }
+
+    fn apply_uncross(&self) -> Vec<MarketEvent> {
+        let mut events = Vec::new();
+
+        if let Some(equilibrium) = self.equilibrium() {
+            info!(
+                "Uncrossing {}: {} at {}",
+                self.symbol, equilibrium.matched_quantity, equilibrium.price
+            );
+            self.execute_uncross(&equilibrium, &mut events);
+            self.reference_price
+                .store(equilibrium.price, Ordering::Relaxed);
+        }
+
+        let previous = std::mem::replace(&mut *self.phase.lock(), TradingPhase::Continuous);
+        self.publish_auction_update();
+        if matches!(
+            previous,
+            TradingPhase::Halted | TradingPhase::VolatilityAuction
+        ) {
+            info!("{} resumed continuous trading", self.symbol);
+            events.push(MarketEvent::TradingResumed);
+        }
+
+        // Stops held back during the call can fire now
+        self.release_triggered_stops(&mut events);
+
+        events
+    }
+
+    /// Take a resting order out of its level and publish its removal
+    fn remove_resting_order(&self, order_id: &OrderId) -> Option<Order> {
+        let (_, location) = self.order_locations.remove(order_id)?;
+        let price_levels = self.side_ladder(location.side);
+        let order = price_levels.get(location.price)?.remove_order(order_id)?;
+
+        // Clean up empty price level
+        price_levels.remove_if_empty(location.price);
+        self.publish_depth(location.side, location.price);
+        self.order_feed.publish(|seq| OrderMessage::Delete {
+            seq,
+            order_id: *order_id,
+            side: location.side,
+            price: location.price,
+        });
+
+        Some(order)
+    }
+
+    fn apply_modify_quantity(
+        &self,
+        order_id: &OrderId,
+        new_quantity: Quantity,
+    ) -> Result<MarketEvent, OrderBookError> {
+        let location = self
+            .order_locations
+            .get(order_id)
+            .map(|entry| entry.value().clone())
+            .ok_or(OrderBookError::OrderNotFound)?;
+
+        if let Some(level) = self.side_ladder(location.side).get(location.price) {
+            if let Some((_, old_visible, new_visible)) = level.resize_order(order_id, new_quantity)
+            {
+                // Market data only sees the visible part of an iceberg
+                self.publish_depth(location.side, location.price);
+                self.order_feed.publish(|seq| {
+                    let (order_id, side, price) = (*order_id, location.side, location.price);
+                    if new_visible < old_visible {
+                        OrderMessage::Reduce {
+                            seq,
+                            order_id,
+                            side,
+                            price,
+                            reduced_by: old_visible - new_visible,
+                            remaining_qty: new_visible,
+                        }
+                    } else {
+                        OrderMessage::Replace {
+                            seq,
+                            order_id,
+                            side,
+                            price,
+                            quantity: new_visible,
+                        }
+                    }
+                });
+                self.publish_indicative();
+                return Ok(MarketEvent::OrderModified {
+                    order_id: *order_id,
+                    new_price: None,
+                    new_quantity: Some(new_quantity),
+                });
+            }
+        }
+
+        Err(OrderBookError::OrderNotFound)
+    }
+
+    fn execute_limit_order(&self, mut order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+        let mut events = Vec::new();
+
+        // Try to match against opposite side first
+        self.match_order(&mut order, &mut events)?;
+
+        // If order has remaining quantity, add to book
+        if order.is_active() {
+            self.rest_order(order, &mut events)?;
+        }
+
+        Ok(events)
+    }
+
+    fn rest_order(
+        &self,
+        mut order: Order,
+        events: &mut Vec<MarketEvent>,
+    ) -> Result<(), OrderBookError> {
+        order.reset_display();
+        self.add_order_to_book(order.clone())?;
+        events.push(MarketEvent::OrderAdded { order });
+        Ok(())
+    }
+
+    /// Execute `equilibrium.matched_quantity` between the best bids and asks,
+    /// in time priority, at the equilibrium price
+    fn execute_uncross(&self, equilibrium: &Equilibrium, events: &mut Vec<MarketEvent>) {
+        let first_event = events.len();
+        let price = equilibrium.price;
+        let mut left = equilibrium.matched_quantity;
+
+        while left > 0 {
+            let (Some((bid_price, bids)), Some((ask_price, asks))) =
+                (self.bids.best_level(), self.asks.best_level())
+            else {
+                break;
+            };
+            if bid_price < price || ask_price > price {
+                break;
+            }
+            let (Some(bid), Some(ask)) = (bids.peek_front(), asks.peek_front()) else {
+                break;
+            };
+
+            let quantity = left.min(bid.visible_quantity()).min(ask.visible_quantity());
+            let trade = Trade::new_stamped(
+                self.ids.next_id(),
+                self.clock.now(),
+                self.symbol.clone(),
+                bid.id,
+                ask.id,
+                price,
+                quantity,
+            );
+
+            for (side, level_price, level) in
+                [(Side::Buy, bid_price, bids), (Side::Sell, ask_price, asks)]
+            {
+                for (matched_order, fill_quantity) in level.take_quantity(quantity) {
+                    self.publish_resting_fill(&matched_order, level_price, fill_quantity, &trade);
+                }
+                self.side_ladder(side).remove_if_empty(level_price);
+                self.publish_depth(side, level_price);
+            }
+
+            left -= quantity;
+            events.push(MarketEvent::Trade { trade });
+        }
+
+        self.record_trades(&events[first_event..]);
+    }
+
+    /// Get the equilibrium of the book as it stands, counting the full open
+    /// quantity of iceberg orders
+    fn equilibrium(&self) -> Option<Equilibrium> {
+        let open_quantity = |ladder: &PriceLadder| -> Vec<(Price, Quantity)> {
+            ladder
+                .levels()
+                .into_iter()
+                .map(|(price, level)| {
+                    let quantity = level
+                        .get_all_orders()
+                        .iter()
+                        .map(|order| order.remaining_quantity)
+                        .sum();
+                    (price, quantity)
+                })
+                .collect()
+        };
+
+        auction::equilibrium(
+            &open_quantity(&self.bids),
+            &open_quantity(&self.asks),
+            self.last_trade_price(),
+        )
+    }
+
+    /// Publish the trading phase and, during a call, the indicative uncross
+    fn publish_auction_update(&self) {
+        let phase = self.trading_phase();
+        let equilibrium = if phase.is_call() {
+            self.equilibrium()
+        } else {
+            None
+        };
+        self.auction_feed.publish(|seq| AuctionUpdate {
+            phase,
+            equilibrium,
+            seq,
+        });
+    }
 
-                    // Update order quantities
-                    order.fill(fill_quantity);
+    /// Publish the indicative uncross after a change to the book during a call
+    fn publish_indicative(&self) {
+        if self.trading_phase().is_call() {
+            self.publish_auction_update();
+        }
+    }
 
-                    // Remove completely filled orders from tracking
-                    if matched_order.is_complete() {
-                        self.order_locations.remove(&matched_order.id);
-                    }
+    fn cancel_stop_order(&self, order_id: &OrderId) -> Result<MarketEvent, OrderBookError> {
+        let mut order = self
+            .stop_book
+            .cancel_order(order_id)
+            .ok_or(OrderBookError::OrderNotFound)?;
+        let remaining_quantity = order.remaining_quantity;
+        order.cancel();
 
-                    trades.push(trade);
-                }
+        Ok(MarketEvent::OrderCancelled {
+            order_id: *order_id,
+            remaining_quantity,
+        })
+    }
 
-                // Clean up empty price level
-                if level.is_empty() {
-                    opposite_side.remove(&price);
-                }
+    /// Release every stop triggered by the last trade price
+    ///
+    /// Released orders can trade and move the last trade price, so this keeps
+    /// checking until no further stops fire.
+    fn release_triggered_stops(&self, events: &mut Vec<MarketEvent>) {
+        while let Some(last_price) = self.last_trade_price() {
+            // Stops wait while a circuit breaker has stopped matching
+            if self.stop_book.is_empty() || self.trading_phase() != TradingPhase::Continuous {
+                break;
             }
-        }
 
-        // Update statistics
-        if !trades.is_empty() {
-            let total_volume: u64 = trades.iter().map(|t| t.quantity).sum();
-            self.total_trades
-                .fetch_add(trades.len() as u64, Ordering::Relaxed);
-            self.total_volume.fetch_add(total_volume, Ordering::Relaxed);
+            let triggered = self.stop_book.take_triggered(last_price);
+            if triggered.is_empty() {
+                break;
+            }
 
-            // Update last trade price
-            if let Some(last_trade) = trades.last() {
-                self.last_trade_price
-                    .store(last_trade.price, Ordering::Relaxed);
+            for order in triggered {
+                self.release_stop(order, last_price, events);
             }
         }
-
-        Ok(trades)
     }
 
-    fn execute_market_order(&self, order: &mut Order) -> Result<Vec<Trade>, OrderBookError> {
-        let mut trades = Vec::new();
-        let opposite_side = match order.side {
-            Side::Buy => &self.asks,
-            Side::Sell => &self.bids,
+    fn release_stop(&self, mut order: Order, trigger_price: Price, events: &mut Vec<MarketEvent>) {
+        let order_id = order.id;
+        let stop_price = order.stop_price().unwrap_or(trigger_price);
+
+        info!(
+            "Stop order {} triggered at {} (stop price {})",
+            order_id, trigger_price, stop_price
+        );
+        events.push(MarketEvent::StopTriggered {
+            order_id,
+            stop_price,
+            trigger_price,
+        });
+
+        order.trigger();
+        let first_event = events.len();
+        let released = match order.order_type {
+            OrderType::Market => self
+                .execute_market_order(&mut order, events)
+                .and_then(|()| self.complete_market_order(order, first_event, events)),
+            _ => self
+                .execute_limit_order(order)
+                .map(|mut released_events| events.append(&mut released_events)),
         };
 
-        // Get sorted prices for market order execution
-        let mut prices: Vec<Price> = opposite_side.iter().map(|entry| *entry.key()).collect();
-
-        // Market orders take the best available prices
-        match order.side {
-            Side::Buy => prices.sort(), // Buy at lowest ask prices first
-            Side::Sell => prices.sort_by(|a, b| b.cmp(a)), // Sell at highest bid prices first
+        if let Err(e) = released {
+            warn!("Triggered stop order {} failed: {}", order_id, e);
         }
+    }
 
-        for price in prices {
-            if order.remaining_quantity == 0 {
-                break;
-            }
+    fn match_order(
+        &self,
+        order: &mut Order,
+        events: &mut Vec<MarketEvent>,
+    ) -> Result<(), OrderBookError> {
+        let limit_price = order.price;
+        self.match_against_book(order, Some(limit_price), events)
+    }
+
+    fn execute_market_order(
+        &self,
+        order: &mut Order,
+        events: &mut Vec<MarketEvent>,
+    ) -> Result<(), OrderBookError> {
+        // Market orders take the best available prices, as far as market
+        // protection allows
+        let limit_price = self
+            .side_ladder(order.side.opposite())
+            .best_price()
+            .and_then(|best| self.instrument.market_limit(order.side, best));
+        self.match_against_book(order, limit_price, events)
+    }
+
+    /// Walk the opposite ladder from its best price, filling `order` until it
+    /// is complete or the next level is beyond `limit_price`
+    ///
+    /// Trades and any self-trade prevention cancels are appended to `events`
+    /// in the order they happen.
+    fn match_against_book(
+        &self,
+        order: &mut Order,
+        limit_price: Option<Price>,
+        events: &mut Vec<MarketEvent>,
+    ) -> Result<(), OrderBookError> {
+        let first_event = events.len();
+        let opposite_side = self.side_ladder(order.side.opposite());
+        let mut cursor = None;
+
+        // Price bands are fixed for the whole order (see `with_circuit_breaker`)
+        let bands = self.circuit_breaker.map(|breaker| {
+            let (low, high) = breaker.limits(self.reference_price(), self.last_trade_price());
+            (breaker.action.phase(), low, high)
+        });
+
+        // Resting orders only need checking one by one when STP can apply
+        let stp_mode = order
+            .stp_mode
+            .or(self.stp_mode)
+            .filter(|_| order.stp_key().is_some());
+
+        while order.is_active() {
+            let (price, level) = match opposite_side.next_level(cursor) {
+                Some(entry) => entry,
+                None => break,
+            };
+            cursor = Some(price);
 
-            if let Some(level) = opposite_side.get(&price) {
-                let available_quantity = level.total_quantity();
-                if available_quantity == 0 {
-                    continue;
+            // Check if we can match at this price
+            if let Some(limit) = limit_price {
+                if !opposite_side.is_within(price, limit) {
+                    break; // No more matches possible
+                }
+            }
+            if let Some((phase, low, high)) = bands {
+                if !(low..=high).contains(&price) {
+                    let reason = HaltReason::PriceBand { price, low, high };
+                    events.push(self.apply_halt(phase, reason));
+                    // The command that got here is already journaled, so a
+                    // failed write can only be logged
+                    let breach = || JournalCommand::PriceBandBreach {
+                        phase,
+                        price,
+                        low,
+                        high,
+                    };
+                    if let Err(e) = self.record_command(breach) {
+                        error!("{} failed to journal price band breach: {}", self.symbol, e);
+                    }
+                    break;
                 }
+            }
 
-                let match_quantity = order.remaining_quantity.min(available_quantity);
-                let fills = level.take_quantity(match_quantity);
+            while order.is_active() {
+                let match_quantity = match stp_mode {
+                    Some(mode) => {
+                        let resting = match level.peek_front() {
+                            Some(resting) => resting,
+                            None => break,
+                        };
+                        if MatchingEngine::is_self_trade(order, &resting) {
+                            self.prevent_self_trade(mode, order, &resting, events);
+                            continue;
+                        }
+                        order.remaining_quantity.min(resting.visible_quantity())
+                    }
+                    None => order.remaining_quantity.min(level.total_quantity()),
+                };
+                if match_quantity == 0 {
+                    break;
+                }
 
-                for (mut matched_order, fill_quantity) in fills {
+                for (matched_order, fill_quantity) in level.take_quantity(match_quantity) {
                     // Create trade
                     let (buyer_id, seller_id) = match order.side {
                         Side::Buy => (order.id, matched_order.id),
                         Side::Sell => (matched_order.id, order.id),
                     };
 
-                    let trade = Trade::new(
+                    let trade = Trade::new_stamped(
+                        self.ids.next_id(),
+                        self.clock.now(),
                         self.symbol.clone(),
                         buyer_id,
                         seller_id,
@@ -408,24 +1669,75 @@ impl OrderBook {
                     );
 
                     // Update order quantities
-                    order.fill(fill_quantity);
-
-                    // Remove completely filled orders from tracking
-                    if matched_order.is_complete() {
-                        self.order_locations.remove(&matched_order.id);
-                    }
+                    order
+                        .fill(fill_quantity)
+                        .map_err(|_| OrderBookError::OverFill)?;
 
-                    trades.push(trade);
-                }
-
-                // Clean up empty price level
-                if level.is_empty() {
-                    opposite_side.remove(&price);
+                    self.publish_resting_fill(&matched_order, price, fill_quantity, &trade);
+                    events.push(MarketEvent::Trade { trade });
                 }
             }
+
+            // Clean up empty price level
+            opposite_side.remove_if_empty(price);
+            self.publish_depth(order.side.opposite(), price);
+        }
+
+        self.record_trades(&events[first_event..]);
+
+        Ok(())
+    }
+
+    /// Publish a fill of the resting order `matched_order` at level `price`
+    /// and stop tracking it once it is complete
+    ///
+    /// `matched_order` is the order as it was right after the fill.
+    fn publish_resting_fill(
+        &self,
+        matched_order: &Order,
+        price: Price,
+        fill_quantity: Quantity,
+        trade: &Trade,
+    ) {
+        self.order_feed.publish(|seq| OrderMessage::Execute {
+            seq,
+            order_id: matched_order.id,
+            side: matched_order.side,
+            price,
+            executed_qty: fill_quantity,
+            remaining_qty: matched_order.visible_quantity(),
+            trade_id: trade.id,
+        });
+
+        // A refreshed iceberg slice rejoins the queue as a new add
+        if matched_order.visible_quantity() == 0 && matched_order.is_active() {
+            let mut refreshed = matched_order.clone();
+            refreshed.reset_display();
+            self.order_feed.publish(|seq| OrderMessage::Add {
+                seq,
+                order_id: refreshed.id,
+                side: refreshed.side,
+                price,
+                quantity: refreshed.visible_quantity(),
+            });
+        }
+
+        // Remove completely filled orders from tracking
+        if matched_order.is_complete() {
+            self.order_locations.remove(&matched_order.id);
         }
+    }
 
-        // Update statistics
+    /// Update trade statistics and the last trade price from the trades in
+    /// `events`
+    fn record_trades(&self, events: &[MarketEvent]) {
+        let trades: Vec<&Trade> = events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::Trade { trade } => Some(trade),
+                _ => None,
+            })
+            .collect();
         if !trades.is_empty() {
             let total_volume: u64 = trades.iter().map(|t| t.quantity).sum();
             self.total_trades
@@ -438,26 +1750,70 @@ impl OrderBook {
                     .store(last_trade.price, Ordering::Relaxed);
             }
         }
+    }
+
+    /// Apply `mode` to an incoming order that would trade with `resting`,
+    /// the front order of its level
+    ///
+    /// Whichever orders the mode removes are reported as `OrderCancelled`.
+    /// A decremented resting order that stays open is reported as
+    /// `OrderModified`, while a decremented incoming order only shows its
+    /// reduction in the quantity it rests or is cancelled with.
+    fn prevent_self_trade(
+        &self,
+        mode: SelfTradePrevention,
+        order: &mut Order,
+        resting: &Order,
+        events: &mut Vec<MarketEvent>,
+    ) {
+        debug!(
+            "Self-trade between {} and {} prevented with {:?}",
+            order.id, resting.id, mode
+        );
+
+        let (cancel_newest, cancel_oldest) = match mode {
+            SelfTradePrevention::CancelNewest => (true, false),
+            SelfTradePrevention::CancelOldest => (false, true),
+            SelfTradePrevention::CancelBoth => (true, true),
+            SelfTradePrevention::DecrementAndCancel => {
+                // The incoming order is not in the book yet, so only the
+                // resting order is reported as modified
+                let decrement = order.remaining_quantity.min(resting.remaining_quantity);
+                order.resize(order.remaining_quantity - decrement);
+                let resting_left = resting.remaining_quantity - decrement;
+                if resting_left > 0 {
+                    events.extend(self.apply_modify_quantity(&resting.id, resting_left).ok());
+                }
+                (order.remaining_quantity == 0, resting_left == 0)
+            }
+        };
+
+        if cancel_oldest {
+            events.extend(self.apply_cancel(&resting.id).ok());
+        }
+        if cancel_newest {
+            Self::cancel_incoming(order, events);
+        }
+    }
 
-        Ok(trades)
+    /// Cancel an incoming order that will not rest, reporting all of its
+    /// unfilled quantity, including any that self-trade prevention took off
+    fn cancel_incoming(order: &mut Order, events: &mut Vec<MarketEvent>) {
+        order.cancel();
+        events.push(MarketEvent::OrderCancelled {
+            order_id: order.id,
+            remaining_quantity: order.original_quantity - order.filled_quantity,
+        });
     }
 
     fn add_order_to_book(&self, order: Order) -> Result<(), OrderBookError> {
         let price = order.price;
         let side = order.side;
         let order_id = order.id;
+        let quantity = order.visible_quantity();
 
-        // Choose the correct side of the book
-        let price_levels = match side {
-            Side::Buy => &self.bids,
-            Side::Sell => &self.asks,
-        };
-
-        // Get or create price level
-        let level = price_levels
-            .entry(price)
-            .or_insert_with(|| Arc::new(PriceLevel::new(price)))
-            .clone();
+        // Get or create price level on the correct side of the book
+        let level = self.side_ladder(side).get_or_insert(price);
 
         // Add order to price level
         level.add_order(order);
@@ -465,12 +1821,63 @@ impl OrderBook {
         // Track order location
         self.order_locations
             .insert(order_id, OrderLocation { price, side });
+        self.publish_depth(side, price);
+        self.order_feed.publish(|seq| OrderMessage::Add {
+            seq,
+            order_id,
+            side,
+            price,
+            quantity,
+        });
 
         Ok(())
     }
 
-    fn next_sequence(&self) -> u64 {
-        self.sequence_number.fetch_add(1, Ordering::Relaxed)
+    /// Publish the current aggregated state of the level at `price`
+    fn publish_depth(&self, side: Side, price: Price) {
+        let (quantity, order_count) = self
+            .side_ladder(side)
+            .get(price)
+            .map(|level| level.get_depth_info())
+            .unwrap_or((0, 0));
+        self.depth_feed.publish(|seq| DepthUpdate {
+            side,
+            price,
+            new_qty: quantity,
+            order_count,
+            seq,
+        });
+    }
+
+    /// Get every level of `ladder` with its orders in queue order
+    fn level_snapshots(ladder: &PriceLadder) -> Vec<LevelSnapshot> {
+        ladder
+            .levels()
+            .into_iter()
+            .map(|(price, level)| LevelSnapshot {
+                price,
+                orders: level.get_all_orders(),
+            })
+            .collect()
+    }
+
+    /// Get every level of `ladder` as market data shows it, without the
+    /// reserve quantity of iceberg orders
+    fn displayed_levels(ladder: &PriceLadder) -> Vec<LevelSnapshot> {
+        let mut levels = Self::level_snapshots(ladder);
+        for level in &mut levels {
+            for order in &mut level.orders {
+                *order = order.displayed();
+            }
+        }
+        levels
+    }
+
+    fn side_ladder(&self, side: Side) -> &PriceLadder {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
     }
 }
 
@@ -488,6 +1895,10 @@ pub struct OrderBookStats {
     pub total_volume: u64,
 }
 
+fn journal_error(e: std::io::Error) -> OrderBookError {
+    OrderBookError::SystemError(format!("journal: {}", e))
+}
+
 impl Default for OrderBook {
     fn default() -> Self {
         Self::new("DEFAULT".to_string())
@@ -574,10 +1985,12 @@ mod tests {
 
         // Add market buy order
         let market_order = create_market_order(Side::Buy, 75);
+        let market_order_id = market_order.id;
         let events = book.add_market_order(market_order).unwrap();
 
-        // Should have two trade events (fills both levels partially)
-        assert_eq!(events.len(), 2);
+        // Should have two trade events (fills both levels partially), then
+        // the fill report
+        assert_eq!(events.len(), 3);
 
         // First trade at 10000 for 50 shares
         if let MarketEvent::Trade { trade } = &events[0] {
@@ -590,6 +2003,11 @@ mod tests {
             assert_eq!(trade.price, 10100);
             assert_eq!(trade.quantity, 25);
         }
+
+        assert!(matches!(
+            events[2],
+            MarketEvent::OrderFilled { order_id } if order_id == market_order_id
+        ));
     }
 
     #[test]
@@ -637,6 +2055,133 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_stop_order_triggers_on_trade() {
+        let book = OrderBook::new("TEST".to_string());
+
+        book.add_limit_order(create_limit_order(Side::Sell, 10000, 50))
+            .unwrap();
+        book.add_limit_order(create_limit_order(Side::Sell, 10100, 100))
+            .unwrap();
+
+        let stop = Order::new_stop("TEST".to_string(), Side::Buy, 10000, 30, None);
+        let stop_id = stop.id;
+        let events = book.add_stop_order(stop).unwrap();
+        assert!(matches!(events[0], MarketEvent::OrderAdded { .. }));
+        assert_eq!(book.total_stop_orders(), 1);
+        assert_eq!(book.total_orders(), 2);
+
+        // Trade at 10000 triggers the buy stop, which lifts the next ask
+        let events = book
+            .add_limit_order(create_limit_order(Side::Buy, 10000, 50))
+            .unwrap();
+        assert_eq!(events.len(), 4);
+        match &events[1] {
+            MarketEvent::StopTriggered {
+                order_id,
+                stop_price,
+                trigger_price,
+            } => {
+                assert_eq!(*order_id, stop_id);
+                assert_eq!(*stop_price, 10000);
+                assert_eq!(*trigger_price, 10000);
+            }
+            other => panic!("Expected stop triggered event, got {:?}", other),
+        }
+        if let MarketEvent::Trade { trade } = &events[2] {
+            assert_eq!(trade.buyer_order_id, stop_id);
+            assert_eq!(trade.price, 10100);
+            assert_eq!(trade.quantity, 30);
+        } else {
+            panic!("Expected trade event");
+        }
+        assert!(matches!(
+            events[3],
+            MarketEvent::OrderFilled { order_id } if order_id == stop_id
+        ));
+
+        assert_eq!(book.total_stop_orders(), 0);
+        assert_eq!(book.last_trade_price(), Some(10100));
+    }
+
+    #[test]
+    fn test_stop_limit_rests_after_trigger() {
+        let book = OrderBook::new("TEST".to_string());
+
+        book.add_limit_order(create_limit_order(Side::Buy, 10000, 50))
+            .unwrap();
+
+        let stop_limit =
+            Order::new_stop_limit("TEST".to_string(), Side::Sell, 10000, 10050, 40, None);
+        book.add_stop_order(stop_limit).unwrap();
+
+        book.add_market_order(create_market_order(Side::Sell, 50))
+            .unwrap();
+
+        // Stop-limit is released as a sell limit at 10050 with nothing to match
+        assert_eq!(book.total_stop_orders(), 0);
+        assert_eq!(book.best_ask(), Some(10050));
+        assert_eq!(book.total_orders(), 1);
+    }
+
+    #[test]
+    fn test_stop_cascade_is_deterministic() {
+        let book = OrderBook::new("TEST".to_string());
+
+        book.add_limit_order(create_limit_order(Side::Sell, 10000, 10))
+            .unwrap();
+        book.add_limit_order(create_limit_order(Side::Sell, 10100, 10))
+            .unwrap();
+        book.add_limit_order(create_limit_order(Side::Sell, 10200, 10))
+            .unwrap();
+
+        // The second stop only fires once the first one trades at 10100
+        let first = Order::new_stop("TEST".to_string(), Side::Buy, 10000, 10, None);
+        let second = Order::new_stop("TEST".to_string(), Side::Buy, 10100, 10, None);
+        let (first_id, second_id) = (first.id, second.id);
+        book.add_stop_order(second).unwrap();
+        book.add_stop_order(first).unwrap();
+
+        let events = book
+            .add_market_order(create_market_order(Side::Buy, 10))
+            .unwrap();
+        let triggered: Vec<OrderId> = events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::StopTriggered { order_id, .. } => Some(*order_id),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(triggered, vec![first_id, second_id]);
+        assert_eq!(book.last_trade_price(), Some(10200));
+        assert_eq!(book.best_ask(), None);
+    }
+
+    #[test]
+    fn test_cancel_stop_order() {
+        let book = OrderBook::new("TEST".to_string());
+
+        let stop = Order::new_stop("TEST".to_string(), Side::Sell, 9900, 100, None);
+        let stop_id = stop.id;
+        book.add_stop_order(stop).unwrap();
+
+        let event = book.cancel_order(&stop_id).unwrap();
+        assert!(matches!(
+            event,
+            MarketEvent::OrderCancelled {
+                remaining_quantity: 100,
+                ..
+            }
+        ));
+        assert_eq!(book.total_stop_orders(), 0);
+        assert_eq!(
+            book.add_stop_order(create_limit_order(Side::Buy, 10000, 10))
+                .unwrap_err(),
+            OrderBookError::InvalidOrderType
+        );
+    }
+
     #[test]
     fn test_price_time_priority() {
         let book = OrderBook::new("TEST".to_string());
@@ -644,6 +2189,7 @@ mod tests {
         // Add two buy orders at same price
         let order1 = create_limit_order(Side::Buy, 10000, 100);
         let order2 = create_limit_order(Side::Buy, 10000, 200);
+        let (order1_id, order2_id) = (order1.id, order2.id);
 
         book.add_limit_order(order1).unwrap();
         book.add_limit_order(order2).unwrap();
@@ -653,9 +2199,685 @@ mod tests {
         let events = book.add_limit_order(sell_order).unwrap();
 
         // Should trade with first order completely (100) and second order partially (50)
-        assert_eq!(events.len(), 1);
-        if let MarketEvent::Trade { trade } = &events[0] {
-            assert_eq!(trade.quantity, 150);
+        let fills: Vec<(OrderId, Quantity)> = events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::Trade { trade } => Some((trade.buyer_order_id, trade.quantity)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fills, vec![(order1_id, 100), (order2_id, 50)]);
+    }
+
+    #[test]
+    fn test_instrument_rules_enforced() {
+        use crate::orderbook::instrument::TickSizeTable;
+
+        let instrument = Instrument::new("TEST")
+            .with_tick_table(TickSizeTable::new(&[(0, 1), (10_000, 5)]).unwrap())
+            .with_lot_size(10)
+            .with_quantity_limits(10, 1_000);
+        let book = OrderBook::new("TEST".to_string())
+            .with_instrument(instrument)
+            .unwrap();
+
+        let check = |order| book.add_limit_order(order).map(|_| ()).unwrap_err();
+        assert_eq!(
+            check(create_limit_order(Side::Buy, 10_002, 100)),
+            OrderBookError::InvalidPrice
+        );
+        assert_eq!(
+            check(create_limit_order(Side::Buy, 10_005, 105)),
+            OrderBookError::InvalidQuantity
+        );
+        assert_eq!(
+            check(create_limit_order(Side::Buy, 10_005, 2_000)),
+            OrderBookError::OrderTooLarge
+        );
+        assert_eq!(
+            book.add_market_order(create_market_order(Side::Sell, 5))
+                .unwrap_err(),
+            OrderBookError::InvalidQuantity
+        );
+
+        let order = create_limit_order(Side::Buy, 9_999, 100);
+        let order_id = order.id;
+        book.add_limit_order(order).unwrap();
+        assert_eq!(
+            book.modify_order_quantity(&order_id, 55).unwrap_err(),
+            OrderBookError::InvalidQuantity
+        );
+        assert!(book.modify_order_quantity(&order_id, 50).is_ok());
+
+        assert!(matches!(
+            OrderBook::new("TEST".to_string()).with_instrument(Instrument::new("OTHER")),
+            Err(OrderBookError::SymbolMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_self_trade_prevention_modes() {
+        let client = |order: Order, client_id: &str| Order {
+            client_id: Some(client_id.to_string()),
+            ..order
+        };
+        // Resting: A sells 100, then B sells 100, both at 10000
+        let setup = |mode| {
+            let book = OrderBook::new("TEST".to_string()).with_self_trade_prevention(mode);
+            let own = client(create_limit_order(Side::Sell, 10000, 100), "A");
+            let other = client(create_limit_order(Side::Sell, 10000, 100), "B");
+            let ids = (own.id, other.id);
+            book.add_limit_order(own).unwrap();
+            book.add_limit_order(other).unwrap();
+            (book, ids)
+        };
+        let cancelled = |events: &[MarketEvent]| -> Vec<(OrderId, Quantity)> {
+            events
+                .iter()
+                .filter_map(|event| match event {
+                    MarketEvent::OrderCancelled {
+                        order_id,
+                        remaining_quantity,
+                    } => Some((*order_id, *remaining_quantity)),
+                    _ => None,
+                })
+                .collect()
+        };
+        let traded = |events: &[MarketEvent]| -> Quantity {
+            events
+                .iter()
+                .filter_map(|event| match event {
+                    MarketEvent::Trade { trade } => Some(trade.quantity),
+                    _ => None,
+                })
+                .sum()
+        };
+
+        // Cancel newest: the incoming buy is cancelled before reaching B
+        let (book, (own_id, _)) = setup(SelfTradePrevention::CancelNewest);
+        let buy = client(create_limit_order(Side::Buy, 10000, 150), "A");
+        let buy_id = buy.id;
+        let events = book.add_limit_order(buy).unwrap();
+        assert_eq!(cancelled(&events), vec![(buy_id, 150)]);
+        assert_eq!(traded(&events), 0);
+        assert!(book.contains_order(&own_id));
+        assert_eq!(book.best_bid(), None);
+
+        // Cancel oldest: A's resting sell goes, the buy trades with B and rests
+        let (book, (own_id, _)) = setup(SelfTradePrevention::CancelOldest);
+        let events = book
+            .add_limit_order(client(create_limit_order(Side::Buy, 10000, 150), "A"))
+            .unwrap();
+        assert_eq!(cancelled(&events), vec![(own_id, 100)]);
+        assert_eq!(traded(&events), 100);
+        assert_eq!(book.best_bid(), Some(10000));
+        assert_eq!(book.best_ask(), None);
+
+        // Cancel both
+        let (book, (own_id, other_id)) = setup(SelfTradePrevention::CancelBoth);
+        let buy = client(create_limit_order(Side::Buy, 10000, 150), "A");
+        let buy_id = buy.id;
+        let events = book.add_limit_order(buy).unwrap();
+        assert_eq!(cancelled(&events), vec![(own_id, 100), (buy_id, 150)]);
+        assert_eq!(book.total_orders(), 1);
+        assert!(book.contains_order(&other_id));
+
+        // Decrement and cancel: both lose 100, A's sell is gone and the
+        // remaining 50 trades with B
+        let (book, (own_id, other_id)) = setup(SelfTradePrevention::DecrementAndCancel);
+        let buy = client(create_limit_order(Side::Buy, 10000, 150), "A");
+        let buy_id = buy.id;
+        let events = book.add_limit_order(buy).unwrap();
+        assert!(!events.iter().any(|event| matches!(
+            event,
+            MarketEvent::OrderModified { order_id, .. } if *order_id == buy_id
+        )));
+        assert_eq!(cancelled(&events), vec![(own_id, 100)]);
+        assert_eq!(traded(&events), 50);
+        assert!(book.contains_order(&other_id));
+        assert_eq!(book.best_ask(), Some(10000));
+        assert_eq!(book.total_orders(), 1);
+
+        // A decremented order that outlasts the level rests with what is left
+        let (book, _) = setup(SelfTradePrevention::DecrementAndCancel);
+        let events = book
+            .add_limit_order(client(create_limit_order(Side::Buy, 10000, 250), "A"))
+            .unwrap();
+        assert_eq!(traded(&events), 100);
+        assert!(matches!(
+            events.last(),
+            Some(MarketEvent::OrderAdded { order }) if order.remaining_quantity == 50
+        ));
+
+        // A smaller incoming order is decremented away entirely: its cancel
+        // reports the 60 taken off and A's sell shrinks to 40
+        let (book, (own_id, _)) = setup(SelfTradePrevention::DecrementAndCancel);
+        let buy = client(create_limit_order(Side::Buy, 10000, 60), "A");
+        let buy_id = buy.id;
+        let events = book.add_limit_order(buy).unwrap();
+        assert!(matches!(
+            events[0],
+            MarketEvent::OrderModified { order_id, new_quantity: Some(40), .. } if order_id == own_id
+        ));
+        assert_eq!(cancelled(&events), vec![(buy_id, 60)]);
+        assert_eq!(traded(&events), 0);
+        assert_eq!(book.total_orders(), 2);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_keys() {
+        let book = OrderBook::new("TEST".to_string());
+        let resting = create_limit_order(Side::Sell, 10000, 100).with_stp_group("desk-1");
+        let resting_id = resting.id;
+        book.add_limit_order(resting).unwrap();
+
+        // Without a book or order mode, STP is off
+        let events = book
+            .add_limit_order(create_limit_order(Side::Buy, 10000, 10).with_stp_group("desk-1"))
+            .unwrap();
+        assert!(matches!(events[0], MarketEvent::Trade { .. }));
+
+        // The order's own mode applies to matching STP groups only
+        let events = book
+            .add_market_order(
+                create_market_order(Side::Buy, 10)
+                    .with_stp_group("desk-2")
+                    .with_self_trade_prevention(SelfTradePrevention::CancelOldest),
+            )
+            .unwrap();
+        assert!(matches!(events[0], MarketEvent::Trade { .. }));
+
+        let market_order = create_market_order(Side::Buy, 10)
+            .with_stp_group("desk-1")
+            .with_self_trade_prevention(SelfTradePrevention::CancelOldest);
+        let market_order_id = market_order.id;
+        let events = book.add_market_order(market_order).unwrap();
+        assert!(matches!(
+            events[..],
+            [
+                MarketEvent::OrderCancelled { order_id: first, remaining_quantity: 80 },
+                MarketEvent::OrderCancelled { order_id: second, remaining_quantity: 10 },
+            ] if first == resting_id && second == market_order_id
+        ));
+        assert_eq!(book.total_orders(), 0);
+    }
+
+    #[test]
+    fn test_expiry_sweeps() {
+        use crate::orderbook::types::TimeInForce;
+        use crate::utils::time::ManualClock;
+        use std::time::Duration;
+
+        let clock = Arc::new(ManualClock::from_epoch());
+        let book = OrderBook::new("TEST".to_string()).with_clock(clock.clone());
+        let in_one_minute = DateTime::UNIX_EPOCH + chrono::Duration::minutes(1);
+
+        let gtc = create_limit_order(Side::Buy, 9_900, 100);
+        let day = create_limit_order(Side::Buy, 9_800, 100).with_time_in_force(TimeInForce::Day);
+        let gtd = create_limit_order(Side::Sell, 10_100, 100)
+            .with_time_in_force(TimeInForce::GoodTillDate(in_one_minute));
+        let gtd_stop = Order::new_stop("TEST".to_string(), Side::Sell, 9_500, 100, None)
+            .with_time_in_force(TimeInForce::GoodTillDate(in_one_minute));
+        let (day_id, gtd_id, stop_id) = (day.id, gtd.id, gtd_stop.id);
+        book.add_limit_order(gtc).unwrap();
+        book.add_limit_order(day).unwrap();
+        book.add_limit_order(gtd).unwrap();
+        book.add_stop_order(gtd_stop).unwrap();
+
+        // Nothing is due yet
+        assert!(book.expire_orders().unwrap().is_empty());
+        let sequence = book.sequence_number();
+
+        clock.advance(Duration::from_secs(60));
+        let expired: Vec<OrderId> = book
+            .expire_orders()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::OrderExpired {
+                    order_id,
+                    remaining_quantity: 100,
+                } => Some(*order_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(expired, vec![gtd_id, stop_id]);
+        assert_eq!(book.sequence_number(), sequence + 1);
+        assert_eq!(book.best_ask(), None);
+        assert_eq!(book.total_stop_orders(), 0);
+
+        let events = book.close_session().unwrap();
+        assert!(matches!(
+            events[..],
+            [MarketEvent::OrderExpired { order_id, .. }] if order_id == day_id
+        ));
+        assert_eq!(book.total_orders(), 1);
+
+        // Good-till-date orders that are already due are refused
+        let stale = create_limit_order(Side::Buy, 9_900, 100)
+            .with_time_in_force(TimeInForce::GoodTillDate(in_one_minute));
+        assert_eq!(
+            book.add_limit_order(stale).unwrap_err(),
+            OrderBookError::InvalidOrderState
+        );
+    }
+
+    #[test]
+    fn test_iceberg_orders() {
+        let book = OrderBook::new("TEST".to_string());
+        let orders = book.subscribe_orders();
+
+        let iceberg = create_limit_order(Side::Sell, 10_000, 500).with_display_quantity(100);
+        let iceberg_id = iceberg.id;
+        book.add_limit_order(iceberg).unwrap();
+        let behind = create_limit_order(Side::Sell, 10_000, 100);
+        let behind_id = behind.id;
+        book.add_limit_order(behind).unwrap();
+
+        // Only the display slice is public
+        let depth = book.snapshot();
+        assert_eq!(depth.asks[0].quantity, 200);
+        let snapshot = book.order_snapshot();
+        assert_eq!(snapshot.asks[0].orders[0].remaining_quantity, 100);
+        assert_eq!(
+            book.full_snapshot().asks[0].orders[0].remaining_quantity,
+            500
+        );
+
+        // Filling the slice refreshes it from reserve behind the other order
+        let events = book
+            .add_market_order(Order::new_market("TEST".to_string(), Side::Buy, 150, None))
+            .unwrap();
+        let fills: Vec<(OrderId, Quantity)> = events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::Trade { trade } => Some((trade.seller_order_id, trade.quantity)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fills, vec![(iceberg_id, 100), (behind_id, 50)]);
+
+        let queue: Vec<(OrderId, Quantity)> = book.order_snapshot().asks[0]
+            .orders
+            .iter()
+            .map(|order| (order.id, order.remaining_quantity))
+            .collect();
+        assert_eq!(queue, vec![(behind_id, 50), (iceberg_id, 100)]);
+        assert_eq!(book.snapshot().asks[0].quantity, 150);
+
+        // The refresh reaches the order feed as a new add at the back
+        let messages: Vec<OrderMessage> = orders.try_iter().collect();
+        assert!(matches!(
+            messages[messages.len() - 3..],
+            [
+                OrderMessage::Execute { order_id: first, remaining_qty: 0, .. },
+                OrderMessage::Add { order_id: refreshed, quantity: 100, .. },
+                OrderMessage::Execute { order_id: second, remaining_qty: 50, .. },
+            ] if first == iceberg_id && refreshed == iceberg_id && second == behind_id
+        ));
+
+        // Zero display quantities are refused
+        let invalid = create_limit_order(Side::Buy, 9_900, 100).with_display_quantity(0);
+        assert_eq!(
+            book.add_limit_order(invalid).unwrap_err(),
+            OrderBookError::InvalidQuantity
+        );
+    }
+
+    #[test]
+    fn test_post_only_orders() {
+        let book = OrderBook::new("TEST".to_string())
+            .with_instrument(Instrument::new("TEST").with_tick_size(5))
+            .unwrap();
+        book.add_limit_order(create_limit_order(Side::Sell, 10_000, 100))
+            .unwrap();
+        book.add_limit_order(create_limit_order(Side::Buy, 9_900, 100))
+            .unwrap();
+
+        // A post-only order that would not cross rests as usual
+        let passive = create_limit_order(Side::Buy, 9_950, 100).with_post_only(PostOnly::Reject);
+        book.add_limit_order(passive).unwrap();
+        assert_eq!(book.best_bid(), Some(9_950));
+
+        let crossing = create_limit_order(Side::Buy, 10_000, 100).with_post_only(PostOnly::Reject);
+        assert_eq!(
+            book.add_limit_order(crossing).unwrap_err(),
+            OrderBookError::PostOnlyWouldCross
+        );
+        assert_eq!(book.total_orders(), 3);
+
+        // A sliding order rests one tick behind the opposite best
+        let buy = create_limit_order(Side::Buy, 10_050, 100).with_post_only(PostOnly::Slide);
+        let events = book.add_limit_order(buy).unwrap();
+        assert!(matches!(
+            &events[..],
+            [MarketEvent::OrderAdded { order }] if order.price == 9_995
+        ));
+        let sell = create_limit_order(Side::Sell, 9_000, 100).with_post_only(PostOnly::Slide);
+        book.add_limit_order(sell).unwrap();
+        assert_eq!(book.best_ask(), Some(10_000));
+        assert_eq!(book.get_stats().total_trades, 0);
+
+        // There is nowhere to slide below the lowest tick
+        let book = OrderBook::new("TEST".to_string())
+            .with_instrument(Instrument::new("TEST").with_tick_size(5))
+            .unwrap();
+        book.add_limit_order(create_limit_order(Side::Sell, 5, 100))
+            .unwrap();
+        let buy = create_limit_order(Side::Buy, 5, 100).with_post_only(PostOnly::Slide);
+        assert_eq!(
+            book.add_limit_order(buy).unwrap_err(),
+            OrderBookError::PostOnlyCannotSlide
+        );
+    }
+
+    #[test]
+    fn test_market_order_protection() {
+        use crate::orderbook::instrument::MarketProtection;
+
+        let book = OrderBook::new("TEST".to_string())
+            .with_instrument(
+                Instrument::new("TEST")
+                    .with_tick_size(5)
+                    .with_market_protection(MarketProtection::Ticks(2)),
+            )
+            .unwrap();
+        for price in [10_000, 10_010, 10_015] {
+            book.add_limit_order(create_limit_order(Side::Sell, price, 50))
+                .unwrap();
+        }
+
+        // Protection stops the sweep two ticks through the best ask and the
+        // remainder is cancelled
+        let market_order = create_market_order(Side::Buy, 200);
+        let market_order_id = market_order.id;
+        let events = book.add_market_order(market_order).unwrap();
+        assert!(matches!(
+            events[..],
+            [
+                MarketEvent::Trade { ref trade },
+                MarketEvent::Trade { .. },
+                MarketEvent::OrderCancelled { order_id, remaining_quantity: 100 },
+            ] if trade.price == 10_000 && order_id == market_order_id
+        ));
+        assert_eq!(book.best_ask(), Some(10_015));
+
+        // A market-to-limit remainder rests at the last executed price
+        let order = Order::new_market_to_limit("TEST".to_string(), Side::Buy, 80, None);
+        let events = book.add_market_order(order).unwrap();
+        assert!(matches!(
+            &events[..],
+            [MarketEvent::Trade { .. }, MarketEvent::OrderAdded { order }]
+                if order.order_type == OrderType::Limit
+                    && order.price == 10_015
+                    && order.remaining_quantity == 30
+        ));
+        assert_eq!(book.best_bid(), Some(10_015));
+
+        assert_eq!(
+            book.add_market_order(create_market_order(Side::Buy, 10))
+                .unwrap_err(),
+            OrderBookError::NoLiquidity
+        );
+    }
+
+    #[test]
+    fn test_call_auction() {
+        let book = OrderBook::new("TEST".to_string());
+        let auction = book.subscribe_auction();
+        book.start_call(TradingPhase::PreOpen).unwrap();
+        assert_eq!(
+            book.start_call(TradingPhase::PreClose).unwrap_err(),
+            OrderBookError::InvalidOrderState
+        );
+
+        // Crossing orders rest without matching
+        let bid_a = create_limit_order(Side::Buy, 10_200, 100);
+        let bid_b = create_limit_order(Side::Buy, 10_100, 100);
+        let bid_c = create_limit_order(Side::Buy, 10_000, 100);
+        let ask_a = create_limit_order(Side::Sell, 9_900, 150);
+        let ask_b = create_limit_order(Side::Sell, 10_100, 100);
+        let ids = [bid_a.id, bid_b.id, ask_a.id, ask_b.id];
+        for order in [bid_a, bid_b, bid_c, ask_a, ask_b] {
+            book.add_limit_order(order).unwrap();
+        }
+        assert_eq!(
+            (book.best_bid(), book.best_ask()),
+            (Some(10_200), Some(9_900))
+        );
+        assert_eq!(
+            book.add_market_order(Order::new_market("TEST".to_string(), Side::Buy, 10, None))
+                .unwrap_err(),
+            OrderBookError::InvalidOrderState
+        );
+
+        // The indicative uncross follows every change during the call
+        let updates: Vec<AuctionUpdate> = auction.try_iter().collect();
+        assert_eq!(updates.len(), 6);
+        let indicative = updates.last().unwrap().equilibrium.unwrap();
+        assert_eq!(indicative.price, 10_100);
+        assert_eq!(indicative.matched_quantity, 200);
+        assert_eq!(indicative.imbalance_quantity, 50);
+        assert_eq!(indicative.imbalance_side, Some(Side::Sell));
+        assert_eq!(book.indicative_uncross(), Some(indicative));
+
+        // Everything that crosses trades at the single equilibrium price
+        let events = book.uncross().unwrap();
+        let trades: Vec<(OrderId, OrderId, Price, Quantity)> = events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::Trade { trade } => Some((
+                    trade.buyer_order_id,
+                    trade.seller_order_id,
+                    trade.price,
+                    trade.quantity,
+                )),
+                _ => None,
+            })
+            .collect();
+        let [bid_a, bid_b, ask_a, ask_b] = ids;
+        assert_eq!(
+            trades,
+            vec![
+                (bid_a, ask_a, 10_100, 100),
+                (bid_b, ask_a, 10_100, 50),
+                (bid_b, ask_b, 10_100, 50),
+            ]
+        );
+        assert_eq!(book.trading_phase(), TradingPhase::Continuous);
+        assert_eq!(book.last_trade_price(), Some(10_100));
+        assert_eq!(
+            (book.best_bid(), book.best_ask()),
+            (Some(10_000), Some(10_100))
+        );
+        assert_eq!(book.get_stats().total_trades, 3);
+
+        let last = auction.try_iter().last().unwrap();
+        assert_eq!(last.phase, TradingPhase::Continuous);
+        assert_eq!(last.equilibrium, None);
+        assert_eq!(
+            book.uncross().unwrap_err(),
+            OrderBookError::InvalidOrderState
+        );
+    }
+
+    #[test]
+    fn test_circuit_breakers() {
+        use crate::orderbook::circuit_breaker::{BandWidth, BreachAction, CircuitBreaker};
+
+        let market_buy =
+            |quantity| Order::new_market("TEST".to_string(), Side::Buy, quantity, None);
+        let traded = |events: &[MarketEvent]| -> Vec<(Price, Quantity)> {
+            events
+                .iter()
+                .filter_map(|event| match event {
+                    MarketEvent::Trade { trade } => Some((trade.price, trade.quantity)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // A sweep stops at the dynamic band and halts the book
+        let book = OrderBook::new("TEST".to_string()).with_circuit_breaker(
+            CircuitBreaker::new(BreachAction::Halt).with_dynamic_band(BandWidth::Absolute(100)),
+        );
+        for (price, quantity) in [(10_000, 100), (10_050, 100), (10_200, 100)] {
+            book.add_limit_order(create_limit_order(Side::Sell, price, quantity))
+                .unwrap();
         }
+        let resting = create_limit_order(Side::Buy, 9_900, 100);
+        let resting_id = resting.id;
+        book.add_limit_order(resting).unwrap();
+        book.add_market_order(market_buy(50)).unwrap();
+
+        let events = book.add_market_order(market_buy(300)).unwrap();
+        assert_eq!(traded(&events), vec![(10_000, 50), (10_050, 100)]);
+        assert!(matches!(
+            events.last(),
+            Some(MarketEvent::OrderCancelled {
+                remaining_quantity: 150,
+                ..
+            })
+        ));
+        assert!(matches!(
+            events.iter().rev().nth(1),
+            Some(MarketEvent::TradingHalted {
+                phase: TradingPhase::Halted,
+                reason: HaltReason::PriceBand {
+                    price: 10_200,
+                    low: 9_900,
+                    high: 10_100,
+                },
+            })
+        ));
+        assert_eq!(book.trading_phase(), TradingPhase::Halted);
+        assert!(matches!(
+            book.add_limit_order(create_limit_order(Side::Buy, 9_800, 100)),
+            Err(OrderBookError::TradingNotAllowed {
+                status: TradingStatus::Halted,
+                ..
+            })
+        ));
+        assert!(book.modify_order_quantity(&resting_id, 50).is_err());
+        book.cancel_order(&resting_id).unwrap();
+
+        assert!(matches!(
+            book.resume().unwrap()[..],
+            [MarketEvent::TradingResumed]
+        ));
+        assert_eq!(book.trading_phase(), TradingPhase::Continuous);
+        assert_eq!(
+            book.resume().unwrap_err(),
+            OrderBookError::InvalidOrderState
+        );
+
+        // Operators can halt at any time
+        assert!(matches!(
+            book.halt().unwrap(),
+            MarketEvent::TradingHalted {
+                phase: TradingPhase::Halted,
+                reason: HaltReason::Admin,
+            }
+        ));
+        assert_eq!(book.halt().unwrap_err(), OrderBookError::InvalidOrderState);
+
+        // Crossing the static band starts a volatility auction, which the
+        // rest of the order joins
+        let book = OrderBook::new("TEST".to_string()).with_circuit_breaker(
+            CircuitBreaker::new(BreachAction::VolatilityAuction)
+                .with_static_band(BandWidth::BasisPoints(100)),
+        );
+        book.set_reference_price(10_000).unwrap();
+        book.add_limit_order(create_limit_order(Side::Sell, 10_000, 100))
+            .unwrap();
+        book.add_limit_order(create_limit_order(Side::Sell, 10_150, 100))
+            .unwrap();
+
+        let events = book
+            .add_limit_order(create_limit_order(Side::Buy, 10_200, 150))
+            .unwrap();
+        assert_eq!(traded(&events), vec![(10_000, 100)]);
+        assert!(matches!(
+            events[..],
+            [
+                MarketEvent::Trade { .. },
+                MarketEvent::TradingHalted {
+                    phase: TradingPhase::VolatilityAuction,
+                    ..
+                },
+                MarketEvent::OrderAdded { .. },
+            ]
+        ));
+        assert_eq!(book.indicative_uncross().unwrap().price, 10_150);
+
+        let events = book.uncross().unwrap();
+        assert_eq!(traded(&events), vec![(10_150, 50)]);
+        assert!(matches!(events.last(), Some(MarketEvent::TradingResumed)));
+        assert_eq!(book.reference_price(), Some(10_150));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Arc::new(OrderBookMetrics::for_symbol("TEST"));
+        let book = OrderBook::new("TEST".to_string()).with_metrics(Some(Arc::clone(&metrics)));
+
+        let bid = create_limit_order(Side::Buy, 9_900, 100);
+        let bid_id = bid.id;
+        book.add_limit_order(bid).unwrap();
+        book.add_limit_order(create_limit_order(Side::Sell, 10_000, 100))
+            .unwrap();
+        book.add_market_order(create_market_order(Side::Buy, 40))
+            .unwrap();
+        book.modify_order_quantity(&bid_id, 50).unwrap();
+        book.cancel_order(&bid_id).unwrap();
+
+        // Rejected commands are not counted, although an order rejected
+        // while matching is still timed
+        assert!(book.cancel_order(&bid_id).is_err());
+        assert!(book
+            .add_market_order(create_market_order(Side::Sell, 10))
+            .is_err());
+
+        assert_eq!(metrics.get_orders_added(), 3);
+        assert_eq!(metrics.get_orders_modified(), 1);
+        assert_eq!(metrics.get_orders_cancelled(), 1);
+        assert_eq!(metrics.get_trades_executed(), 1);
+        assert_eq!(metrics.get_total_volume(), 40);
+        assert_eq!(metrics.get_total_notional(), 400_000);
+
+        let latency = metrics.get_latency_stats();
+        assert_eq!(latency.add_order.samples, 4);
+        assert_eq!(latency.cancel_order.samples, 1);
+        assert_eq!(latency.modify_order.samples, 1);
+        // The limit orders and both market orders reached the matching step
+        assert_eq!(latency.match_order.samples, 4);
+    }
+
+    #[test]
+    fn test_failed_journal_append_is_rolled_back() {
+        let path = std::env::temp_dir().join(format!("orderbook-{}.wal", OrderId::new_v4()));
+        let book = OrderBook::with_journal("TEST".to_string(), &path, FsyncPolicy::Always).unwrap();
+        book.add_limit_order(create_limit_order(Side::Buy, 9_900, 10))
+            .unwrap();
+
+        // A write torn partway through, by a full disk say, is cut back off
+        book.journal.as_ref().unwrap().lock().tear_next_write(12);
+        let refused = create_limit_order(Side::Buy, 9_950, 10);
+        assert!(matches!(
+            book.add_limit_order(refused.clone()),
+            Err(OrderBookError::SystemError(_))
+        ));
+        assert!(!book.contains_order(&refused.id));
+
+        let later = create_limit_order(Side::Sell, 10_100, 5);
+        book.add_limit_order(later.clone()).unwrap();
+        assert_eq!(book.sequence_number(), 2);
+
+        let recovered = OrderBook::recover_from(&path).unwrap();
+        assert!(recovered.contains_order(&later.id));
+        assert!(!recovered.contains_order(&refused.id));
+        assert_eq!(recovered.total_orders(), 2);
+        assert_eq!(recovered.sequence_number(), 2);
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,126 @@
+//! Volatility circuit breakers
+//!
+//! A [`CircuitBreaker`] bounds the prices an order may trade at by a static
+//! band around the book's reference price (the last auction price, or one set
+//! by an operator) and a dynamic band around the last trade price before the
+//! order arrived. An order that would trade outside either band stops
+//! matching at the band, and the book either halts or enters a volatility
+//! auction.
+
+use serde::{Deserialize, Serialize};
+
+use crate::orderbook::types::{Price, TradingPhase};
+
+/// Half-width of a price band
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BandWidth {
+    /// Fixed distance from the reference price
+    Absolute(Price),
+    /// Distance in basis points of the reference price
+    BasisPoints(u32),
+}
+
+impl BandWidth {
+    /// Get the lowest and highest price inside the band around `reference`
+    pub fn limits(&self, reference: Price) -> (Price, Price) {
+        let width = match *self {
+            BandWidth::Absolute(width) => width,
+            BandWidth::BasisPoints(bps) => {
+                (reference as u128 * bps as u128 / 10_000).min(Price::MAX as u128) as Price
+            }
+        };
+        (
+            reference.saturating_sub(width),
+            reference.saturating_add(width),
+        )
+    }
+}
+
+/// What a book does when an order reaches a price band
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BreachAction {
+    /// Halt trading until an operator resumes it
+    Halt,
+    /// Start a volatility auction, ended by an uncross
+    VolatilityAuction,
+}
+
+impl BreachAction {
+    /// Get the trading phase the book moves into
+    pub fn phase(&self) -> TradingPhase {
+        match self {
+            BreachAction::Halt => TradingPhase::Halted,
+            BreachAction::VolatilityAuction => TradingPhase::VolatilityAuction,
+        }
+    }
+}
+
+/// Static and dynamic price bands enforced during continuous matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitBreaker {
+    /// Band around the reference price
+    pub static_band: Option<BandWidth>,
+    /// Band around the last trade price
+    pub dynamic_band: Option<BandWidth>,
+    pub action: BreachAction,
+}
+
+impl CircuitBreaker {
+    /// Circuit breaker that takes `action` on a breach, with no bands yet
+    pub fn new(action: BreachAction) -> Self {
+        Self {
+            static_band: None,
+            dynamic_band: None,
+            action,
+        }
+    }
+
+    pub fn with_static_band(mut self, width: BandWidth) -> Self {
+        self.static_band = Some(width);
+        self
+    }
+
+    pub fn with_dynamic_band(mut self, width: BandWidth) -> Self {
+        self.dynamic_band = Some(width);
+        self
+    }
+
+    /// Get the range of prices that may trade, given the reference price and
+    /// last trade price; a band without its reference price does not apply
+    pub fn limits(
+        &self,
+        reference_price: Option<Price>,
+        last_trade_price: Option<Price>,
+    ) -> (Price, Price) {
+        [
+            self.static_band.zip(reference_price),
+            self.dynamic_band.zip(last_trade_price),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|(width, reference)| width.limits(reference))
+        .fold((0, Price::MAX), |(low, high), (band_low, band_high)| {
+            (low.max(band_low), high.min(band_high))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_band_limits() {
+        assert_eq!(BandWidth::Absolute(50).limits(10_000), (9_950, 10_050));
+        assert_eq!(BandWidth::BasisPoints(500).limits(10_000), (9_500, 10_500));
+        assert_eq!(BandWidth::Absolute(50).limits(20), (0, 70));
+
+        let breaker = CircuitBreaker::new(BreachAction::Halt)
+            .with_static_band(BandWidth::BasisPoints(1_000))
+            .with_dynamic_band(BandWidth::Absolute(200));
+        assert_eq!(breaker.limits(None, None), (0, Price::MAX));
+        assert_eq!(breaker.limits(Some(10_000), None), (9_000, 11_000));
+        // Both bands apply, so the narrower side of each wins
+        assert_eq!(breaker.limits(Some(10_000), Some(10_900)), (10_700, 11_000));
+    }
+}
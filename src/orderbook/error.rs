@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::orderbook::risk::RiskRejection;
+use crate::orderbook::types::TradingStatus;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OrderBookError {
     /// Order not found in the book
@@ -39,6 +42,33 @@ pub enum OrderBookError {
     /// Price is outside allowed range
     PriceOutOfRange,
 
+    /// Market data update does not follow the last applied sequence number
+    SequenceGap { expected: u64, received: u64 },
+
+    /// No instrument is listed under the symbol
+    UnknownSymbol(String),
+
+    /// An instrument is already listed under the symbol
+    DuplicateSymbol(String),
+
+    /// Order was sent to the book of another symbol
+    SymbolMismatch { expected: String, received: String },
+
+    /// Instrument does not accept new orders in its current status
+    TradingNotAllowed {
+        symbol: String,
+        status: TradingStatus,
+    },
+
+    /// Post-only order would have taken liquidity
+    PostOnlyWouldCross,
+
+    /// Post-only slide order has no valid price behind the opposite best
+    PostOnlyCannotSlide,
+
+    /// Order failed a pre-trade risk check
+    RiskRejected(RiskRejection),
+
     /// System error
     SystemError(String),
 }
@@ -58,11 +88,62 @@ impl fmt::Display for OrderBookError {
             OrderBookError::SelfTrade => write!(f, "Self-trade not allowed"),
             OrderBookError::OrderTooLarge => write!(f, "Order size exceeds maximum"),
             OrderBookError::PriceOutOfRange => write!(f, "Price outside allowed range"),
+            OrderBookError::SequenceGap { expected, received } => write!(
+                f,
+                "Sequence gap: expected {}, received {}",
+                expected, received
+            ),
+            OrderBookError::UnknownSymbol(symbol) => write!(f, "Unknown symbol: {}", symbol),
+            OrderBookError::DuplicateSymbol(symbol) => {
+                write!(f, "Symbol already listed: {}", symbol)
+            }
+            OrderBookError::SymbolMismatch { expected, received } => write!(
+                f,
+                "Symbol mismatch: book is {}, order is for {}",
+                expected, received
+            ),
+            OrderBookError::TradingNotAllowed { symbol, status } => {
+                write!(f, "Trading not allowed in {}: {:?}", symbol, status)
+            }
+            OrderBookError::PostOnlyWouldCross => write!(f, "Post-only order would cross"),
+            OrderBookError::PostOnlyCannotSlide => {
+                write!(f, "Post-only order cannot slide behind the opposite best")
+            }
+            OrderBookError::RiskRejected(reason) => write!(f, "Risk check failed: {}", reason),
             OrderBookError::SystemError(msg) => write!(f, "System error: {}", msg),
         }
     }
 }
 
+impl OrderBookError {
+    /// Stable numeric code for reporting the error over the wire
+    pub fn code(&self) -> u16 {
+        match self {
+            OrderBookError::OrderNotFound => 1,
+            OrderBookError::InvalidSymbol => 2,
+            OrderBookError::InvalidOrderType => 3,
+            OrderBookError::InvalidPrice => 4,
+            OrderBookError::InvalidQuantity => 5,
+            OrderBookError::NoLiquidity => 6,
+            OrderBookError::DuplicateOrder => 7,
+            OrderBookError::OverFill => 8,
+            OrderBookError::InvalidOrderState => 9,
+            OrderBookError::SelfTrade => 10,
+            OrderBookError::OrderTooLarge => 11,
+            OrderBookError::PriceOutOfRange => 12,
+            OrderBookError::SequenceGap { .. } => 13,
+            OrderBookError::UnknownSymbol(_) => 14,
+            OrderBookError::DuplicateSymbol(_) => 15,
+            OrderBookError::SymbolMismatch { .. } => 16,
+            OrderBookError::TradingNotAllowed { .. } => 17,
+            OrderBookError::PostOnlyWouldCross => 18,
+            OrderBookError::PostOnlyCannotSlide => 19,
+            OrderBookError::RiskRejected(_) => 20,
+            OrderBookError::SystemError(_) => 999,
+        }
+    }
+}
+
 impl std::error::Error for OrderBookError {}
 
 /// Result type for order book operations
@@ -82,6 +163,13 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(OrderBookError::OrderNotFound.code(), 1);
+        assert_eq!(OrderBookError::NoLiquidity.code(), 6);
+        assert_eq!(OrderBookError::SystemError("io".to_string()).code(), 999);
+    }
+
     #[test]
     fn test_error_serialization() {
         let error = OrderBookError::OrderNotFound;
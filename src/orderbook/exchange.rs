@@ -0,0 +1,570 @@
+//! Multi-symbol exchange
+//!
+//! An [`Exchange`] lists instruments, each with its own [`OrderBook`] and
+//! [`InstrumentConfig`] reference data, and routes orders to the book named
+//! by `Order::symbol`. Orders for symbols that are not listed fail with
+//! `UnknownSymbol` before reaching any book. The exchange checks trading
+//! status and price bands; tick and lot sizes are enforced by the book
+//! itself through its [`Instrument`]. A halt is the book's own trading phase
+//! (`OrderBook::halt`), so `TradingStatus::Halted` always agrees with it.
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::orderbook::book::OrderBook;
+use crate::orderbook::circuit_breaker::CircuitBreaker;
+use crate::orderbook::error::OrderBookError;
+use crate::orderbook::instrument::Instrument;
+use crate::orderbook::journal::FsyncPolicy;
+use crate::orderbook::risk::RiskCheck;
+use crate::orderbook::types::{
+    MarketEvent, Order, OrderId, OrderType, Price, Quantity, TradingPhase, TradingStatus,
+};
+
+/// Static band of acceptable limit and stop prices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceBand {
+    pub low: Price,
+    pub high: Price,
+}
+
+impl PriceBand {
+    pub fn contains(&self, price: Price) -> bool {
+        (self.low..=self.high).contains(&price)
+    }
+}
+
+/// Reference data for one listed instrument
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstrumentConfig {
+    pub symbol: String,
+    /// Tick sizes, lot size and order size limits, enforced by the book
+    pub instrument: Instrument,
+    /// Limit and stop prices outside the band are rejected
+    pub price_band: Option<PriceBand>,
+    /// `Halted` halts the book when the instrument is listed
+    pub status: TradingStatus,
+    /// Price bands enforced by the book while matching
+    pub circuit_breaker: Option<CircuitBreaker>,
+}
+
+impl InstrumentConfig {
+    /// Open instrument with default `Instrument` rules and no price band
+    pub fn new(symbol: impl Into<String>) -> Self {
+        let symbol = symbol.into();
+        Self {
+            instrument: Instrument::new(symbol.clone()),
+            symbol,
+            price_band: None,
+            status: TradingStatus::Open,
+            circuit_breaker: None,
+        }
+    }
+
+    pub fn with_instrument(mut self, instrument: Instrument) -> Self {
+        self.instrument = instrument;
+        self
+    }
+
+    pub fn with_tick_size(mut self, tick_size: Price) -> Self {
+        self.instrument = self.instrument.with_tick_size(tick_size);
+        self
+    }
+
+    pub fn with_lot_size(mut self, lot_size: Quantity) -> Self {
+        self.instrument = self.instrument.with_lot_size(lot_size);
+        self
+    }
+
+    pub fn with_price_band(mut self, low: Price, high: Price) -> Self {
+        self.price_band = Some(PriceBand { low, high });
+        self
+    }
+
+    pub fn with_status(mut self, status: TradingStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Check an incoming order against the trading status and price band
+    pub fn validate_order(&self, order: &Order) -> Result<(), OrderBookError> {
+        self.check_open()?;
+
+        let limit_price = match order.order_type {
+            OrderType::Market | OrderType::MarketToLimit | OrderType::Stop => None,
+            _ => Some(order.price),
+        };
+        for price in [limit_price, order.stop_price()].into_iter().flatten() {
+            if self.price_band.is_some_and(|band| !band.contains(price)) {
+                return Err(OrderBookError::PriceOutOfRange);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the instrument accepts new orders and amendments
+    pub fn check_open(&self) -> Result<(), OrderBookError> {
+        if self.status != TradingStatus::Open {
+            return Err(OrderBookError::TradingNotAllowed {
+                symbol: self.symbol.clone(),
+                status: self.status,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Check that `book` is the book for `config`'s symbol
+fn check_book_symbol(config: &InstrumentConfig, book: &OrderBook) -> Result<(), OrderBookError> {
+    if book.symbol != config.symbol {
+        return Err(OrderBookError::SymbolMismatch {
+            expected: config.symbol.clone(),
+            received: book.symbol.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// A listed instrument
+///
+/// `config.status` is only ever `Open` or `Closed`; whether the instrument
+/// is halted is read from the book.
+#[derive(Debug)]
+struct Listing {
+    config: RwLock<InstrumentConfig>,
+    book: Arc<OrderBook>,
+}
+
+impl Listing {
+    fn status(&self) -> TradingStatus {
+        match self.config.read().status {
+            TradingStatus::Open if self.book.trading_phase() == TradingPhase::Halted => {
+                TradingStatus::Halted
+            }
+            status => status,
+        }
+    }
+}
+
+/// Registry of order books that routes orders by symbol
+///
+/// Instruments can be listed and delisted while orders are flowing. Cancels
+/// are accepted in every trading status so participants can always pull
+/// their orders; new orders and quantity changes need `TradingStatus::Open`.
+#[derive(Debug, Default)]
+pub struct Exchange {
+    listings: DashMap<String, Arc<Listing>>,
+    /// Risk checks added to the book of every instrument listed afterwards
+    risk_checks: Vec<Arc<dyn RiskCheck>>,
+}
+
+impl Exchange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run new orders in every book listed from now on through `check`, so
+    /// that one `AccountRisk` sees a client's positions across symbols
+    pub fn with_risk_check(mut self, check: Arc<dyn RiskCheck>) -> Self {
+        self.risk_checks.push(check);
+        self
+    }
+
+    /// List an instrument with a new, empty order book
+    pub fn add_instrument(
+        &self,
+        config: InstrumentConfig,
+    ) -> Result<Arc<OrderBook>, OrderBookError> {
+        let book = OrderBook::new(config.symbol.clone());
+        self.add_instrument_with_book(config, book)
+    }
+
+    /// List an instrument backed by `book`, journaling to `path` and
+    /// recovering from the journal there if one exists
+    ///
+    /// The book is configured as in `add_instrument_with_book` before the
+    /// journal is replayed, so recovery sees the same circuit breaker,
+    /// instrument rules and risk checks as the book that wrote it.
+    pub fn add_instrument_with_journal(
+        &self,
+        config: InstrumentConfig,
+        book: OrderBook,
+        path: impl AsRef<Path>,
+        policy: FsyncPolicy,
+    ) -> Result<Arc<OrderBook>, OrderBookError> {
+        check_book_symbol(&config, &book)?;
+        if self.listings.contains_key(&config.symbol) {
+            return Err(OrderBookError::DuplicateSymbol(config.symbol));
+        }
+        let book = self.configure(&config, book)?.open_journal(path, policy)?;
+        self.list(config, book)
+    }
+
+    /// List an instrument backed by an existing order book
+    ///
+    /// The book takes over the config's `Instrument` rules and circuit
+    /// breaker, and the exchange's risk checks. To recover a book from its
+    /// journal use `add_instrument_with_journal`, which applies them before
+    /// replaying.
+    pub fn add_instrument_with_book(
+        &self,
+        config: InstrumentConfig,
+        book: OrderBook,
+    ) -> Result<Arc<OrderBook>, OrderBookError> {
+        check_book_symbol(&config, &book)?;
+        let book = self.configure(&config, book)?;
+        self.list(config, book)
+    }
+
+    /// Insert a listing for a configured book, halting it if the config
+    /// lists it halted
+    fn list(
+        &self,
+        mut config: InstrumentConfig,
+        book: OrderBook,
+    ) -> Result<Arc<OrderBook>, OrderBookError> {
+        match self.listings.entry(config.symbol.clone()) {
+            Entry::Occupied(_) => Err(OrderBookError::DuplicateSymbol(config.symbol)),
+            Entry::Vacant(entry) => {
+                info!("Listing instrument {}", config.symbol);
+                if config.status == TradingStatus::Halted {
+                    config.status = TradingStatus::Open;
+                    if book.trading_phase() != TradingPhase::Halted {
+                        book.halt()?;
+                    }
+                }
+                let book = Arc::new(book);
+                entry.insert(Arc::new(Listing {
+                    config: RwLock::new(config),
+                    book: Arc::clone(&book),
+                }));
+                Ok(book)
+            }
+        }
+    }
+
+    /// Apply `config`'s trading rules and circuit breaker and the exchange's
+    /// risk checks to `book`
+    fn configure(
+        &self,
+        config: &InstrumentConfig,
+        book: OrderBook,
+    ) -> Result<OrderBook, OrderBookError> {
+        let mut book = book.with_instrument(config.instrument.clone())?;
+        if let Some(circuit_breaker) = config.circuit_breaker {
+            book = book.with_circuit_breaker(circuit_breaker);
+        }
+        for check in &self.risk_checks {
+            book = book.with_risk_check(Arc::clone(check));
+        }
+        Ok(book)
+    }
+
+    /// Delist an instrument, returning its book
+    ///
+    /// Orders still resting in the book stay there; the exchange simply stops
+    /// routing to it.
+    pub fn remove_instrument(&self, symbol: &str) -> Result<Arc<OrderBook>, OrderBookError> {
+        let (_, listing) = self
+            .listings
+            .remove(symbol)
+            .ok_or_else(|| OrderBookError::UnknownSymbol(symbol.to_string()))?;
+        info!(
+            "Delisted instrument {} with {} resting orders",
+            symbol,
+            listing.book.total_orders()
+        );
+        Ok(Arc::clone(&listing.book))
+    }
+
+    /// Get the order book for `symbol`
+    pub fn book(&self, symbol: &str) -> Result<Arc<OrderBook>, OrderBookError> {
+        Ok(Arc::clone(&self.listing(symbol)?.book))
+    }
+
+    /// Get the reference data for `symbol`
+    pub fn instrument(&self, symbol: &str) -> Result<InstrumentConfig, OrderBookError> {
+        let listing = self.listing(symbol)?;
+        let mut config = listing.config.read().clone();
+        config.status = listing.status();
+        Ok(config)
+    }
+
+    /// Get the trading status of `symbol`
+    pub fn trading_status(&self, symbol: &str) -> Result<TradingStatus, OrderBookError> {
+        Ok(self.listing(symbol)?.status())
+    }
+
+    /// Get all listed symbols, sorted
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.listings.iter().map(|l| l.key().clone()).collect();
+        symbols.sort();
+        symbols
+    }
+
+    /// Get all listed order books, sorted by symbol
+    pub fn books(&self) -> Vec<Arc<OrderBook>> {
+        let mut books: Vec<Arc<OrderBook>> = self
+            .listings
+            .iter()
+            .map(|listing| Arc::clone(&listing.book))
+            .collect();
+        books.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        books
+    }
+
+    /// Get number of listed instruments
+    pub fn len(&self) -> usize {
+        self.listings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listings.is_empty()
+    }
+
+    /// Change the trading status of `symbol`
+    ///
+    /// `Halted` halts the book and `Open` resumes it if it is halted,
+    /// returning the book's events; `Closed` leaves the book as it is.
+    pub fn set_trading_status(
+        &self,
+        symbol: &str,
+        status: TradingStatus,
+    ) -> Result<Vec<MarketEvent>, OrderBookError> {
+        let listing = self.listing(symbol)?;
+        let halted = listing.book.trading_phase() == TradingPhase::Halted;
+        let events = match status {
+            TradingStatus::Halted if !halted => vec![listing.book.halt()?],
+            TradingStatus::Open if halted => listing.book.resume()?,
+            _ => Vec::new(),
+        };
+        listing.config.write().status = match status {
+            TradingStatus::Closed => TradingStatus::Closed,
+            TradingStatus::Open | TradingStatus::Halted => TradingStatus::Open,
+        };
+        info!("Trading status of {} is now {:?}", symbol, status);
+        Ok(events)
+    }
+
+    /// Change or remove the price band of `symbol`
+    pub fn set_price_band(
+        &self,
+        symbol: &str,
+        price_band: Option<PriceBand>,
+    ) -> Result<(), OrderBookError> {
+        self.listing(symbol)?.config.write().price_band = price_band;
+        info!("Price band of {} is now {:?}", symbol, price_band);
+        Ok(())
+    }
+
+    /// Halt matching in the book for `symbol` until `resume` is called
+    pub fn halt(&self, symbol: &str) -> Result<MarketEvent, OrderBookError> {
+        self.listing(symbol)?.book.halt()
+    }
+
+    /// Resume matching in the halted book for `symbol`
+    pub fn resume(&self, symbol: &str) -> Result<Vec<MarketEvent>, OrderBookError> {
+        self.listing(symbol)?.book.resume()
+    }
+
+    /// Route a limit order to its book
+    pub fn add_limit_order(&self, order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+        self.route(&order)?.add_limit_order(order)
+    }
+
+    /// Route a market order to its book
+    pub fn add_market_order(&self, order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+        self.route(&order)?.add_market_order(order)
+    }
+
+    /// Route a stop or stop-limit order to its book
+    pub fn add_stop_order(&self, order: Order) -> Result<Vec<MarketEvent>, OrderBookError> {
+        self.route(&order)?.add_stop_order(order)
+    }
+
+    /// Cancel an order in the book for `symbol`
+    pub fn cancel_order(
+        &self,
+        symbol: &str,
+        order_id: &OrderId,
+    ) -> Result<MarketEvent, OrderBookError> {
+        self.listing(symbol)?.book.cancel_order(order_id)
+    }
+
+    /// Change the open quantity of an order in the book for `symbol`
+    pub fn modify_order_quantity(
+        &self,
+        symbol: &str,
+        order_id: &OrderId,
+        new_quantity: Quantity,
+    ) -> Result<MarketEvent, OrderBookError> {
+        let listing = self.listing(symbol)?;
+        listing.config.read().check_open()?;
+        listing.book.modify_order_quantity(order_id, new_quantity)
+    }
+
+    fn listing(&self, symbol: &str) -> Result<Arc<Listing>, OrderBookError> {
+        self.listings
+            .get(symbol)
+            .map(|listing| Arc::clone(listing.value()))
+            .ok_or_else(|| OrderBookError::UnknownSymbol(symbol.to_string()))
+    }
+
+    /// Find the book for a new order and check it against the reference data
+    fn route(&self, order: &Order) -> Result<Arc<OrderBook>, OrderBookError> {
+        let listing = self.listing(&order.symbol)?;
+        listing.config.read().validate_order(order)?;
+        Ok(Arc::clone(&listing.book))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::types::Side;
+
+    fn limit(symbol: &str, side: Side, price: Price, quantity: Quantity) -> Order {
+        Order::new_limit(symbol.to_string(), side, price, quantity, None)
+    }
+
+    #[test]
+    fn test_routing_by_symbol() {
+        let exchange = Exchange::new();
+        exchange
+            .add_instrument(InstrumentConfig::new("AAPL"))
+            .unwrap();
+        exchange
+            .add_instrument(InstrumentConfig::new("MSFT"))
+            .unwrap();
+        assert_eq!(
+            exchange
+                .add_instrument(InstrumentConfig::new("AAPL"))
+                .unwrap_err(),
+            OrderBookError::DuplicateSymbol("AAPL".to_string())
+        );
+        assert_eq!(exchange.symbols(), vec!["AAPL", "MSFT"]);
+
+        exchange
+            .add_limit_order(limit("AAPL", Side::Sell, 15_000, 100))
+            .unwrap();
+        exchange
+            .add_limit_order(limit("MSFT", Side::Sell, 30_000, 100))
+            .unwrap();
+        let events = exchange
+            .add_market_order(Order::new_market("AAPL".to_string(), Side::Buy, 40, None))
+            .unwrap();
+        assert!(matches!(&events[0], MarketEvent::Trade { trade } if trade.price == 15_000));
+        assert_eq!(exchange.book("MSFT").unwrap().best_ask(), Some(30_000));
+
+        assert_eq!(
+            exchange
+                .add_limit_order(limit("TSLA", Side::Buy, 100, 1))
+                .unwrap_err(),
+            OrderBookError::UnknownSymbol("TSLA".to_string())
+        );
+
+        // A delisted symbol no longer routes; its book keeps its orders
+        let book = exchange.remove_instrument("MSFT").unwrap();
+        assert_eq!(book.total_orders(), 1);
+        assert!(matches!(
+            exchange.book("MSFT"),
+            Err(OrderBookError::UnknownSymbol(_))
+        ));
+        assert_eq!(exchange.len(), 1);
+
+        // Books still refuse orders for other symbols
+        assert!(matches!(
+            book.add_limit_order(limit("AAPL", Side::Buy, 100, 1)),
+            Err(OrderBookError::SymbolMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reference_data_checks() {
+        let exchange = Exchange::new();
+        let config = InstrumentConfig::new("ES")
+            .with_tick_size(25)
+            .with_lot_size(10)
+            .with_price_band(400_000, 500_000);
+        exchange.add_instrument(config).unwrap();
+
+        let check = |price, quantity| {
+            exchange
+                .add_limit_order(limit("ES", Side::Buy, price, quantity))
+                .map(|_| ())
+        };
+        assert_eq!(check(450_000, 20), Ok(()));
+        assert_eq!(check(450_010, 20), Err(OrderBookError::InvalidPrice));
+        assert_eq!(check(450_000, 15), Err(OrderBookError::InvalidQuantity));
+        assert_eq!(check(600_000, 20), Err(OrderBookError::PriceOutOfRange));
+
+        let stop = Order::new_stop("ES".to_string(), Side::Sell, 300_000, 10, None);
+        assert_eq!(
+            exchange.add_stop_order(stop).unwrap_err(),
+            OrderBookError::PriceOutOfRange
+        );
+
+        // Halted instruments take cancels but not new orders or amendments
+        let order = limit("ES", Side::Buy, 449_975, 10);
+        let order_id = order.id;
+        exchange.add_limit_order(order).unwrap();
+        exchange
+            .set_trading_status("ES", TradingStatus::Halted)
+            .unwrap();
+        assert!(matches!(
+            check(450_000, 10),
+            Err(OrderBookError::TradingNotAllowed {
+                status: TradingStatus::Halted,
+                ..
+            })
+        ));
+        assert!(matches!(
+            exchange.modify_order_quantity("ES", &order_id, 20),
+            Err(OrderBookError::TradingNotAllowed { .. })
+        ));
+        exchange.cancel_order("ES", &order_id).unwrap();
+
+        // The status and the book's phase are the same halt
+        let book = exchange.book("ES").unwrap();
+        assert_eq!(book.trading_phase(), TradingPhase::Halted);
+        assert_eq!(
+            exchange.instrument("ES").unwrap().status,
+            TradingStatus::Halted
+        );
+        assert!(matches!(
+            exchange
+                .set_trading_status("ES", TradingStatus::Open)
+                .unwrap()[..],
+            [MarketEvent::TradingResumed]
+        ));
+        assert_eq!(book.trading_phase(), TradingPhase::Continuous);
+
+        book.halt().unwrap();
+        assert_eq!(exchange.trading_status("ES"), Ok(TradingStatus::Halted));
+        exchange.resume("ES").unwrap();
+        assert_eq!(exchange.trading_status("ES"), Ok(TradingStatus::Open));
+
+        exchange.set_price_band("ES", None).unwrap();
+        assert_eq!(check(600_000, 10), Ok(()));
+        assert_eq!(
+            exchange
+                .book("ES")
+                .unwrap()
+                .instrument()
+                .tick_table
+                .tick_size_at(600_000),
+            25
+        );
+    }
+}
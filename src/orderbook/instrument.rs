@@ -0,0 +1,399 @@
+//! Instrument reference data
+//!
+//! A [`Price`] is an integer number of price units, where one unit is
+//! `10^-price_scale` of the quote currency. The [`Instrument`] decides which
+//! of those prices are tradable, through a price-dependent [`TickSizeTable`],
+//! and which quantities are, through the lot size and order size limits.
+
+use serde::{Deserialize, Serialize};
+
+use crate::orderbook::error::OrderBookError;
+use crate::orderbook::types::{Order, OrderType, Price, Quantity, Side};
+
+/// Prices at or above `from` trade in increments of `tick_size`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickBand {
+    pub from: Price,
+    pub tick_size: Price,
+}
+
+/// Price-dependent tick sizes, such as the MiFID II tick regimes
+///
+/// Bands are sorted by their starting price; the first starts at 0. Every
+/// band starts on a multiple of its own tick size, so a price is valid
+/// exactly when it is a multiple of the tick size of its band.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickSizeTable {
+    bands: Vec<TickBand>,
+}
+
+impl TickSizeTable {
+    /// One tick size for all prices
+    pub fn fixed(tick_size: Price) -> Self {
+        Self {
+            bands: vec![TickBand {
+                from: 0,
+                tick_size: tick_size.max(1),
+            }],
+        }
+    }
+
+    /// Build a table from `(from, tick_size)` bands in ascending order
+    pub fn new(bands: &[(Price, Price)]) -> Result<Self, OrderBookError> {
+        let bands: Vec<TickBand> = bands
+            .iter()
+            .map(|&(from, tick_size)| TickBand { from, tick_size })
+            .collect();
+
+        let valid = bands.first().is_some_and(|band| band.from == 0)
+            && bands.windows(2).all(|pair| pair[0].from < pair[1].from)
+            && bands
+                .iter()
+                .all(|band| band.tick_size > 0 && band.from.is_multiple_of(band.tick_size));
+        if !valid {
+            return Err(OrderBookError::SystemError(
+                "tick size table bands must start at 0, ascend, and start on a tick".to_string(),
+            ));
+        }
+
+        Ok(Self { bands })
+    }
+
+    pub fn bands(&self) -> &[TickBand] {
+        &self.bands
+    }
+
+    /// Get the tick size that applies at `price`
+    pub fn tick_size_at(&self, price: Price) -> Price {
+        self.band_at(price).tick_size
+    }
+
+    /// Check that `price` lies on the tick grid
+    pub fn is_valid_price(&self, price: Price) -> bool {
+        price.is_multiple_of(self.tick_size_at(price))
+    }
+
+    /// Get the highest valid price strictly below `price`
+    pub fn tick_below(&self, price: Price) -> Option<Price> {
+        let below = price.checked_sub(1)?;
+        let tick = self.tick_size_at(below);
+        Some(below - below % tick).filter(|&p| p > 0)
+    }
+
+    /// Get the lowest valid price strictly above `price`
+    pub fn tick_above(&self, price: Price) -> Option<Price> {
+        let tick = self.tick_size_at(price);
+        let above = price.checked_add(tick - price % tick)?;
+        // Crossing into a band with a coarser tick may leave `above` off-grid
+        let tick = self.tick_size_at(above);
+        above.checked_add((tick - above % tick) % tick)
+    }
+
+    fn band_at(&self, price: Price) -> &TickBand {
+        let index = self.bands.partition_point(|band| band.from <= price);
+        &self.bands[index.saturating_sub(1)]
+    }
+}
+
+/// How far past the best price a market order may trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MarketProtection {
+    /// Number of ticks through the best price
+    Ticks(u32),
+    /// Distance in basis points of the best price
+    BasisPoints(u32),
+}
+
+/// Trading rules of one instrument
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instrument {
+    pub symbol: String,
+    pub tick_table: TickSizeTable,
+    /// Order quantities must be a multiple of the lot size
+    pub lot_size: Quantity,
+    pub min_quantity: Quantity,
+    pub max_quantity: Quantity,
+    /// Decimal places of the quote currency carried by one price unit
+    pub price_scale: u32,
+    /// Limit on how far market orders sweep; `None` sweeps the whole book
+    pub market_protection: Option<MarketProtection>,
+}
+
+impl Instrument {
+    /// Largest price scale whose unit, `10^price_scale`, fits in a `Price`
+    pub const MAX_PRICE_SCALE: u32 = 19;
+
+    /// Instrument with unit ticks and lots, no size limits and cents pricing
+    pub fn new(symbol: impl Into<String>) -> Self {
+        Self {
+            symbol: symbol.into(),
+            tick_table: TickSizeTable::fixed(1),
+            lot_size: 1,
+            min_quantity: 1,
+            max_quantity: Quantity::MAX,
+            price_scale: 2,
+            market_protection: None,
+        }
+    }
+
+    pub fn with_tick_table(mut self, tick_table: TickSizeTable) -> Self {
+        self.tick_table = tick_table;
+        self
+    }
+
+    pub fn with_tick_size(self, tick_size: Price) -> Self {
+        self.with_tick_table(TickSizeTable::fixed(tick_size))
+    }
+
+    pub fn with_lot_size(mut self, lot_size: Quantity) -> Self {
+        self.lot_size = lot_size.max(1);
+        self
+    }
+
+    pub fn with_quantity_limits(mut self, min_quantity: Quantity, max_quantity: Quantity) -> Self {
+        self.min_quantity = min_quantity.max(1);
+        self.max_quantity = max_quantity;
+        self
+    }
+
+    /// Set the price scale, capped at [`Self::MAX_PRICE_SCALE`]
+    pub fn with_price_scale(mut self, price_scale: u32) -> Self {
+        self.price_scale = price_scale.min(Self::MAX_PRICE_SCALE);
+        self
+    }
+
+    pub fn with_market_protection(mut self, market_protection: MarketProtection) -> Self {
+        self.market_protection = Some(market_protection);
+        self
+    }
+
+    /// Get the worst price a market order on `side` may trade at when the
+    /// opposite best is `best_price`, if market protection is set
+    pub fn market_limit(&self, side: Side, best_price: Price) -> Option<Price> {
+        match self.market_protection? {
+            MarketProtection::Ticks(ticks) => {
+                let mut limit = best_price;
+                for _ in 0..ticks {
+                    let next = match side {
+                        Side::Buy => self.tick_table.tick_above(limit),
+                        Side::Sell => self.tick_table.tick_below(limit),
+                    };
+                    match next {
+                        Some(price) => limit = price,
+                        None => break,
+                    }
+                }
+                Some(limit)
+            }
+            MarketProtection::BasisPoints(bps) => {
+                let width = (best_price as u128 * bps as u128 / 10_000).min(Price::MAX as u128);
+                Some(match side {
+                    Side::Buy => best_price.saturating_add(width as Price),
+                    Side::Sell => best_price.saturating_sub(width as Price),
+                })
+            }
+        }
+    }
+
+    /// Check an order's quantity and its limit and stop prices
+    pub fn validate_order(&self, order: &Order) -> Result<(), OrderBookError> {
+        self.validate_quantity(order.original_quantity)?;
+        if let Some(display_quantity) = order.display_quantity {
+            if display_quantity == 0 || !display_quantity.is_multiple_of(self.lot_size) {
+                return Err(OrderBookError::InvalidQuantity);
+            }
+        }
+
+        let limit_price = match order.order_type {
+            OrderType::Market | OrderType::MarketToLimit | OrderType::Stop => None,
+            _ => Some(order.price),
+        };
+        for price in [limit_price, order.stop_price()].into_iter().flatten() {
+            self.validate_price(price)?;
+        }
+        Ok(())
+    }
+
+    /// Check that `price` is positive and on the tick grid
+    pub fn validate_price(&self, price: Price) -> Result<(), OrderBookError> {
+        if price == 0 || !self.tick_table.is_valid_price(price) {
+            return Err(OrderBookError::InvalidPrice);
+        }
+        Ok(())
+    }
+
+    /// Check that `quantity` is a whole number of lots within the size limits
+    pub fn validate_quantity(&self, quantity: Quantity) -> Result<(), OrderBookError> {
+        if quantity > self.max_quantity {
+            return Err(OrderBookError::OrderTooLarge);
+        }
+        if quantity == 0 || quantity < self.min_quantity || !quantity.is_multiple_of(self.lot_size)
+        {
+            return Err(OrderBookError::InvalidQuantity);
+        }
+        Ok(())
+    }
+
+    /// Format a price as a decimal in the quote currency
+    pub fn format_price(&self, price: Price) -> String {
+        if self.price_scale == 0 {
+            return price.to_string();
+        }
+        // Past `MAX_PRICE_SCALE` every price is a fraction of one unit
+        let (whole, fraction) = match 10u64.checked_pow(self.price_scale) {
+            Some(scale) => (price / scale, price % scale),
+            None => (0, price),
+        };
+        format!(
+            "{}.{:0width$}",
+            whole,
+            fraction,
+            width = self.price_scale as usize
+        )
+    }
+
+    /// Parse a decimal price in the quote currency
+    ///
+    /// Returns `None` for malformed prices and prices finer than one unit;
+    /// whether the price is on a tick is left to `validate_price`.
+    pub fn parse_price(&self, price: &str) -> Option<Price> {
+        let (whole, fraction) = price.split_once('.').unwrap_or((price, ""));
+        let fraction = fraction.trim_end_matches('0');
+        let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty())
+            || fraction.len() > self.price_scale as usize
+            || !digits(whole)
+            || !digits(fraction)
+        {
+            return None;
+        }
+
+        let whole: Price = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().ok()?
+        };
+        let fraction: Price = if fraction.is_empty() {
+            0
+        } else {
+            let padding = self.price_scale.checked_sub(fraction.len() as u32)?;
+            fraction
+                .parse::<Price>()
+                .ok()?
+                .checked_mul(10u64.checked_pow(padding)?)?
+        };
+        whole
+            .checked_mul(10u64.checked_pow(self.price_scale)?)?
+            .checked_add(fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::types::Side;
+
+    /// Equity-style table in cents: 0.01 below 10.00, 0.05 to 100.00, 0.10 above
+    fn banded_table() -> TickSizeTable {
+        TickSizeTable::new(&[(0, 1), (1_000, 5), (10_000, 10)]).unwrap()
+    }
+
+    #[test]
+    fn test_tick_size_table() {
+        let table = banded_table();
+        assert_eq!(table.tick_size_at(999), 1);
+        assert_eq!(table.tick_size_at(1_000), 5);
+        assert_eq!(table.tick_size_at(50_000), 10);
+
+        assert!(table.is_valid_price(999));
+        assert!(table.is_valid_price(1_005));
+        assert!(!table.is_valid_price(1_003));
+        assert!(!table.is_valid_price(10_005));
+
+        assert_eq!(table.tick_above(999), Some(1_000));
+        assert_eq!(table.tick_above(1_003), Some(1_005));
+        assert_eq!(table.tick_above(9_995), Some(10_000));
+        assert_eq!(table.tick_below(1_000), Some(999));
+        assert_eq!(table.tick_below(10_000), Some(9_995));
+        assert_eq!(table.tick_below(10_007), Some(10_000));
+        assert_eq!(table.tick_below(1), None);
+
+        assert!(TickSizeTable::new(&[]).is_err());
+        assert!(TickSizeTable::new(&[(0, 1), (1_003, 5)]).is_err());
+        assert!(TickSizeTable::new(&[(0, 1), (1_000, 5), (500, 1)]).is_err());
+    }
+
+    #[test]
+    fn test_order_validation() {
+        let instrument = Instrument::new("TEST")
+            .with_tick_table(banded_table())
+            .with_lot_size(10)
+            .with_quantity_limits(20, 10_000);
+        let limit = |price, quantity| {
+            instrument.validate_order(&Order::new_limit(
+                "TEST".to_string(),
+                Side::Buy,
+                price,
+                quantity,
+                None,
+            ))
+        };
+
+        assert_eq!(limit(1_005, 100), Ok(()));
+        assert_eq!(limit(1_003, 100), Err(OrderBookError::InvalidPrice));
+        assert_eq!(limit(1_005, 105), Err(OrderBookError::InvalidQuantity));
+        assert_eq!(limit(1_005, 10), Err(OrderBookError::InvalidQuantity));
+        assert_eq!(limit(1_005, 10_010), Err(OrderBookError::OrderTooLarge));
+
+        let stop_limit =
+            Order::new_stop_limit("TEST".to_string(), Side::Buy, 1_002, 1_005, 100, None);
+        assert_eq!(
+            instrument.validate_order(&stop_limit),
+            Err(OrderBookError::InvalidPrice)
+        );
+        let market = Order::new_market("TEST".to_string(), Side::Sell, 100, None);
+        assert_eq!(instrument.validate_order(&market), Ok(()));
+    }
+
+    #[test]
+    fn test_market_limit() {
+        let instrument = Instrument::new("TEST").with_tick_table(banded_table());
+        assert_eq!(instrument.market_limit(Side::Buy, 995), None);
+
+        let instrument = instrument.with_market_protection(MarketProtection::Ticks(3));
+        assert_eq!(instrument.market_limit(Side::Buy, 998), Some(1_005));
+        assert_eq!(instrument.market_limit(Side::Sell, 1_005), Some(998));
+        // Protection stops at the lowest valid price
+        assert_eq!(instrument.market_limit(Side::Sell, 2), Some(1));
+
+        let instrument = instrument.with_market_protection(MarketProtection::BasisPoints(50));
+        assert_eq!(instrument.market_limit(Side::Buy, 10_000), Some(10_050));
+        assert_eq!(instrument.market_limit(Side::Sell, 10_000), Some(9_950));
+    }
+
+    #[test]
+    fn test_price_formatting() {
+        let instrument = Instrument::new("TEST");
+        assert_eq!(instrument.format_price(12_550), "125.50");
+        assert_eq!(instrument.parse_price("125.5"), Some(12_550));
+        assert_eq!(instrument.parse_price("125.505"), None);
+
+        let instrument = instrument.with_price_scale(4);
+        assert_eq!(instrument.format_price(1_000_025), "100.0025");
+        assert_eq!(instrument.parse_price("100.0025"), Some(1_000_025));
+
+        let instrument = instrument.with_price_scale(25);
+        assert_eq!(instrument.price_scale, Instrument::MAX_PRICE_SCALE);
+        assert_eq!(instrument.format_price(5), "0.0000000000000000005");
+        assert_eq!(instrument.parse_price("1.5"), Some(15 * 10u64.pow(18)));
+        assert_eq!(instrument.parse_price("18.5"), None);
+
+        // Scales past the cap can still arrive through deserialization
+        let instrument = Instrument {
+            price_scale: 21,
+            ..instrument
+        };
+        assert_eq!(instrument.format_price(5), "0.000000000000000000005");
+        assert_eq!(instrument.parse_price("0.5"), None);
+    }
+}
@@ -0,0 +1,643 @@
+//! Write-ahead journal of accepted order book commands
+//!
+//! The journal is an append-only file of framed records:
+//!
+//! ```text
+//! +----------------+----------------+---------------------+
+//! | length: u32 LE | crc32: u32 LE  | payload (JSON)      |
+//! +----------------+----------------+---------------------+
+//! ```
+//!
+//! The first frame holds a [`JournalHeader`]; every later frame holds a
+//! [`JournalRecord`]. Reading stops at the first incomplete or corrupt frame,
+//! which is how a write torn by a crash shows up at the tail of the file.
+//!
+//! A failed append never leaves a frame behind for later records to follow:
+//! the writer truncates back to its last good record, and if it cannot, or
+//! if a sync fails, it refuses every later append.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+use crate::orderbook::types::{Order, OrderId, Price, Quantity, TradingPhase};
+
+/// Current on-disk journal format version
+pub const JOURNAL_VERSION: u32 = 1;
+
+/// Size of the length and checksum prefix on every frame
+const FRAME_HEADER_LEN: usize = 8;
+
+/// When appended records are forced to stable storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FsyncPolicy {
+    /// `fsync` after every record (survives power loss)
+    #[default]
+    Always,
+    /// `fsync` after every `n` records
+    EveryN(u32),
+    /// Leave flushing to the OS (survives process crashes only)
+    Never,
+}
+
+/// First frame of every journal file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalHeader {
+    pub version: u32,
+    pub symbol: String,
+}
+
+/// A command accepted by the order book
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JournalCommand {
+    AddLimit {
+        order: Order,
+    },
+    AddMarket {
+        order: Order,
+    },
+    AddStop {
+        order: Order,
+    },
+    Cancel {
+        order_id: OrderId,
+    },
+    ModifyQuantity {
+        order_id: OrderId,
+        new_quantity: Quantity,
+    },
+    /// Expiry sweep as of `as_of`; `session_closed` also expires DAY orders
+    Expire {
+        as_of: DateTime<Utc>,
+        session_closed: bool,
+    },
+    /// Start of an opening or closing call
+    StartCall {
+        phase: TradingPhase,
+    },
+    /// Auction uncross ending the call
+    Uncross,
+    /// Operator halt
+    Halt,
+    /// Operator resume, uncrossing the book if it crosses
+    Resume,
+    /// New reference price for the static price band
+    SetReferencePrice {
+        price: Price,
+    },
+    /// Circuit breaker trip while matching the previous command, which
+    /// moved the book into `phase`
+    PriceBandBreach {
+        phase: TradingPhase,
+        price: Price,
+        low: Price,
+        high: Price,
+    },
+}
+
+/// A journaled command with the book sequence number it was accepted under
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalRecord {
+    pub sequence: u64,
+    pub command: JournalCommand,
+}
+
+/// Contents of a journal file up to its last intact record
+#[derive(Debug)]
+pub struct JournalContents {
+    pub header: JournalHeader,
+    pub records: Vec<JournalRecord>,
+    /// Byte length of the intact prefix of the file
+    pub valid_len: u64,
+}
+
+/// Append-only journal writer
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+    policy: FsyncPolicy,
+    unsynced: u32,
+    /// Byte length of the file up to the last record appended in full
+    len: u64,
+    /// A write or sync failed in a way truncating could not undo
+    failed: bool,
+    /// Make the next frame write stop with an error after this many bytes
+    #[cfg(test)]
+    tear_next_write: Option<usize>,
+}
+
+impl Journal {
+    /// Create a new journal, writing its header
+    pub fn create(path: impl AsRef<Path>, symbol: &str, policy: FsyncPolicy) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        let mut journal = Self::with_file(file, path, policy, 0);
+
+        let header = JournalHeader {
+            version: JOURNAL_VERSION,
+            symbol: symbol.to_string(),
+        };
+        journal.write_frame(&serde_json::to_vec(&header)?)?;
+        journal.file.sync_all()?;
+        journal.len = journal.file.metadata()?.len();
+
+        info!("Created journal {} for {}", journal.path.display(), symbol);
+        Ok(journal)
+    }
+
+    /// Open an existing journal for appending after its last intact record
+    ///
+    /// Any torn or corrupt tail is truncated so new records follow directly on
+    /// from the recovered ones.
+    pub fn open_append(
+        path: impl AsRef<Path>,
+        valid_len: u64,
+        policy: FsyncPolicy,
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().write(true).open(&path)?;
+
+        if file.metadata()?.len() > valid_len {
+            warn!(
+                "Truncating journal {} to {} bytes after torn tail",
+                path.display(),
+                valid_len
+            );
+            file.set_len(valid_len)?;
+            file.sync_all()?;
+        }
+
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(Self::with_file(file, path, policy, valid_len))
+    }
+
+    fn with_file(file: File, path: PathBuf, policy: FsyncPolicy, len: u64) -> Self {
+        Self {
+            file,
+            path,
+            policy,
+            unsynced: 0,
+            len,
+            failed: false,
+            #[cfg(test)]
+            tear_next_write: None,
+        }
+    }
+
+    /// Read a journal file up to its last intact record
+    pub fn read(path: impl AsRef<Path>) -> io::Result<JournalContents> {
+        let mut data = Vec::new();
+        File::open(path.as_ref())?.read_to_end(&mut data)?;
+
+        let mut offset = 0;
+        let header: JournalHeader = match next_frame(&data, &mut offset) {
+            Some(payload) => serde_json::from_slice(payload)?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "journal header is missing or corrupt",
+                ))
+            }
+        };
+
+        if header.version != JOURNAL_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported journal version {}", header.version),
+            ));
+        }
+
+        let mut records = Vec::new();
+        let mut valid_len = offset;
+        while let Some(payload) = next_frame(&data, &mut offset) {
+            match serde_json::from_slice::<JournalRecord>(payload) {
+                Ok(record) => {
+                    records.push(record);
+                    valid_len = offset;
+                }
+                Err(e) => {
+                    warn!("Stopping journal replay at undecodable record: {}", e);
+                    break;
+                }
+            }
+        }
+
+        if valid_len < data.len() {
+            warn!(
+                "Journal {} has {} trailing bytes after the last intact record",
+                path.as_ref().display(),
+                data.len() - valid_len
+            );
+        }
+
+        Ok(JournalContents {
+            header,
+            records,
+            valid_len: valid_len as u64,
+        })
+    }
+
+    /// Append a record, syncing according to the fsync policy
+    ///
+    /// On error the record is not in the journal.
+    pub fn append(&mut self, record: &JournalRecord) -> io::Result<()> {
+        self.check_usable()?;
+        let payload = serde_json::to_vec(record)?;
+        if let Err(e) = self.write_frame(&payload) {
+            self.truncate_to_last_record();
+            return Err(e);
+        }
+
+        self.unsynced += 1;
+        let should_sync = match self.policy {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::EveryN(n) => self.unsynced >= n.max(1),
+            FsyncPolicy::Never => false,
+        };
+        if should_sync {
+            if let Err(e) = self.file.sync_data() {
+                // What reached the disk is unknown; take the record back out
+                // and stop appending
+                self.truncate_to_last_record();
+                self.fail(&e);
+                return Err(e);
+            }
+            self.unsynced = 0;
+        }
+
+        self.len += (FRAME_HEADER_LEN + payload.len()) as u64;
+        Ok(())
+    }
+
+    /// Force every appended record to stable storage
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.check_usable()?;
+        if let Err(e) = self.file.sync_data() {
+            self.fail(&e);
+            return Err(e);
+        }
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    /// Check whether an earlier error left the journal refusing appends
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Get the journal file path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write_frame(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&crc32(payload).to_le_bytes());
+        frame.extend_from_slice(payload);
+
+        #[cfg(test)]
+        if let Some(written) = self.tear_next_write.take() {
+            self.file.write_all(&frame[..written.min(frame.len())])?;
+            return Err(io::Error::new(io::ErrorKind::StorageFull, "torn write"));
+        }
+
+        // One write per frame, so a killed process leaves at most one torn frame
+        self.file.write_all(&frame)
+    }
+
+    /// Make the next append fail after writing `written` bytes of its frame
+    #[cfg(test)]
+    pub(crate) fn tear_next_write(&mut self, written: usize) {
+        self.tear_next_write = Some(written);
+    }
+
+    fn check_usable(&self) -> io::Result<()> {
+        if self.failed {
+            return Err(io::Error::other(format!(
+                "journal {} stopped after a write error",
+                self.path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Cut off whatever part of a failed append reached the file
+    fn truncate_to_last_record(&mut self) {
+        let truncated = self
+            .file
+            .set_len(self.len)
+            .and_then(|()| self.file.seek(SeekFrom::Start(self.len)));
+        if let Err(e) = truncated {
+            self.fail(&e);
+        }
+    }
+
+    fn fail(&mut self, e: &io::Error) {
+        error!(
+            "Journal {} refuses further appends: {}",
+            self.path.display(),
+            e
+        );
+        self.failed = true;
+    }
+}
+
+/// Decode the frame at `offset`, advancing past it if it is intact
+fn next_frame<'a>(data: &'a [u8], offset: &mut usize) -> Option<&'a [u8]> {
+    let header = data.get(*offset..*offset + FRAME_HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[0..4].try_into().ok()?) as usize;
+    let checksum = u32::from_le_bytes(header[4..8].try_into().ok()?);
+
+    let start = *offset + FRAME_HEADER_LEN;
+    let payload = data.get(start..start.checked_add(len)?)?;
+    if crc32(payload) != checksum {
+        return None;
+    }
+
+    *offset = start + len;
+    Some(payload)
+}
+
+/// CRC-32 (IEEE 802.3) checksum
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::types::{MarketEvent, Side};
+    use crate::orderbook::OrderBook;
+    use uuid::Uuid;
+
+    fn temp_journal_path() -> PathBuf {
+        std::env::temp_dir().join(format!("orderbook-journal-{}.wal", Uuid::new_v4()))
+    }
+
+    fn limit(side: Side, price: u64, quantity: u64) -> Order {
+        Order::new_limit("TEST".to_string(), side, price, quantity, None)
+    }
+
+    /// Compare everything `snapshot()` exposes except its wall-clock timestamp
+    fn assert_same_book(left: &OrderBook, right: &OrderBook) {
+        let (a, b) = (left.snapshot(), right.snapshot());
+        assert_eq!(a.symbol, b.symbol);
+        assert_eq!(
+            serde_json::to_string(&a.bids).unwrap(),
+            serde_json::to_string(&b.bids).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_string(&a.asks).unwrap(),
+            serde_json::to_string(&b.asks).unwrap()
+        );
+        assert_eq!(a.last_trade_price, b.last_trade_price);
+        assert_eq!(left.total_orders(), right.total_orders());
+        assert_eq!(left.total_stop_orders(), right.total_stop_orders());
+        assert_eq!(left.sequence_number(), right.sequence_number());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let path = temp_journal_path();
+        let mut journal = Journal::create(&path, "TEST", FsyncPolicy::EveryN(2)).unwrap();
+
+        for sequence in 0..3 {
+            journal
+                .append(&JournalRecord {
+                    sequence,
+                    command: JournalCommand::Cancel {
+                        order_id: Uuid::new_v4(),
+                    },
+                })
+                .unwrap();
+        }
+
+        let contents = Journal::read(&path).unwrap();
+        assert_eq!(contents.header.symbol, "TEST");
+        assert_eq!(contents.records.len(), 3);
+        assert_eq!(contents.records[2].sequence, 2);
+        assert_eq!(contents.valid_len, std::fs::metadata(&path).unwrap().len());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_record_stops_replay() {
+        let path = temp_journal_path();
+        let mut journal = Journal::create(&path, "TEST", FsyncPolicy::Never).unwrap();
+        for sequence in 0..2 {
+            journal
+                .append(&JournalRecord {
+                    sequence,
+                    command: JournalCommand::AddLimit {
+                        order: limit(Side::Buy, 10000, 100),
+                    },
+                })
+                .unwrap();
+        }
+        drop(journal);
+
+        // Flip a byte inside the last record's payload
+        let mut data = std::fs::read(&path).unwrap();
+        let last = data.len() - 2;
+        data[last] ^= 0xFF;
+        std::fs::write(&path, &data).unwrap();
+
+        let contents = Journal::read(&path).unwrap();
+        assert_eq!(contents.records.len(), 1);
+        assert!(contents.valid_len < data.len() as u64);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recover_after_kill_mid_stream() {
+        let path = temp_journal_path();
+        let book = OrderBook::with_journal("TEST".to_string(), &path, FsyncPolicy::Always).unwrap();
+
+        let mut resting = Vec::new();
+        for i in 0..20 {
+            let bid = limit(Side::Buy, 9_900 + i * 5, 100 + i);
+            let ask = limit(Side::Sell, 10_000 + i * 5, 100 + i);
+            resting.push(bid.id);
+            resting.push(ask.id);
+            book.add_limit_order(bid).unwrap();
+            book.add_limit_order(ask).unwrap();
+        }
+
+        // Crossing flow, stops, cancels and modifies
+        book.add_limit_order(limit(Side::Buy, 10_020, 350)).unwrap();
+        book.add_market_order(Order::new_market("TEST".to_string(), Side::Sell, 150, None))
+            .unwrap();
+        book.add_stop_order(Order::new_stop(
+            "TEST".to_string(),
+            Side::Sell,
+            9_800,
+            50,
+            None,
+        ))
+        .unwrap();
+        book.cancel_order(&resting[10]).unwrap();
+        book.modify_order_quantity(&resting[13], 42).unwrap();
+        assert!(book.cancel_order(&Uuid::new_v4()).is_err());
+
+        // Reference state, then kill the book without running any destructors
+        let reference = OrderBook::recover_from(&path).unwrap();
+        assert_same_book(&book, &reference);
+        drop(reference);
+        std::mem::forget(book);
+
+        // A torn, half-written record at the tail is ignored and truncated
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0x40, 0x00, 0x00, 0x00, 0xAA, 0xBB])
+            .unwrap();
+        drop(file);
+
+        let recovered = OrderBook::recover_from(&path).unwrap();
+        let expected = OrderBook::recover_from(&path).unwrap();
+        assert_same_book(&recovered, &expected);
+        assert_eq!(recovered.total_orders(), 35);
+        assert_eq!(recovered.total_stop_orders(), 1);
+
+        // The recovered book keeps journaling from where it left off
+        let events = recovered
+            .add_limit_order(limit(Side::Sell, 9_990, 10))
+            .unwrap();
+        assert!(matches!(events[0], MarketEvent::Trade { .. }));
+        let contents = Journal::read(&path).unwrap();
+        assert_eq!(
+            contents.records.last().unwrap().sequence + 1,
+            recovered.sequence_number()
+        );
+
+        drop(recovered);
+        drop(expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_expiry_is_replayed() {
+        use crate::orderbook::types::TimeInForce;
+
+        let path = temp_journal_path();
+        let book = OrderBook::with_journal("TEST".to_string(), &path, FsyncPolicy::Always).unwrap();
+
+        book.add_limit_order(limit(Side::Buy, 9_900, 100)).unwrap();
+        book.add_limit_order(limit(Side::Buy, 9_800, 100).with_time_in_force(TimeInForce::Day))
+            .unwrap();
+        assert_eq!(book.close_session().unwrap().len(), 1);
+        assert!(book.close_session().unwrap().is_empty());
+
+        let recovered = OrderBook::recover_from(&path).unwrap();
+        assert_same_book(&book, &recovered);
+        assert_eq!(recovered.total_orders(), 1);
+
+        drop(book);
+        drop(recovered);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_call_auction_is_replayed() {
+        let path = temp_journal_path();
+        let book = OrderBook::with_journal("TEST".to_string(), &path, FsyncPolicy::Always).unwrap();
+
+        book.start_call(TradingPhase::PreOpen).unwrap();
+        book.add_limit_order(limit(Side::Buy, 10_100, 100)).unwrap();
+        book.add_limit_order(limit(Side::Sell, 9_900, 60)).unwrap();
+        book.uncross().unwrap();
+        book.start_call(TradingPhase::PreClose).unwrap();
+        book.add_limit_order(limit(Side::Sell, 10_000, 10)).unwrap();
+
+        let recovered = OrderBook::recover_from(&path).unwrap();
+        assert_same_book(&book, &recovered);
+        assert_eq!(recovered.trading_phase(), TradingPhase::PreClose);
+        assert_eq!(recovered.last_trade_price(), Some(10_100));
+
+        drop(book);
+        drop(recovered);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_circuit_breaker_is_replayed() {
+        use crate::orderbook::circuit_breaker::{BandWidth, BreachAction, CircuitBreaker};
+
+        let configured = || {
+            OrderBook::new("TEST".to_string()).with_circuit_breaker(
+                CircuitBreaker::new(BreachAction::Halt).with_static_band(BandWidth::Absolute(100)),
+            )
+        };
+        let path = temp_journal_path();
+        let book = configured()
+            .open_journal(&path, FsyncPolicy::Always)
+            .unwrap();
+
+        book.set_reference_price(10_000).unwrap();
+        book.add_limit_order(limit(Side::Sell, 10_050, 100))
+            .unwrap();
+        book.add_limit_order(limit(Side::Sell, 10_200, 100))
+            .unwrap();
+        // Trades 100 at 10_050, then trips the band at 10_200
+        book.add_limit_order(limit(Side::Buy, 10_300, 150)).unwrap();
+        assert_eq!(book.trading_phase(), TradingPhase::Halted);
+        assert!(matches!(
+            Journal::read(&path)
+                .unwrap()
+                .records
+                .last()
+                .unwrap()
+                .command,
+            JournalCommand::PriceBandBreach {
+                phase: TradingPhase::Halted,
+                price: 10_200,
+                ..
+            }
+        ));
+
+        // Replayed with the same breaker, the order stops at the band again
+        let recovered = configured()
+            .open_journal(&path, FsyncPolicy::Always)
+            .unwrap();
+        assert_same_book(&book, &recovered);
+        assert_eq!(recovered.trading_phase(), TradingPhase::Halted);
+        assert_eq!(recovered.best_bid(), Some(10_300));
+        assert_eq!(recovered.best_ask(), Some(10_200));
+
+        // Without it the book is still halted by the journaled breach
+        let unconfigured = OrderBook::recover_from(&path).unwrap();
+        assert_eq!(unconfigured.trading_phase(), TradingPhase::Halted);
+
+        // The recovered book resumes as the original would have
+        let events = recovered.resume().unwrap();
+        assert!(matches!(events.last(), Some(MarketEvent::TradingResumed)));
+        assert_eq!(recovered.total_orders(), 1);
+
+        drop(book);
+        drop(recovered);
+        drop(unconfigured);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::orderbook::price_level::PriceLevel;
+use crate::orderbook::types::{Price, Side};
+
+/// Sentinel stored in `best_price` while the ladder is empty
+const NO_PRICE: u64 = u64::MAX;
+
+/// Direction in which a ladder ranks its prices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LadderOrder {
+    /// Lowest price first (asks)
+    Ascending,
+    /// Highest price first (bids)
+    Descending,
+}
+
+/// Sorted price ladder for one side of the book
+///
+/// Price levels live in a `BTreeMap`, so walking the ladder in priority order
+/// never needs a sort. The best price is cached in an atomic and refreshed on
+/// every structural change, which makes `best_price` O(1) and lock-free.
+#[derive(Debug)]
+pub struct PriceLadder {
+    order: LadderOrder,
+    levels: RwLock<BTreeMap<Price, Arc<PriceLevel>>>,
+    best_price: AtomicU64,
+}
+
+impl PriceLadder {
+    pub fn new(order: LadderOrder) -> Self {
+        Self {
+            order,
+            levels: RwLock::new(BTreeMap::new()),
+            best_price: AtomicU64::new(NO_PRICE),
+        }
+    }
+
+    /// Ladder for resting orders on `side`: bids descending, asks ascending
+    pub fn for_side(side: Side) -> Self {
+        match side {
+            Side::Buy => Self::new(LadderOrder::Descending),
+            Side::Sell => Self::new(LadderOrder::Ascending),
+        }
+    }
+
+    /// Get the best price in O(1)
+    pub fn best_price(&self) -> Option<Price> {
+        match self.best_price.load(Ordering::Acquire) {
+            NO_PRICE => None,
+            price => Some(price),
+        }
+    }
+
+    /// Get the best price level
+    pub fn best_level(&self) -> Option<(Price, Arc<PriceLevel>)> {
+        self.next_level(None)
+    }
+
+    /// Get the first level ranked strictly after `after` (or the best level)
+    pub fn next_level(&self, after: Option<Price>) -> Option<(Price, Arc<PriceLevel>)> {
+        let levels = self.levels.read();
+        let entry = match (self.order, after) {
+            (LadderOrder::Ascending, None) => levels.iter().next(),
+            (LadderOrder::Descending, None) => levels.iter().next_back(),
+            (LadderOrder::Ascending, Some(price)) => levels
+                .range((Bound::Excluded(price), Bound::Unbounded))
+                .next(),
+            (LadderOrder::Descending, Some(price)) => levels.range(..price).next_back(),
+        };
+        entry.map(|(price, level)| (*price, Arc::clone(level)))
+    }
+
+    /// Check whether `price` ranks at or ahead of `limit`
+    pub fn is_within(&self, price: Price, limit: Price) -> bool {
+        match self.order {
+            LadderOrder::Ascending => price <= limit,
+            LadderOrder::Descending => price >= limit,
+        }
+    }
+
+    /// Get the level at `price`
+    pub fn get(&self, price: Price) -> Option<Arc<PriceLevel>> {
+        self.levels.read().get(&price).cloned()
+    }
+
+    /// Get the level at `price`, creating it if needed
+    pub fn get_or_insert(&self, price: Price) -> Arc<PriceLevel> {
+        if let Some(level) = self.get(price) {
+            return level;
+        }
+
+        let mut levels = self.levels.write();
+        let level = levels
+            .entry(price)
+            .or_insert_with(|| Arc::new(PriceLevel::new(price)))
+            .clone();
+        self.refresh_best(&levels);
+        level
+    }
+
+    /// Remove the level at `price`
+    pub fn remove(&self, price: Price) -> Option<Arc<PriceLevel>> {
+        let mut levels = self.levels.write();
+        let removed = levels.remove(&price);
+        if removed.is_some() {
+            self.refresh_best(&levels);
+        }
+        removed
+    }
+
+    /// Remove the level at `price` if it no longer holds any orders
+    pub fn remove_if_empty(&self, price: Price) -> bool {
+        let mut levels = self.levels.write();
+        let empty = levels.get(&price).is_some_and(|level| level.is_empty());
+        if empty {
+            levels.remove(&price);
+            self.refresh_best(&levels);
+        }
+        empty
+    }
+
+    /// Get all levels in priority order (best first)
+    pub fn levels(&self) -> Vec<(Price, Arc<PriceLevel>)> {
+        let levels = self.levels.read();
+        let entries = levels
+            .iter()
+            .map(|(price, level)| (*price, Arc::clone(level)));
+        match self.order {
+            LadderOrder::Ascending => entries.collect(),
+            LadderOrder::Descending => entries.rev().collect(),
+        }
+    }
+
+    /// Get levels in priority order up to and including `limit`
+    pub fn levels_through(&self, limit: Price) -> Vec<(Price, Arc<PriceLevel>)> {
+        let levels = self.levels.read();
+        match self.order {
+            LadderOrder::Ascending => levels
+                .range(..=limit)
+                .map(|(price, level)| (*price, Arc::clone(level)))
+                .collect(),
+            LadderOrder::Descending => levels
+                .range(limit..)
+                .rev()
+                .map(|(price, level)| (*price, Arc::clone(level)))
+                .collect(),
+        }
+    }
+
+    /// Get number of price levels
+    pub fn len(&self) -> usize {
+        self.levels.read().len()
+    }
+
+    /// Check if the ladder has no price levels
+    pub fn is_empty(&self) -> bool {
+        self.levels.read().is_empty()
+    }
+
+    fn refresh_best(&self, levels: &BTreeMap<Price, Arc<PriceLevel>>) {
+        let best = match self.order {
+            LadderOrder::Ascending => levels.keys().next(),
+            LadderOrder::Descending => levels.keys().next_back(),
+        };
+        self.best_price
+            .store(best.copied().unwrap_or(NO_PRICE), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::types::Order;
+
+    #[test]
+    fn test_best_price_tracking() {
+        let bids = PriceLadder::for_side(Side::Buy);
+        assert_eq!(bids.best_price(), None);
+
+        bids.get_or_insert(10000);
+        bids.get_or_insert(10100);
+        bids.get_or_insert(9900);
+        assert_eq!(bids.best_price(), Some(10100));
+
+        bids.remove(10100);
+        assert_eq!(bids.best_price(), Some(10000));
+
+        let asks = PriceLadder::for_side(Side::Sell);
+        asks.get_or_insert(10200);
+        asks.get_or_insert(10150);
+        assert_eq!(asks.best_price(), Some(10150));
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let bids = PriceLadder::for_side(Side::Buy);
+        for price in [10000, 10200, 10100] {
+            bids.get_or_insert(price);
+        }
+
+        let prices: Vec<Price> = bids.levels().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(prices, vec![10200, 10100, 10000]);
+
+        let through: Vec<Price> = bids
+            .levels_through(10100)
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(through, vec![10200, 10100]);
+
+        assert_eq!(bids.next_level(Some(10200)).map(|(p, _)| p), Some(10100));
+        assert_eq!(bids.next_level(Some(10000)).map(|(p, _)| p), None);
+    }
+
+    #[test]
+    fn test_remove_if_empty() {
+        let asks = PriceLadder::for_side(Side::Sell);
+        let level = asks.get_or_insert(10000);
+        level.add_order(Order::new_limit(
+            "TEST".to_string(),
+            Side::Sell,
+            10000,
+            100,
+            None,
+        ));
+
+        assert!(!asks.remove_if_empty(10000));
+        level.take_quantity(100);
+        assert!(asks.remove_if_empty(10000));
+        assert!(asks.is_empty());
+        assert_eq!(asks.best_price(), None);
+    }
+}
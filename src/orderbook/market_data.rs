@@ -0,0 +1,767 @@
+//! Incremental market data feeds
+//!
+//! Level 2: every change to the aggregated quantity at a price publishes a
+//! [`DepthUpdate`] carrying the level's new totals and a per-book sequence
+//! number. `OrderBook::snapshot()` is tagged with the sequence number of the
+//! last update it reflects, so a subscriber can subscribe, take a snapshot and
+//! continue from it with [`L2BookBuilder`] without missing or double-applying
+//! an update.
+//!
+//! Level 3 (market by order): every change to a resting order publishes an
+//! [`OrderMessage`] on a separately sequenced feed. `OrderBook::order_snapshot()`
+//! and [`L3BookBuilder`] play the same roles as their level-2 counterparts.
+//!
+//! Each subscriber gets a bounded queue. A subscriber that falls a full queue
+//! behind is disconnected rather than slowing the book down: it drains what
+//! was queued, sees the channel close, and must resubscribe and resnapshot.
+
+use crossbeam::channel::{self, Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use uuid::Uuid;
+
+use crate::orderbook::auction::Equilibrium;
+use crate::orderbook::error::OrderBookError;
+use crate::orderbook::snapshot::LevelSnapshot;
+use crate::orderbook::types::{
+    BookSnapshot, OrderId, Price, PriceLevelInfo, Quantity, Side, TradingPhase,
+};
+
+/// New aggregated state of one price level
+///
+/// `new_qty == 0` means the level was removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthUpdate {
+    pub side: Side,
+    pub price: Price,
+    pub new_qty: Quantity,
+    pub order_count: u32,
+    pub seq: u64,
+}
+
+/// Market-by-order message for one resting order
+///
+/// All messages refer to an order already announced by `Add`, except `Add`
+/// itself. Orders keep their queue position on `Reduce` and `Replace`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderMessage {
+    /// Order joined the back of the queue at `price`
+    Add {
+        seq: u64,
+        order_id: OrderId,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+    },
+    /// Order traded; it leaves the book when `remaining_qty` reaches zero
+    Execute {
+        seq: u64,
+        order_id: OrderId,
+        side: Side,
+        price: Price,
+        executed_qty: Quantity,
+        remaining_qty: Quantity,
+        trade_id: Uuid,
+    },
+    /// Order quantity was reduced by a modify
+    Reduce {
+        seq: u64,
+        order_id: OrderId,
+        side: Side,
+        price: Price,
+        reduced_by: Quantity,
+        remaining_qty: Quantity,
+    },
+    /// Order was cancelled
+    Delete {
+        seq: u64,
+        order_id: OrderId,
+        side: Side,
+        price: Price,
+    },
+    /// Order quantity was raised by a modify
+    Replace {
+        seq: u64,
+        order_id: OrderId,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+    },
+}
+
+impl OrderMessage {
+    /// Get the feed sequence number of this message
+    pub fn seq(&self) -> u64 {
+        match *self {
+            OrderMessage::Add { seq, .. }
+            | OrderMessage::Execute { seq, .. }
+            | OrderMessage::Reduce { seq, .. }
+            | OrderMessage::Delete { seq, .. }
+            | OrderMessage::Replace { seq, .. } => seq,
+        }
+    }
+}
+
+/// Trading phase of a book and, during a call, its indicative uncross
+///
+/// Published on every phase change and after every change to the book
+/// during a call. `equilibrium` is `None` outside a call or while the book
+/// does not cross.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuctionUpdate {
+    pub phase: TradingPhase,
+    pub equilibrium: Option<Equilibrium>,
+    pub seq: u64,
+}
+
+/// Order-level snapshot tagged with the L3 feed sequence it reflects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderSnapshot {
+    pub symbol: String,
+    pub sequence: u64,
+    /// Bid levels, best (highest) price first
+    pub bids: Vec<LevelSnapshot>,
+    /// Ask levels, best (lowest) price first
+    pub asks: Vec<LevelSnapshot>,
+}
+
+/// Default number of messages a subscriber may fall behind before it is
+/// disconnected
+pub const DEFAULT_FEED_CAPACITY: usize = 65_536;
+
+/// Publisher side of a sequenced market data feed
+///
+/// Messages are published under the book's matching lock, so sequence
+/// numbers follow the order in which the book changed. Publishing never
+/// blocks: a subscriber whose queue is full is dropped from the feed.
+#[derive(Debug)]
+pub struct SequencedFeed<T> {
+    seq: AtomicU64,
+    capacity: usize,
+    subscribers: Mutex<Vec<Sender<T>>>,
+}
+
+/// Level-2 depth feed
+pub type DepthFeed = SequencedFeed<DepthUpdate>;
+
+/// Level-3 (market by order) feed
+pub type OrderFeed = SequencedFeed<OrderMessage>;
+
+/// Trading phase and indicative auction feed
+pub type AuctionFeed = SequencedFeed<AuctionUpdate>;
+
+impl<T: Clone> SequencedFeed<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_FEED_CAPACITY)
+    }
+
+    /// Create a feed whose subscribers may each fall `capacity` messages
+    /// behind
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            seq: AtomicU64::new(0),
+            capacity: capacity.max(1),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Subscribe to every message published from now on
+    ///
+    /// The receiver disconnects once its queue overflows; everything queued
+    /// before that is still delivered, so the next message after the
+    /// disconnect is a sequence gap and the subscriber must resnapshot.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (sender, receiver) = channel::bounded(self.capacity);
+        self.subscribers.lock().push(sender);
+        receiver
+    }
+
+    /// Publish the message built by `message` for the next sequence number
+    pub fn publish(&self, message: impl FnOnce(u64) -> T) -> u64 {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let mut subscribers = self.subscribers.lock();
+        if !subscribers.is_empty() {
+            let message = message(seq);
+            // Drop subscribers whose receiver has gone away or fallen a full
+            // queue behind
+            subscribers.retain(|subscriber| match subscriber.try_send(message.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+            });
+        }
+
+        seq
+    }
+
+    /// Get the sequence number of the last published update (0 if none)
+    pub fn last_sequence(&self) -> u64 {
+        self.seq.load(Ordering::Relaxed)
+    }
+
+    /// Get number of live subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().len()
+    }
+}
+
+impl<T: Clone> Default for SequencedFeed<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What a builder should do with an incoming sequence number
+enum Admission {
+    /// No snapshot yet; hold the message until one arrives
+    Buffer,
+    /// Already reflected in the current state
+    Skip,
+    Apply,
+}
+
+/// Sequence continuity check shared by the client-side builders
+#[derive(Debug, Default)]
+struct SequenceTracker {
+    last_seq: Option<u64>,
+}
+
+impl SequenceTracker {
+    fn admit(&mut self, seq: u64) -> Result<Admission, OrderBookError> {
+        let last_seq = match self.last_seq {
+            Some(last_seq) => last_seq,
+            None => return Ok(Admission::Buffer),
+        };
+
+        if seq <= last_seq {
+            return Ok(Admission::Skip);
+        }
+        if seq != last_seq + 1 {
+            self.last_seq = None;
+            return Err(OrderBookError::SequenceGap {
+                expected: last_seq + 1,
+                received: seq,
+            });
+        }
+
+        self.last_seq = Some(seq);
+        Ok(Admission::Apply)
+    }
+}
+
+/// Reference client-side level-2 book
+///
+/// Updates received before the first snapshot are buffered and replayed on
+/// top of it. After that, every update must carry the next sequence number;
+/// a gap desynchronizes the builder until a fresh snapshot is applied.
+#[derive(Debug, Default)]
+pub struct L2BookBuilder {
+    bids: BTreeMap<Price, (Quantity, u32)>,
+    asks: BTreeMap<Price, (Quantity, u32)>,
+    tracker: SequenceTracker,
+    pending: Vec<DepthUpdate>,
+}
+
+impl L2BookBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reset the book to `snapshot` and replay any buffered newer updates
+    pub fn apply_snapshot(&mut self, snapshot: &BookSnapshot) -> Result<(), OrderBookError> {
+        let level = |info: &PriceLevelInfo| (info.price, (info.quantity, info.order_count));
+        self.bids = snapshot.bids.iter().map(level).collect();
+        self.asks = snapshot.asks.iter().map(level).collect();
+        self.tracker.last_seq = Some(snapshot.sequence);
+
+        for update in std::mem::take(&mut self.pending) {
+            self.apply(update)?;
+        }
+        Ok(())
+    }
+
+    /// Apply an update, checking sequence continuity
+    ///
+    /// Updates already covered by the snapshot are ignored.
+    pub fn apply(&mut self, update: DepthUpdate) -> Result<(), OrderBookError> {
+        match self.tracker.admit(update.seq)? {
+            Admission::Buffer => {
+                self.pending.push(update);
+                return Ok(());
+            }
+            Admission::Skip => return Ok(()),
+            Admission::Apply => {}
+        }
+
+        let levels = match update.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        if update.new_qty == 0 {
+            levels.remove(&update.price);
+        } else {
+            levels.insert(update.price, (update.new_qty, update.order_count));
+        }
+
+        Ok(())
+    }
+
+    /// Check whether the builder holds a snapshot with no gap since
+    pub fn is_synced(&self) -> bool {
+        self.tracker.last_seq.is_some()
+    }
+
+    /// Get the sequence number of the last applied update or snapshot
+    pub fn last_sequence(&self) -> Option<u64> {
+        self.tracker.last_seq
+    }
+
+    pub fn best_bid(&self) -> Option<Price> {
+        self.bids.keys().next_back().copied()
+    }
+
+    pub fn best_ask(&self) -> Option<Price> {
+        self.asks.keys().next().copied()
+    }
+
+    /// Get up to `depth` levels on `side`, best price first
+    pub fn levels(&self, side: Side, depth: usize) -> Vec<PriceLevelInfo> {
+        let info = |(price, (quantity, order_count)): (&Price, &(Quantity, u32))| PriceLevelInfo {
+            price: *price,
+            quantity: *quantity,
+            order_count: *order_count,
+        };
+        match side {
+            Side::Buy => self.bids.iter().rev().take(depth).map(info).collect(),
+            Side::Sell => self.asks.iter().take(depth).map(info).collect(),
+        }
+    }
+}
+
+/// Reference client-side market-by-order book
+///
+/// Follows the same snapshot, buffering and gap rules as [`L2BookBuilder`].
+#[derive(Debug, Default)]
+pub struct L3BookBuilder {
+    bids: BTreeMap<Price, VecDeque<(OrderId, Quantity)>>,
+    asks: BTreeMap<Price, VecDeque<(OrderId, Quantity)>>,
+    locations: HashMap<OrderId, (Side, Price)>,
+    tracker: SequenceTracker,
+    pending: Vec<OrderMessage>,
+}
+
+impl L3BookBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reset the book to `snapshot` and replay any buffered newer messages
+    pub fn apply_snapshot(&mut self, snapshot: &OrderSnapshot) -> Result<(), OrderBookError> {
+        self.bids.clear();
+        self.asks.clear();
+        self.locations.clear();
+
+        for (side, levels) in [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+            for level in levels {
+                for order in &level.orders {
+                    self.insert(side, level.price, order.id, order.remaining_quantity);
+                }
+            }
+        }
+        self.tracker.last_seq = Some(snapshot.sequence);
+
+        for message in std::mem::take(&mut self.pending) {
+            self.apply(message)?;
+        }
+        Ok(())
+    }
+
+    /// Apply a message, checking sequence continuity
+    pub fn apply(&mut self, message: OrderMessage) -> Result<(), OrderBookError> {
+        match self.tracker.admit(message.seq())? {
+            Admission::Buffer => {
+                self.pending.push(message);
+                return Ok(());
+            }
+            Admission::Skip => return Ok(()),
+            Admission::Apply => {}
+        }
+
+        match message {
+            OrderMessage::Add {
+                order_id,
+                side,
+                price,
+                quantity,
+                ..
+            } => self.insert(side, price, order_id, quantity),
+            OrderMessage::Execute {
+                order_id,
+                remaining_qty,
+                ..
+            }
+            | OrderMessage::Reduce {
+                order_id,
+                remaining_qty,
+                ..
+            }
+            | OrderMessage::Replace {
+                order_id,
+                quantity: remaining_qty,
+                ..
+            } => self.update(&order_id, remaining_qty)?,
+            OrderMessage::Delete { order_id, .. } => self.update(&order_id, 0)?,
+        }
+
+        Ok(())
+    }
+
+    /// Check whether the builder holds a snapshot with no gap since
+    pub fn is_synced(&self) -> bool {
+        self.tracker.last_seq.is_some()
+    }
+
+    /// Get the sequence number of the last applied message or snapshot
+    pub fn last_sequence(&self) -> Option<u64> {
+        self.tracker.last_seq
+    }
+
+    /// Get the orders queued at `price` on `side`, front of the queue first
+    pub fn orders_at(&self, side: Side, price: Price) -> Vec<(OrderId, Quantity)> {
+        self.side_levels(side)
+            .get(&price)
+            .map(|queue| queue.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get aggregated levels on `side`, best price first
+    pub fn levels(&self, side: Side) -> Vec<PriceLevelInfo> {
+        let info = |(price, queue): (&Price, &VecDeque<(OrderId, Quantity)>)| PriceLevelInfo {
+            price: *price,
+            quantity: queue.iter().map(|(_, quantity)| quantity).sum(),
+            order_count: queue.len() as u32,
+        };
+        match side {
+            Side::Buy => self.bids.iter().rev().map(info).collect(),
+            Side::Sell => self.asks.iter().map(info).collect(),
+        }
+    }
+
+    /// Get number of orders in the book
+    pub fn order_count(&self) -> usize {
+        self.locations.len()
+    }
+
+    fn side_levels(&self, side: Side) -> &BTreeMap<Price, VecDeque<(OrderId, Quantity)>> {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    fn insert(&mut self, side: Side, price: Price, order_id: OrderId, quantity: Quantity) {
+        let levels = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        levels
+            .entry(price)
+            .or_default()
+            .push_back((order_id, quantity));
+        self.locations.insert(order_id, (side, price));
+    }
+
+    /// Set an order's remaining quantity, removing it at zero
+    fn update(&mut self, order_id: &OrderId, remaining: Quantity) -> Result<(), OrderBookError> {
+        let (side, price) = *self
+            .locations
+            .get(order_id)
+            .ok_or(OrderBookError::OrderNotFound)?;
+        let levels = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        let queue = levels
+            .get_mut(&price)
+            .ok_or(OrderBookError::OrderNotFound)?;
+        let position = queue
+            .iter()
+            .position(|(id, _)| id == order_id)
+            .ok_or(OrderBookError::OrderNotFound)?;
+
+        if remaining == 0 {
+            queue.remove(position);
+            if queue.is_empty() {
+                levels.remove(&price);
+            }
+            self.locations.remove(order_id);
+        } else {
+            queue[position].1 = remaining;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::types::Order;
+    use crate::orderbook::OrderBook;
+    use rand::{Rng, SeedableRng};
+
+    fn limit(side: Side, price: Price, quantity: Quantity) -> Order {
+        Order::new_limit("TEST".to_string(), side, price, quantity, None)
+    }
+
+    fn assert_matches_book(builder: &L2BookBuilder, book: &OrderBook) {
+        let snapshot = book.snapshot();
+        let json = |levels: &Vec<PriceLevelInfo>| serde_json::to_string(levels).unwrap();
+        assert_eq!(
+            json(&builder.levels(Side::Buy, usize::MAX)),
+            json(&snapshot.bids)
+        );
+        assert_eq!(
+            json(&builder.levels(Side::Sell, usize::MAX)),
+            json(&snapshot.asks)
+        );
+        assert_eq!(builder.last_sequence(), Some(snapshot.sequence));
+    }
+
+    #[test]
+    fn test_builder_tracks_book_from_mid_stream_snapshot() {
+        let book = OrderBook::new("TEST".to_string());
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut resting = Vec::new();
+
+        let mut trade = |book: &OrderBook, rng: &mut rand::rngs::StdRng| {
+            if !resting.is_empty() && rng.gen_bool(0.2) {
+                let id = resting.swap_remove(rng.gen_range(0..resting.len()));
+                let _ = book.cancel_order(&id);
+            } else {
+                let side = if rng.gen_bool(0.5) {
+                    Side::Buy
+                } else {
+                    Side::Sell
+                };
+                let order = limit(side, rng.gen_range(9_990..10_010), rng.gen_range(1..50));
+                resting.push(order.id);
+                book.add_limit_order(order).unwrap();
+            }
+        };
+
+        for _ in 0..200 {
+            trade(&book, &mut rng);
+        }
+
+        // Subscribe, let updates queue up, then snapshot mid-stream
+        let updates = book.subscribe_depth();
+        let mut builder = L2BookBuilder::new();
+        for _ in 0..100 {
+            trade(&book, &mut rng);
+        }
+        for update in updates.try_iter() {
+            builder.apply(update).unwrap();
+        }
+        assert!(!builder.is_synced());
+
+        builder.apply_snapshot(&book.snapshot()).unwrap();
+        for _ in 0..500 {
+            trade(&book, &mut rng);
+        }
+        for update in updates.try_iter() {
+            builder.apply(update).unwrap();
+        }
+
+        assert_matches_book(&builder, &book);
+        assert_eq!(builder.best_bid(), book.best_bid());
+        assert_eq!(builder.best_ask(), book.best_ask());
+    }
+
+    #[test]
+    fn test_sequence_gap_is_detected() {
+        let book = OrderBook::new("TEST".to_string());
+        let updates = book.subscribe_depth();
+        let mut builder = L2BookBuilder::new();
+        builder.apply_snapshot(&book.snapshot()).unwrap();
+
+        book.add_limit_order(limit(Side::Buy, 10_000, 10)).unwrap();
+        book.add_limit_order(limit(Side::Buy, 9_990, 10)).unwrap();
+        book.add_limit_order(limit(Side::Sell, 10_010, 10)).unwrap();
+
+        let received: Vec<DepthUpdate> = updates.try_iter().collect();
+        assert_eq!(received.len(), 3);
+        builder.apply(received[0]).unwrap();
+
+        // Update 2 is lost
+        assert_eq!(
+            builder.apply(received[2]),
+            Err(OrderBookError::SequenceGap {
+                expected: 2,
+                received: 3
+            })
+        );
+        assert!(!builder.is_synced());
+
+        // A fresh snapshot resynchronizes
+        builder.apply_snapshot(&book.snapshot()).unwrap();
+        assert_matches_book(&builder, &book);
+    }
+
+    #[test]
+    fn test_slow_subscriber_is_disconnected() {
+        let feed = SequencedFeed::<u64>::with_capacity(2);
+        let fast = feed.subscribe();
+        let slow = feed.subscribe();
+        let abandoned = feed.subscribe();
+        drop(abandoned);
+
+        for _ in 0..3 {
+            feed.publish(|seq| seq);
+            assert_eq!(fast.try_recv().ok(), Some(feed.last_sequence()));
+        }
+
+        // The slow subscriber keeps what was queued, then sees the feed close
+        assert_eq!(feed.subscriber_count(), 1);
+        assert_eq!(slow.try_iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert!(slow.recv().is_err());
+    }
+
+    #[test]
+    fn test_trade_publishes_level_updates() {
+        let book = OrderBook::new("TEST".to_string());
+        book.add_limit_order(limit(Side::Sell, 10_000, 30)).unwrap();
+        book.add_limit_order(limit(Side::Sell, 10_000, 20)).unwrap();
+
+        let updates = book.subscribe_depth();
+        book.add_limit_order(limit(Side::Buy, 10_000, 40)).unwrap();
+        book.add_limit_order(limit(Side::Buy, 10_000, 15)).unwrap();
+
+        let received: Vec<(Side, Quantity, u32)> = updates
+            .try_iter()
+            .map(|u| (u.side, u.new_qty, u.order_count))
+            .collect();
+        assert_eq!(
+            received,
+            vec![(Side::Sell, 10, 1), (Side::Sell, 0, 0), (Side::Buy, 5, 1)]
+        );
+    }
+
+    fn assert_l3_matches_book(builder: &L3BookBuilder, book: &OrderBook) {
+        let snapshot = book.order_snapshot();
+        for (side, levels) in [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+            assert_eq!(builder.levels(side).len(), levels.len());
+            for level in levels {
+                let expected: Vec<(OrderId, Quantity)> = level
+                    .orders
+                    .iter()
+                    .map(|order| (order.id, order.remaining_quantity))
+                    .collect();
+                assert_eq!(builder.orders_at(side, level.price), expected);
+            }
+        }
+        assert_eq!(builder.order_count(), book.total_orders());
+        assert_eq!(builder.last_sequence(), Some(snapshot.sequence));
+    }
+
+    #[test]
+    fn test_l3_builder_rebuilds_order_book() {
+        let book = OrderBook::new("TEST".to_string());
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let mut resting = Vec::new();
+
+        let mut trade = |book: &OrderBook, rng: &mut rand::rngs::StdRng| {
+            let roll = rng.gen_range(0..10);
+            if !resting.is_empty() && roll < 2 {
+                let id = resting.swap_remove(rng.gen_range(0..resting.len()));
+                let _ = book.cancel_order(&id);
+            } else if !resting.is_empty() && roll < 4 {
+                let id = resting[rng.gen_range(0..resting.len())];
+                let _ = book.modify_order_quantity(&id, rng.gen_range(1..80));
+            } else {
+                let side = if rng.gen_bool(0.5) {
+                    Side::Buy
+                } else {
+                    Side::Sell
+                };
+                let order = limit(side, rng.gen_range(9_990..10_010), rng.gen_range(1..50));
+                resting.push(order.id);
+                book.add_limit_order(order).unwrap();
+            }
+        };
+
+        for _ in 0..200 {
+            trade(&book, &mut rng);
+        }
+
+        let messages = book.subscribe_orders();
+        let mut builder = L3BookBuilder::new();
+        for _ in 0..100 {
+            trade(&book, &mut rng);
+        }
+        for message in messages.try_iter() {
+            builder.apply(message).unwrap();
+        }
+
+        builder.apply_snapshot(&book.order_snapshot()).unwrap();
+        for _ in 0..500 {
+            trade(&book, &mut rng);
+        }
+        for message in messages.try_iter() {
+            builder.apply(message).unwrap();
+        }
+
+        assert_l3_matches_book(&builder, &book);
+    }
+
+    #[test]
+    fn test_l3_messages() {
+        let book = OrderBook::new("TEST".to_string());
+        let resting = limit(Side::Sell, 10_000, 50);
+        let resting_id = resting.id;
+        book.add_limit_order(resting).unwrap();
+
+        let messages = book.subscribe_orders();
+        book.add_limit_order(limit(Side::Buy, 10_000, 20)).unwrap();
+        book.modify_order_quantity(&resting_id, 10).unwrap();
+        book.modify_order_quantity(&resting_id, 40).unwrap();
+        book.cancel_order(&resting_id).unwrap();
+
+        let received: Vec<OrderMessage> = messages.try_iter().collect();
+        assert_eq!(received.len(), 4);
+        assert!(matches!(
+            received[0],
+            OrderMessage::Execute {
+                seq: 2,
+                side: Side::Sell,
+                price: 10_000,
+                executed_qty: 20,
+                remaining_qty: 30,
+                ..
+            }
+        ));
+        assert!(matches!(
+            received[1],
+            OrderMessage::Reduce {
+                reduced_by: 20,
+                remaining_qty: 10,
+                ..
+            }
+        ));
+        assert!(matches!(
+            received[2],
+            OrderMessage::Replace { quantity: 40, .. }
+        ));
+        assert!(matches!(
+            received[3],
+            OrderMessage::Delete {
+                seq: 5,
+                side: Side::Sell,
+                price: 10_000,
+                ..
+            }
+        ));
+    }
+}
@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use tracing::{debug, warn};
+use tracing::debug;
 
 use crate::orderbook::error::OrderBookError;
 use crate::orderbook::price_level::PriceLevel;
@@ -15,12 +15,16 @@ impl MatchingEngine {
         opposite_levels: &[(Price, Arc<PriceLevel>)],
     ) -> Result<Vec<Trade>, OrderBookError> {
         match order.order_type {
-            OrderType::Market => Self::match_market_order(order, opposite_levels),
+            OrderType::Market | OrderType::MarketToLimit => {
+                Self::match_market_order(order, opposite_levels)
+            }
             OrderType::Limit => Self::match_limit_order(order, opposite_levels),
             OrderType::ImmediateOrCancel => Self::match_ioc_order(order, opposite_levels),
             OrderType::FillOrKill => Self::match_fok_order(order, opposite_levels),
-            OrderType::Stop => Err(OrderBookError::InvalidOrderType), // Stop orders need special handling
-            OrderType::StopLimit { .. } => Err(OrderBookError::InvalidOrderType), // Stop-limit orders need special handling
+            // Stops are held by the order book until triggered, then execute
+            // like the order type they release
+            OrderType::Stop => Self::match_market_order(order, opposite_levels),
+            OrderType::StopLimit { .. } => Self::match_limit_order(order, opposite_levels),
         }
     }
 
@@ -49,7 +53,7 @@ impl MatchingEngine {
             let match_quantity = order.remaining_quantity.min(available_quantity);
             let fills = level.take_quantity(match_quantity);
 
-            for (mut matched_order, fill_quantity) in fills {
+            for (matched_order, fill_quantity) in fills {
                 let (buyer_id, seller_id) = match order.side {
                     Side::Buy => (order.id, matched_order.id),
                     Side::Sell => (matched_order.id, order.id),
@@ -64,7 +68,9 @@ impl MatchingEngine {
                 );
 
                 // Update order quantities
-                order.fill(fill_quantity);
+                order
+                    .fill(fill_quantity)
+                    .map_err(|_| OrderBookError::OverFill)?;
 
                 trades.push(trade);
 
@@ -117,7 +123,7 @@ impl MatchingEngine {
             let match_quantity = order.remaining_quantity.min(available_quantity);
             let fills = level.take_quantity(match_quantity);
 
-            for (mut matched_order, fill_quantity) in fills {
+            for (matched_order, fill_quantity) in fills {
                 let (buyer_id, seller_id) = match order.side {
                     Side::Buy => (order.id, matched_order.id),
                     Side::Sell => (matched_order.id, order.id),
@@ -132,7 +138,9 @@ impl MatchingEngine {
                 );
 
                 // Update order quantities
-                order.fill(fill_quantity);
+                order
+                    .fill(fill_quantity)
+                    .map_err(|_| OrderBookError::OverFill)?;
 
                 trades.push(trade);
 
@@ -212,16 +220,18 @@ impl MatchingEngine {
     }
 
     /// Check if two orders would result in a self-trade
+    ///
+    /// Orders are compared by STP group, falling back to client id; orders
+    /// with neither never self-trade.
     pub fn is_self_trade(order1: &Order, order2: &Order) -> bool {
-        if let (Some(client1), Some(client2)) = (&order1.client_id, &order2.client_id) {
-            client1 == client2
-        } else {
-            false
+        match (order1.stp_key(), order2.stp_key()) {
+            (Some(key1), Some(key2)) => key1 == key2,
+            _ => false,
         }
     }
 
     /// Calculate the fair value price for a trade between two orders
-    pub fn calculate_trade_price(aggressive_order: &Order, passive_order: &Order) -> Price {
+    pub fn calculate_trade_price(_aggressive_order: &Order, passive_order: &Order) -> Price {
         // In most markets, trades execute at the price of the passive (resting) order
         // This gives price priority to orders that were placed first
         passive_order.price
@@ -238,20 +248,19 @@ impl MatchingEngine {
         }
 
         match order.order_type {
-            OrderType::Market => {
-                // Market orders don't need price validation
+            OrderType::Market | OrderType::MarketToLimit | OrderType::Stop => {
+                // Market and triggered stop orders don't need price validation
                 Ok(())
             }
-            OrderType::Limit | OrderType::ImmediateOrCancel | OrderType::FillOrKill => {
+            OrderType::Limit
+            | OrderType::ImmediateOrCancel
+            | OrderType::FillOrKill
+            | OrderType::StopLimit { .. } => {
                 if order.price == 0 {
                     return Err(OrderBookError::InvalidPrice);
                 }
                 Ok(())
             }
-            OrderType::Stop | OrderType::StopLimit { .. } => {
-                // Stop orders need special validation
-                Err(OrderBookError::InvalidOrderType)
-            }
         }
     }
 
@@ -316,7 +325,7 @@ impl TradeContext {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::orderbook::types::{OrderStatus, OrderType};
+    use crate::orderbook::types::{OrderStatus, OrderType, TimeInForce};
     use chrono::Utc;
     use uuid::Uuid;
 
@@ -338,6 +347,12 @@ mod tests {
             status: OrderStatus::New,
             timestamp: Utc::now(),
             client_id: None,
+            stp_group: None,
+            stp_mode: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0,
+            post_only: None,
         }
     }
 
@@ -351,6 +366,14 @@ mod tests {
 
         let invalid_order = create_test_order(Side::Buy, 0, 100, OrderType::Limit);
         assert!(MatchingEngine::validate_order_for_matching(&invalid_order).is_err());
+
+        let stop_limit = create_test_order(
+            Side::Buy,
+            10100,
+            100,
+            OrderType::StopLimit { stop_price: 10050 },
+        );
+        assert!(MatchingEngine::validate_order_for_matching(&stop_limit).is_ok());
     }
 
     #[test]
@@ -393,6 +416,12 @@ mod tests {
             status: OrderStatus::New,
             timestamp: Utc::now(),
             client_id: Some("client1".to_string()),
+            stp_group: None,
+            stp_mode: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0,
+            post_only: None,
         };
 
         let order2 = Order {
@@ -407,6 +436,12 @@ mod tests {
             status: OrderStatus::New,
             timestamp: Utc::now(),
             client_id: Some("client1".to_string()),
+            stp_group: None,
+            stp_mode: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0,
+            post_only: None,
         };
 
         let order3 = Order {
@@ -421,6 +456,12 @@ mod tests {
             status: OrderStatus::New,
             timestamp: Utc::now(),
             client_id: Some("client2".to_string()),
+            stp_group: None,
+            stp_mode: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0,
+            post_only: None,
         };
 
         assert!(MatchingEngine::is_self_trade(&order1, &order2));
@@ -3,20 +3,46 @@
 //! This module contains the main order book data structures and algorithms
 //! for high-performance electronic trading systems.
 
+pub mod auction;
 pub mod book;
+pub mod circuit_breaker;
 pub mod error;
+pub mod exchange;
+pub mod instrument;
+pub mod journal;
+pub mod ladder;
+pub mod market_data;
 pub mod matching;
 pub mod operations;
 pub mod price_level;
+pub mod replay;
+pub mod risk;
+pub mod snapshot;
+pub mod stop_book;
 pub mod types;
 
 // Re-export main types for convenience
+pub use auction::Equilibrium;
 pub use book::{OrderBook, OrderBookStats};
+pub use circuit_breaker::{BandWidth, BreachAction, CircuitBreaker};
 pub use error::{OrderBookError, OrderBookResult};
+pub use exchange::{Exchange, InstrumentConfig, PriceBand};
+pub use instrument::{Instrument, MarketProtection, TickBand, TickSizeTable};
+pub use journal::{FsyncPolicy, Journal, JournalCommand, JournalRecord};
+pub use ladder::{LadderOrder, PriceLadder};
+pub use market_data::{
+    AuctionFeed, AuctionUpdate, DepthFeed, DepthUpdate, L2BookBuilder, L3BookBuilder, OrderFeed,
+    OrderMessage, OrderSnapshot,
+};
 pub use price_level::PriceLevel;
+pub use replay::{ReplayCommand, Replayer};
+pub use risk::{AccountRisk, Exposure, MarketContext, RiskCheck, RiskLimits, RiskRejection};
+pub use snapshot::FullSnapshot;
+pub use stop_book::StopBook;
 pub use types::{
-    BookSnapshot, MarketEvent, Order, OrderId, OrderLocation, OrderStatus, OrderType, Price,
-    PriceLevelInfo, Quantity, Side, Trade,
+    BookSnapshot, HaltReason, MarketEvent, Order, OrderId, OrderLocation, OrderStatus, OrderType,
+    PostOnly, Price, PriceLevelInfo, Quantity, SelfTradePrevention, Side, TimeInForce, Trade,
+    TradingPhase, TradingStatus,
 };
 
 #[cfg(test)]
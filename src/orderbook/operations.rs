@@ -1,9 +1,7 @@
-use std::sync::Arc;
 use tracing::{debug, info, warn};
-use uuid::Uuid;
 
 use crate::orderbook::error::{OrderBookError, OrderBookResult};
-use crate::orderbook::price_level::PriceLevel;
+use crate::orderbook::ladder::PriceLadder;
 use crate::orderbook::types::{
     MarketEvent, Order, OrderId, OrderStatus, OrderType, Price, Quantity, Side,
 };
@@ -15,8 +13,8 @@ impl OrderOperations {
     /// Add a new order to the book
     pub fn add_order(
         order: Order,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
         order_locations: &dashmap::DashMap<OrderId, crate::orderbook::types::OrderLocation>,
     ) -> OrderBookResult<Vec<MarketEvent>> {
         debug!("Adding order: {:?}", order);
@@ -24,6 +22,11 @@ impl OrderOperations {
         // Validate order
         Self::validate_order(&order)?;
 
+        // Untriggered stops need a stop book to wait in (see `OrderBook::add_stop_order`)
+        if order.stop_price().is_some() {
+            return Err(OrderBookError::InvalidOrderType);
+        }
+
         let mut events = Vec::new();
         let mut order = order;
 
@@ -47,8 +50,8 @@ impl OrderOperations {
     /// Cancel an existing order
     pub fn cancel_order(
         order_id: &OrderId,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
         order_locations: &dashmap::DashMap<OrderId, crate::orderbook::types::OrderLocation>,
     ) -> OrderBookResult<MarketEvent> {
         debug!("Cancelling order: {}", order_id);
@@ -66,15 +69,13 @@ impl OrderOperations {
         };
 
         // Remove order from price level
-        if let Some(level) = price_levels.get(&location.price) {
+        if let Some(level) = price_levels.get(location.price) {
             if let Some(mut order) = level.remove_order(order_id) {
                 let remaining_quantity = order.remaining_quantity;
                 order.cancel();
 
                 // Clean up empty price level
-                if level.is_empty() {
-                    price_levels.remove(&location.price);
-                }
+                price_levels.remove_if_empty(location.price);
 
                 info!(
                     "Order {} cancelled, {} shares remaining",
@@ -95,8 +96,8 @@ impl OrderOperations {
         order_id: &OrderId,
         new_price: Option<Price>,
         new_quantity: Option<Quantity>,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
         order_locations: &dashmap::DashMap<OrderId, crate::orderbook::types::OrderLocation>,
     ) -> OrderBookResult<Vec<MarketEvent>> {
         debug!(
@@ -134,8 +135,8 @@ impl OrderOperations {
     pub fn replace_order(
         old_order_id: &OrderId,
         new_order: Order,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
         order_locations: &dashmap::DashMap<OrderId, crate::orderbook::types::OrderLocation>,
     ) -> OrderBookResult<Vec<MarketEvent>> {
         debug!(
@@ -163,7 +164,7 @@ impl OrderOperations {
 
     // Private helper methods
 
-    fn validate_order(order: &Order) -> OrderBookResult<()> {
+    pub(crate) fn validate_order(order: &Order) -> OrderBookResult<()> {
         // Check quantity
         if order.original_quantity == 0 || order.remaining_quantity == 0 {
             return Err(OrderBookError::InvalidQuantity);
@@ -176,11 +177,19 @@ impl OrderOperations {
                     return Err(OrderBookError::InvalidPrice);
                 }
             }
-            OrderType::Market => {
+            OrderType::Market | OrderType::MarketToLimit => {
                 // Market orders don't need price validation
             }
-            OrderType::Stop | OrderType::StopLimit { .. } => {
-                return Err(OrderBookError::InvalidOrderType);
+            OrderType::Stop => {
+                // Stop price is carried in `price`
+                if order.price == 0 {
+                    return Err(OrderBookError::InvalidPrice);
+                }
+            }
+            OrderType::StopLimit { stop_price } => {
+                if stop_price == 0 || order.price == 0 {
+                    return Err(OrderBookError::InvalidPrice);
+                }
             }
         }
 
@@ -194,29 +203,21 @@ impl OrderOperations {
 
     fn match_order(
         order: &mut Order,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
     ) -> OrderBookResult<Vec<crate::orderbook::types::Trade>> {
         use crate::orderbook::matching::MatchingEngine;
 
-        // Get opposite side levels
-        let opposite_levels = match order.side {
-            Side::Buy => {
-                let mut levels: Vec<_> = asks
-                    .iter()
-                    .map(|entry| (*entry.key(), Arc::clone(entry.value())))
-                    .collect();
-                levels.sort_by_key(|(price, _)| *price); // Ascending for asks
-                levels
-            }
-            Side::Sell => {
-                let mut levels: Vec<_> = bids
-                    .iter()
-                    .map(|entry| (*entry.key(), Arc::clone(entry.value())))
-                    .collect();
-                levels.sort_by_key(|(price, _)| std::cmp::Reverse(*price)); // Descending for bids
-                levels
+        // Get opposite side levels, already in priority order
+        let opposite_ladder = match order.side {
+            Side::Buy => asks,
+            Side::Sell => bids,
+        };
+        let opposite_levels = match order.order_type {
+            OrderType::Market | OrderType::MarketToLimit | OrderType::Stop => {
+                opposite_ladder.levels()
             }
+            _ => opposite_ladder.levels_through(order.price),
         };
 
         // Validate order for matching
@@ -228,14 +229,7 @@ impl OrderOperations {
         // Clean up empty levels
         for (price, level) in opposite_levels {
             if MatchingEngine::should_cleanup_level(&level) {
-                match order.side {
-                    Side::Buy => {
-                        asks.remove(&price);
-                    }
-                    Side::Sell => {
-                        bids.remove(&price);
-                    }
-                }
+                opposite_ladder.remove_if_empty(price);
             }
         }
 
@@ -244,8 +238,8 @@ impl OrderOperations {
 
     fn add_order_to_book(
         order: Order,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
         order_locations: &dashmap::DashMap<OrderId, crate::orderbook::types::OrderLocation>,
     ) -> OrderBookResult<()> {
         let price = order.price;
@@ -259,10 +253,7 @@ impl OrderOperations {
         };
 
         // Get or create price level
-        let level = price_levels
-            .entry(price)
-            .or_insert_with(|| Arc::new(PriceLevel::new(price)))
-            .clone();
+        let level = price_levels.get_or_insert(price);
 
         // Add order to price level
         level.add_order(order);
@@ -284,8 +275,8 @@ impl OrderOperations {
         order_id: &OrderId,
         new_price: Price,
         new_quantity: Option<Quantity>,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
         order_locations: &dashmap::DashMap<OrderId, crate::orderbook::types::OrderLocation>,
     ) -> OrderBookResult<Vec<MarketEvent>> {
         debug!(
@@ -305,13 +296,12 @@ impl OrderOperations {
         };
 
         // Remove order from current location
-        let mut order = if let Some(level) = price_levels.get(&location.price) {
-            level
-                .remove_order(order_id)
-                .ok_or(OrderBookError::OrderNotFound)?
-        } else {
-            return Err(OrderBookError::OrderNotFound);
-        };
+        let level = price_levels
+            .get(location.price)
+            .ok_or(OrderBookError::OrderNotFound)?;
+        let mut order = level
+            .remove_order(order_id)
+            .ok_or(OrderBookError::OrderNotFound)?;
 
         // Update order properties
         order.price = new_price;
@@ -328,11 +318,7 @@ impl OrderOperations {
         }
 
         // Clean up old price level if empty
-        if let Some(old_level) = price_levels.get(&location.price) {
-            if old_level.is_empty() {
-                price_levels.remove(&location.price);
-            }
-        }
+        price_levels.remove_if_empty(location.price);
 
         // Remove old location
         order_locations.remove(order_id);
@@ -344,8 +330,8 @@ impl OrderOperations {
     fn modify_order_quantity_only(
         order_id: &OrderId,
         new_quantity: Quantity,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
         order_locations: &dashmap::DashMap<OrderId, crate::orderbook::types::OrderLocation>,
     ) -> OrderBookResult<Vec<MarketEvent>> {
         debug!("Modifying order {} quantity to {}", order_id, new_quantity);
@@ -364,7 +350,7 @@ impl OrderOperations {
             Side::Sell => asks,
         };
 
-        if let Some(level) = price_levels.get(&location.price) {
+        if let Some(level) = price_levels.get(location.price) {
             if level
                 .modify_order_quantity(order_id, new_quantity)
                 .is_some()
@@ -388,8 +374,8 @@ impl BatchOperations {
     /// Process multiple orders in a batch
     pub fn process_batch(
         orders: Vec<Order>,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
         order_locations: &dashmap::DashMap<OrderId, crate::orderbook::types::OrderLocation>,
     ) -> Vec<OrderBookResult<Vec<MarketEvent>>> {
         orders
@@ -401,8 +387,8 @@ impl BatchOperations {
     /// Cancel multiple orders in a batch
     pub fn cancel_batch(
         order_ids: Vec<OrderId>,
-        bids: &dashmap::DashMap<Price, Arc<PriceLevel>>,
-        asks: &dashmap::DashMap<Price, Arc<PriceLevel>>,
+        bids: &PriceLadder,
+        asks: &PriceLadder,
         order_locations: &dashmap::DashMap<OrderId, crate::orderbook::types::OrderLocation>,
     ) -> Vec<OrderBookResult<MarketEvent>> {
         order_ids
@@ -415,10 +401,9 @@ impl BatchOperations {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::orderbook::types::{OrderStatus, OrderType, Side};
+    use crate::orderbook::types::{OrderStatus, OrderType, Side, TimeInForce};
     use chrono::Utc;
     use dashmap::DashMap;
-    use std::sync::Arc;
     use uuid::Uuid;
 
     fn create_test_order(side: Side, price: Price, quantity: Quantity) -> Order {
@@ -434,14 +419,20 @@ mod tests {
             status: OrderStatus::New,
             timestamp: Utc::now(),
             client_id: None,
+            stp_group: None,
+            stp_mode: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0,
+            post_only: None,
         }
     }
 
     #[test]
     fn test_add_order() {
-        let bids = Arc::new(DashMap::new());
-        let asks = Arc::new(DashMap::new());
-        let locations = Arc::new(DashMap::new());
+        let bids = PriceLadder::for_side(Side::Buy);
+        let asks = PriceLadder::for_side(Side::Sell);
+        let locations = DashMap::new();
 
         let order = create_test_order(Side::Buy, 10000, 100);
         let events = OrderOperations::add_order(order, &bids, &asks, &locations).unwrap();
@@ -453,9 +444,9 @@ mod tests {
 
     #[test]
     fn test_cancel_order() {
-        let bids = Arc::new(DashMap::new());
-        let asks = Arc::new(DashMap::new());
-        let locations = Arc::new(DashMap::new());
+        let bids = PriceLadder::for_side(Side::Buy);
+        let asks = PriceLadder::for_side(Side::Sell);
+        let locations = DashMap::new();
 
         let order = create_test_order(Side::Buy, 10000, 100);
         let order_id = order.id;
@@ -482,9 +473,9 @@ mod tests {
 
     #[test]
     fn test_modify_order_quantity() {
-        let bids = Arc::new(DashMap::new());
-        let asks = Arc::new(DashMap::new());
-        let locations = Arc::new(DashMap::new());
+        let bids = PriceLadder::for_side(Side::Buy);
+        let asks = PriceLadder::for_side(Side::Sell);
+        let locations = DashMap::new();
 
         let order = create_test_order(Side::Buy, 10000, 100);
         let order_id = order.id;
@@ -5,6 +5,10 @@ use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Represents a price level in the order book
 /// All orders at this price level maintain time priority (FIFO)
+///
+/// Quantities are visible quantities: an iceberg order counts only its
+/// current display slice, and moves to the back of the queue each time the
+/// slice is refreshed from its reserve.
 #[derive(Debug)]
 pub struct PriceLevel {
     pub price: Price,
@@ -25,7 +29,7 @@ impl PriceLevel {
 
     /// Add an order to this price level (maintains time priority)
     pub fn add_order(&self, order: Order) {
-        let quantity = order.remaining_quantity;
+        let quantity = order.visible_quantity();
 
         {
             let mut orders = self.orders.write();
@@ -43,7 +47,7 @@ impl PriceLevel {
         if let Some(pos) = orders.iter().position(|o| &o.id == order_id) {
             if let Some(order) = orders.remove(pos) {
                 self.total_quantity
-                    .fetch_sub(order.remaining_quantity, Ordering::Relaxed);
+                    .fetch_sub(order.visible_quantity(), Ordering::Relaxed);
                 self.order_count.fetch_sub(1, Ordering::Relaxed);
                 return Some(order);
             }
@@ -52,6 +56,12 @@ impl PriceLevel {
         None
     }
 
+    /// Get an order at this price level by ID
+    pub fn get_order(&self, order_id: &OrderId) -> Option<Order> {
+        let orders = self.orders.read();
+        orders.iter().find(|o| &o.id == order_id).cloned()
+    }
+
     /// Get the first order in the queue (for matching)
     pub fn peek_front(&self) -> Option<Order> {
         let orders = self.orders.read();
@@ -60,34 +70,44 @@ impl PriceLevel {
 
     /// Take quantity from the front of the queue (for market orders)
     /// Returns Vec of (order, filled_quantity) pairs
+    ///
+    /// Each order is reported as it was right after its fill, so an iceberg
+    /// whose slice was used up shows a visible quantity of zero even though
+    /// it has since been refreshed and requeued.
     pub fn take_quantity(&self, mut requested_quantity: Quantity) -> Vec<(Order, Quantity)> {
         let mut filled_orders = Vec::new();
         let mut orders = self.orders.write();
 
-        while requested_quantity > 0 && !orders.is_empty() {
-            if let Some(mut order) = orders.front_mut() {
-                let available = order.remaining_quantity;
-                let fill_quantity = requested_quantity.min(available);
+        while requested_quantity > 0 {
+            let order = match orders.front_mut() {
+                Some(order) => order,
+                None => break,
+            };
+            let available = order.visible_quantity();
+            let fill_quantity = requested_quantity.min(available);
 
-                // Fill the order
-                order.fill(fill_quantity).expect("Fill should succeed");
-                requested_quantity -= fill_quantity;
+            // Fill the order
+            order.fill(fill_quantity).expect("Fill should succeed");
+            requested_quantity -= fill_quantity;
+            self.total_quantity
+                .fetch_sub(fill_quantity, Ordering::Relaxed);
 
-                // Track the fill
-                filled_orders.push((order.clone(), fill_quantity));
+            // Track the fill
+            filled_orders.push((order.clone(), fill_quantity));
 
+            if order.remaining_quantity == 0 {
                 // Remove if completely filled
-                if order.remaining_quantity == 0 {
-                    orders.pop_front();
-                    self.order_count.fetch_sub(1, Ordering::Relaxed);
-                }
-
+                orders.pop_front();
+                self.order_count.fetch_sub(1, Ordering::Relaxed);
+            } else if order.refresh_display() {
+                // A refreshed iceberg slice joins the back of the queue
                 self.total_quantity
-                    .fetch_sub(fill_quantity, Ordering::Relaxed);
-
-                if fill_quantity < available {
-                    break; // Order partially filled, we're done
+                    .fetch_add(order.visible_quantity(), Ordering::Relaxed);
+                if let Some(order) = orders.pop_front() {
+                    orders.push_back(order);
                 }
+            } else {
+                break; // Order partially filled, we're done
             }
         }
 
@@ -100,13 +120,26 @@ impl PriceLevel {
         order_id: &OrderId,
         new_quantity: Quantity,
     ) -> Option<Quantity> {
+        self.resize_order(order_id, new_quantity)
+            .map(|(old_quantity, _, _)| old_quantity)
+    }
+
+    /// Modify an order's quantity at this price level, returning its old open
+    /// quantity and its visible quantity before and after
+    pub fn resize_order(
+        &self,
+        order_id: &OrderId,
+        new_quantity: Quantity,
+    ) -> Option<(Quantity, Quantity, Quantity)> {
         let mut orders = self.orders.write();
 
         if let Some(order) = orders.iter_mut().find(|o| &o.id == order_id) {
             let old_quantity = order.remaining_quantity;
-            let quantity_diff = new_quantity as i64 - old_quantity as i64;
+            let old_visible = order.visible_quantity();
 
-            order.remaining_quantity = new_quantity;
+            order.resize(new_quantity);
+            let new_visible = order.visible_quantity();
+            let quantity_diff = new_visible as i64 - old_visible as i64;
 
             if quantity_diff != 0 {
                 if quantity_diff > 0 {
@@ -118,7 +151,7 @@ impl PriceLevel {
                 }
             }
 
-            Some(old_quantity)
+            Some((old_quantity, old_visible, new_visible))
         } else {
             None
         }
@@ -166,7 +199,7 @@ impl Clone for PriceLevel {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::orderbook::types::{OrderStatus, OrderType, Side};
+    use crate::orderbook::types::{OrderStatus, OrderType, Side, TimeInForce};
     use chrono::Utc;
     use uuid::Uuid;
 
@@ -183,6 +216,12 @@ mod tests {
             status: OrderStatus::New,
             timestamp: Utc::now(),
             client_id: None,
+            stp_group: None,
+            stp_mode: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0,
+            post_only: None,
         }
     }
 
@@ -258,6 +297,26 @@ mod tests {
         assert_eq!(level.order_count(), 1);
     }
 
+    #[test]
+    fn test_take_quantity_refreshes_iceberg() {
+        let level = PriceLevel::new(10000);
+        let iceberg = create_test_order(10000, 250).with_display_quantity(100);
+        let iceberg_id = iceberg.id;
+
+        level.add_order(iceberg);
+        level.add_order(create_test_order(10000, 50));
+        assert_eq!(level.total_quantity(), 150);
+
+        // The used-up slice is refreshed and requeued behind the second order
+        let fills = level.take_quantity(120);
+        assert_eq!(fills.len(), 2);
+        assert_eq!((fills[0].0.id, fills[0].1), (iceberg_id, 100));
+        assert_eq!(fills[0].0.visible_quantity(), 0);
+        assert_eq!(fills[1].1, 20);
+        assert_eq!(level.total_quantity(), 130); // 30 + refreshed 100
+        assert_eq!(level.get_all_orders()[1].id, iceberg_id);
+    }
+
     #[test]
     fn test_remove_order() {
         let level = PriceLevel::new(10000);
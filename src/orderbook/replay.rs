@@ -0,0 +1,283 @@
+//! Deterministic replay of order flow
+//!
+//! A replay input file holds one JSON [`ReplayCommand`] per line. Each command
+//! produces one JSON [`ReplayOutput`] line. Run against a book built with
+//! [`deterministic_book`], the same input always yields byte-identical output,
+//! which makes replay files usable as regression fixtures and for reconciling
+//! against production event logs.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+
+use crate::orderbook::book::OrderBook;
+use crate::orderbook::error::OrderBookError;
+use crate::orderbook::types::{MarketEvent, Order, OrderId, Price, Quantity, Side};
+use crate::utils::ids::SequentialIds;
+use crate::utils::time::ManualClock;
+
+/// One line of a replay input file
+///
+/// Orders are referred to by `client_id`, since order ids are only assigned
+/// by the book during replay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReplayCommand {
+    Limit {
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+        client_id: Option<String>,
+    },
+    Market {
+        side: Side,
+        quantity: Quantity,
+        client_id: Option<String>,
+    },
+    Stop {
+        side: Side,
+        stop_price: Price,
+        quantity: Quantity,
+        client_id: Option<String>,
+    },
+    StopLimit {
+        side: Side,
+        stop_price: Price,
+        limit_price: Price,
+        quantity: Quantity,
+        client_id: Option<String>,
+    },
+    Cancel {
+        client_id: String,
+    },
+    Modify {
+        client_id: String,
+        quantity: Quantity,
+    },
+}
+
+/// Result of replaying one input line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayOutput {
+    pub line: usize,
+    pub events: Vec<MarketEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<OrderBookError>,
+}
+
+/// Create a book whose ids and timestamps depend only on `seed`
+pub fn deterministic_book(symbol: String, seed: u64) -> OrderBook {
+    OrderBook::new(symbol)
+        .with_clock(Arc::new(ManualClock::from_epoch()))
+        .with_id_generator(Arc::new(SequentialIds::new(seed)))
+}
+
+/// Feeds replay commands into an order book
+#[derive(Debug)]
+pub struct Replayer<'a> {
+    book: &'a OrderBook,
+    client_orders: HashMap<String, OrderId>,
+}
+
+impl<'a> Replayer<'a> {
+    pub fn new(book: &'a OrderBook) -> Self {
+        Self {
+            book,
+            client_orders: HashMap::new(),
+        }
+    }
+
+    /// Apply a single command to the book
+    pub fn apply(&mut self, command: ReplayCommand) -> Result<Vec<MarketEvent>, OrderBookError> {
+        let symbol = self.book.symbol.clone();
+
+        match command {
+            ReplayCommand::Limit {
+                side,
+                price,
+                quantity,
+                client_id,
+            } => {
+                let order = self.stamp(Order::new_limit(symbol, side, price, quantity, client_id));
+                self.book.add_limit_order(order)
+            }
+            ReplayCommand::Market {
+                side,
+                quantity,
+                client_id,
+            } => {
+                let order = self.stamp(Order::new_market(symbol, side, quantity, client_id));
+                self.book.add_market_order(order)
+            }
+            ReplayCommand::Stop {
+                side,
+                stop_price,
+                quantity,
+                client_id,
+            } => {
+                let order = self.stamp(Order::new_stop(
+                    symbol, side, stop_price, quantity, client_id,
+                ));
+                self.book.add_stop_order(order)
+            }
+            ReplayCommand::StopLimit {
+                side,
+                stop_price,
+                limit_price,
+                quantity,
+                client_id,
+            } => {
+                let order = self.stamp(Order::new_stop_limit(
+                    symbol,
+                    side,
+                    stop_price,
+                    limit_price,
+                    quantity,
+                    client_id,
+                ));
+                self.book.add_stop_order(order)
+            }
+            ReplayCommand::Cancel { client_id } => {
+                let order_id = self.lookup(&client_id)?;
+                self.book.cancel_order(&order_id).map(|event| vec![event])
+            }
+            ReplayCommand::Modify {
+                client_id,
+                quantity,
+            } => {
+                let order_id = self.lookup(&client_id)?;
+                self.book
+                    .modify_order_quantity(&order_id, quantity)
+                    .map(|event| vec![event])
+            }
+        }
+    }
+
+    /// Replay every line of `input`, writing one output line per command
+    ///
+    /// Returns the number of commands replayed. Blank lines are skipped; a
+    /// line that is not a valid command stops the replay with `InvalidData`.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<usize> {
+        let mut replayed = 0;
+
+        for (index, line) in input.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let command: ReplayCommand = serde_json::from_str(&line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", index + 1, e),
+                )
+            })?;
+
+            let result = match self.apply(command) {
+                Ok(events) => ReplayOutput {
+                    line: index + 1,
+                    events,
+                    error: None,
+                },
+                Err(e) => ReplayOutput {
+                    line: index + 1,
+                    events: Vec::new(),
+                    error: Some(e),
+                },
+            };
+
+            serde_json::to_writer(&mut output, &result)?;
+            output.write_all(b"\n")?;
+            replayed += 1;
+        }
+
+        output.flush()?;
+        Ok(replayed)
+    }
+
+    fn stamp(&mut self, order: Order) -> Order {
+        let order = self.book.stamp_order(order);
+        if let Some(client_id) = &order.client_id {
+            self.client_orders.insert(client_id.clone(), order.id);
+        }
+        order
+    }
+
+    fn lookup(&self, client_id: &str) -> Result<OrderId, OrderBookError> {
+        self.client_orders
+            .get(client_id)
+            .copied()
+            .ok_or(OrderBookError::OrderNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#"
+{"type":"limit","side":"Sell","price":10000,"quantity":100,"client_id":"s1"}
+{"type":"limit","side":"Sell","price":10100,"quantity":50,"client_id":"s2"}
+{"type":"stop","side":"Buy","stop_price":10000,"quantity":30,"client_id":"stop1"}
+{"type":"limit","side":"Buy","price":9900,"quantity":80,"client_id":"b1"}
+{"type":"limit","side":"Buy","price":10000,"quantity":120,"client_id":"b2"}
+{"type":"modify","client_id":"b1","quantity":40}
+{"type":"market","side":"Sell","quantity":60,"client_id":null}
+{"type":"cancel","client_id":"s2"}
+{"type":"cancel","client_id":"unknown"}
+"#;
+
+    fn replay(seed: u64) -> String {
+        let book = deterministic_book("TEST".to_string(), seed);
+        let mut output = Vec::new();
+        let replayed = Replayer::new(&book)
+            .run(INPUT.as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(replayed, 9);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_replay_is_byte_identical() {
+        let first = replay(42);
+        let second = replay(42);
+        assert_eq!(first, second);
+
+        // A different seed changes the ids but not the shape of the stream
+        let other = replay(7);
+        assert_ne!(first, other);
+        assert_eq!(first.lines().count(), other.lines().count());
+    }
+
+    #[test]
+    fn test_replay_output() {
+        let outputs: Vec<ReplayOutput> = replay(1)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        // b2 takes s1 at 10000 and triggers stop1, which lifts s2 at 10100
+        let trades: Vec<(Price, Quantity)> = outputs[4]
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::Trade { trade } => Some((trade.price, trade.quantity)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(trades, vec![(10000, 100), (10100, 30)]);
+
+        assert_eq!(outputs[8].error, Some(OrderBookError::OrderNotFound));
+        assert!(outputs[7].error.is_none());
+    }
+
+    #[test]
+    fn test_invalid_line_is_rejected() {
+        let book = deterministic_book("TEST".to_string(), 0);
+        let err = Replayer::new(&book)
+            .run(&b"{\"type\":\"teleport\"}\n"[..], io::sink())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
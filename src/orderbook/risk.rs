@@ -0,0 +1,761 @@
+//! Pre-trade risk checks
+//!
+//! An order book runs every new order and quantity amend through its
+//! [`RiskCheck`] pipeline before it is journaled or matched, and the first
+//! check that fails rejects it with `OrderBookError::RiskRejected`. [`AccountRisk`] is
+//! the standard check: per-client [`RiskLimits`] on order size and notional,
+//! open orders, positions and price collars, enforced against the exposure it
+//! tracks from the events of the books it is attached to. Attaching one
+//! `AccountRisk` to several books makes its position limits span them.
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+
+use crate::orderbook::circuit_breaker::BandWidth;
+use crate::orderbook::types::{MarketEvent, Order, OrderId, OrderType, Price, Quantity, Side};
+
+/// Top of the book an order is checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MarketContext {
+    pub best_bid: Option<Price>,
+    pub best_ask: Option<Price>,
+}
+
+impl MarketContext {
+    /// Get the best price an order on `side` would trade against
+    pub fn opposite_best(&self, side: Side) -> Option<Price> {
+        match side {
+            Side::Buy => self.best_ask,
+            Side::Sell => self.best_bid,
+        }
+    }
+}
+
+/// Why a risk check rejected an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RiskRejection {
+    /// Order quantity is above the client's maximum
+    OrderQuantity { quantity: Quantity, limit: Quantity },
+    /// Order value (price times quantity) is above the client's maximum
+    OrderNotional { notional: u128, limit: u128 },
+    /// Client already has the maximum number of open orders
+    OpenOrders { limit: usize },
+    /// Sum of absolute positions across symbols would exceed the maximum
+    GrossPosition {
+        projected: Quantity,
+        limit: Quantity,
+    },
+    /// Position in the order's symbol would exceed the maximum either way
+    NetPosition { projected: i64, limit: Quantity },
+    /// Limit price is further through the market than the collar allows
+    PriceCollar { price: Price, limit: Price },
+}
+
+impl fmt::Display for RiskRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskRejection::OrderQuantity { quantity, limit } => {
+                write!(f, "order quantity {} exceeds {}", quantity, limit)
+            }
+            RiskRejection::OrderNotional { notional, limit } => {
+                write!(f, "order notional {} exceeds {}", notional, limit)
+            }
+            RiskRejection::OpenOrders { limit } => write!(f, "{} orders already open", limit),
+            RiskRejection::GrossPosition { projected, limit } => {
+                write!(f, "gross position {} would exceed {}", projected, limit)
+            }
+            RiskRejection::NetPosition { projected, limit } => {
+                write!(f, "net position {} would exceed {}", projected, limit)
+            }
+            RiskRejection::PriceCollar { price, limit } => {
+                write!(f, "price {} is through the collar at {}", price, limit)
+            }
+        }
+    }
+}
+
+/// A stage of the pre-trade risk pipeline
+pub trait RiskCheck: Send + Sync + Debug {
+    /// Check a new order before it reaches the book
+    fn check(&self, order: &Order, market: &MarketContext) -> Result<(), RiskRejection>;
+
+    /// Check an amend of a resting order before it reaches the book;
+    /// `order` has the amended quantity and `_previous` is the quantity it
+    /// replaces
+    fn check_amend(
+        &self,
+        order: &Order,
+        _previous: Quantity,
+        market: &MarketContext,
+    ) -> Result<(), RiskRejection> {
+        self.check(order, market)
+    }
+
+    /// Observe the events of a command the book accepted; `order` is the new
+    /// order that caused them, if there is one
+    fn on_events(&self, _order: Option<&Order>, _events: &[MarketEvent]) {}
+}
+
+/// Limits applied to one client; `None` leaves a dimension unchecked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RiskLimits {
+    pub max_order_quantity: Option<Quantity>,
+    /// Largest price times quantity, in price units
+    pub max_order_notional: Option<u128>,
+    pub max_open_orders: Option<usize>,
+    pub max_gross_position: Option<Quantity>,
+    pub max_net_position: Option<Quantity>,
+    /// Band around the opposite best price that limit prices must stay in
+    pub price_collar: Option<BandWidth>,
+}
+
+impl RiskLimits {
+    /// Limits with every dimension unchecked
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_order_quantity(mut self, quantity: Quantity) -> Self {
+        self.max_order_quantity = Some(quantity);
+        self
+    }
+
+    pub fn with_max_order_notional(mut self, notional: u128) -> Self {
+        self.max_order_notional = Some(notional);
+        self
+    }
+
+    pub fn with_max_open_orders(mut self, open_orders: usize) -> Self {
+        self.max_open_orders = Some(open_orders);
+        self
+    }
+
+    pub fn with_max_gross_position(mut self, position: Quantity) -> Self {
+        self.max_gross_position = Some(position);
+        self
+    }
+
+    pub fn with_max_net_position(mut self, position: Quantity) -> Self {
+        self.max_net_position = Some(position);
+        self
+    }
+
+    pub fn with_price_collar(mut self, width: BandWidth) -> Self {
+        self.price_collar = Some(width);
+        self
+    }
+
+    /// Check the limits that depend on the order alone
+    fn check_order(&self, order: &Order, market: &MarketContext) -> Result<(), RiskRejection> {
+        let quantity = order.remaining_quantity;
+        if let Some(limit) = self.max_order_quantity.filter(|&limit| quantity > limit) {
+            return Err(RiskRejection::OrderQuantity { quantity, limit });
+        }
+
+        // Market orders are valued at the price they would trade first at
+        let price = match order.order_type {
+            OrderType::Market | OrderType::MarketToLimit => market.opposite_best(order.side),
+            OrderType::Stop => order.stop_price(),
+            _ => Some(order.price),
+        };
+        if let (Some(price), Some(limit)) = (price, self.max_order_notional) {
+            let notional = price as u128 * quantity as u128;
+            if notional > limit {
+                return Err(RiskRejection::OrderNotional { notional, limit });
+            }
+        }
+
+        // Collars apply to limit prices, around the opposite best or the
+        // same-side best when the opposite side is empty
+        let reference = market.opposite_best(order.side).or(match order.side {
+            Side::Buy => market.best_bid,
+            Side::Sell => market.best_ask,
+        });
+        if let (OrderType::Limit, Some(collar), Some(reference)) =
+            (order.order_type, self.price_collar, reference)
+        {
+            let (low, high) = collar.limits(reference);
+            match order.side {
+                Side::Buy if order.price > high => {
+                    return Err(RiskRejection::PriceCollar {
+                        price: order.price,
+                        limit: high,
+                    })
+                }
+                Side::Sell if order.price < low => {
+                    return Err(RiskRejection::PriceCollar {
+                        price: order.price,
+                        limit: low,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A client's exposure in one symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Exposure {
+    /// Bought minus sold quantity
+    pub position: i64,
+    /// Open quantity of the client's buy orders
+    pub open_buy: Quantity,
+    /// Open quantity of the client's sell orders
+    pub open_sell: Quantity,
+}
+
+impl Exposure {
+    /// Get the position if every open order on `side` and `quantity` more
+    /// were filled
+    fn projected(&self, side: Side, quantity: Quantity) -> i64 {
+        match side {
+            Side::Buy => self
+                .position
+                .saturating_add_unsigned(self.open_buy)
+                .saturating_add_unsigned(quantity),
+            Side::Sell => self
+                .position
+                .saturating_sub_unsigned(self.open_sell)
+                .saturating_sub_unsigned(quantity),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Account {
+    open_orders: usize,
+    exposures: HashMap<String, Exposure>,
+}
+
+#[derive(Debug)]
+struct OpenOrder {
+    client_id: String,
+    symbol: String,
+    side: Side,
+    remaining: Quantity,
+}
+
+#[derive(Debug, Default)]
+struct RiskState {
+    accounts: HashMap<String, Account>,
+    open_orders: HashMap<OrderId, OpenOrder>,
+}
+
+impl RiskState {
+    fn exposure(&mut self, client_id: &str, symbol: &str) -> &mut Exposure {
+        self.accounts
+            .entry(client_id.to_string())
+            .or_default()
+            .exposures
+            .entry(symbol.to_string())
+            .or_default()
+    }
+
+    fn track(&mut self, order: &Order) {
+        let Some(client_id) = order.client_id.clone() else {
+            return;
+        };
+        if order.remaining_quantity == 0 || self.open_orders.contains_key(&order.id) {
+            return;
+        }
+
+        let exposure = self.exposure(&client_id, &order.symbol);
+        match order.side {
+            Side::Buy => exposure.open_buy += order.remaining_quantity,
+            Side::Sell => exposure.open_sell += order.remaining_quantity,
+        }
+        self.accounts
+            .entry(client_id.clone())
+            .or_default()
+            .open_orders += 1;
+        self.open_orders.insert(
+            order.id,
+            OpenOrder {
+                client_id,
+                symbol: order.symbol.clone(),
+                side: order.side,
+                remaining: order.remaining_quantity,
+            },
+        );
+    }
+
+    /// Set the open quantity of a tracked order, crediting `filled` to the
+    /// client's position
+    fn update(&mut self, order_id: &OrderId, remaining: Quantity, filled: Quantity) {
+        let Some(open) = self.open_orders.get_mut(order_id) else {
+            return;
+        };
+        let previous = std::mem::replace(&mut open.remaining, remaining);
+        let (client_id, symbol, side) = (open.client_id.clone(), open.symbol.clone(), open.side);
+
+        let exposure = self.exposure(&client_id, &symbol);
+        match side {
+            Side::Buy => {
+                exposure.open_buy = exposure.open_buy.saturating_sub(previous) + remaining;
+                exposure.position = exposure.position.saturating_add_unsigned(filled);
+            }
+            Side::Sell => {
+                exposure.open_sell = exposure.open_sell.saturating_sub(previous) + remaining;
+                exposure.position = exposure.position.saturating_sub_unsigned(filled);
+            }
+        }
+
+        if remaining == 0 {
+            self.open_orders.remove(order_id);
+            if let Some(account) = self.accounts.get_mut(&client_id) {
+                account.open_orders = account.open_orders.saturating_sub(1);
+            }
+        }
+    }
+
+    fn fill(&mut self, order_id: &OrderId, quantity: Quantity) {
+        if let Some(remaining) = self.open_orders.get(order_id).map(|open| open.remaining) {
+            let filled = quantity.min(remaining);
+            self.update(order_id, remaining - filled, filled);
+        }
+    }
+}
+
+/// Per-client limits enforced against tracked orders and positions
+///
+/// Orders are tracked from the moment their book accepts them. Orders
+/// already resting when the check is attached count as open, but earlier
+/// fills only reach the positions if the check is attached before the book
+/// replays its journal (`OrderBook::open_journal`). Orders without a client
+/// id are held to the default per-order limits only.
+#[derive(Debug, Default)]
+pub struct AccountRisk {
+    default_limits: RiskLimits,
+    client_limits: DashMap<String, RiskLimits>,
+    state: Mutex<RiskState>,
+}
+
+impl AccountRisk {
+    /// Check that applies `default_limits` to clients without their own
+    pub fn new(default_limits: RiskLimits) -> Self {
+        Self {
+            default_limits,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_client_limits(self, client_id: impl Into<String>, limits: RiskLimits) -> Self {
+        self.set_client_limits(client_id, limits);
+        self
+    }
+
+    /// Replace the limits of one client
+    pub fn set_client_limits(&self, client_id: impl Into<String>, limits: RiskLimits) {
+        self.client_limits.insert(client_id.into(), limits);
+    }
+
+    /// Get the limits that apply to `client_id`
+    pub fn limits(&self, client_id: &str) -> RiskLimits {
+        self.client_limits
+            .get(client_id)
+            .map_or(self.default_limits, |limits| *limits)
+    }
+
+    /// Get a client's exposure in `symbol`
+    pub fn exposure(&self, client_id: &str, symbol: &str) -> Exposure {
+        let state = self.state.lock();
+        state
+            .accounts
+            .get(client_id)
+            .and_then(|account| account.exposures.get(symbol))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Get the number of a client's orders still open
+    pub fn open_orders(&self, client_id: &str) -> usize {
+        let state = self.state.lock();
+        state
+            .accounts
+            .get(client_id)
+            .map_or(0, |account| account.open_orders)
+    }
+
+    /// Check the limits on a client's open orders and positions if `added`
+    /// more of `order`'s side were to open; `new_order` also counts it
+    /// against the open-order limit
+    fn check_account(
+        &self,
+        client_id: &str,
+        limits: &RiskLimits,
+        order: &Order,
+        added: Quantity,
+        new_order: bool,
+    ) -> Result<(), RiskRejection> {
+        let state = self.state.lock();
+        let new_account = Account::default();
+        let account = state.accounts.get(client_id).unwrap_or(&new_account);
+
+        if let Some(limit) = limits.max_open_orders.filter(|_| new_order) {
+            if account.open_orders >= limit {
+                return Err(RiskRejection::OpenOrders { limit });
+            }
+        }
+
+        let exposure = account
+            .exposures
+            .get(&order.symbol)
+            .copied()
+            .unwrap_or_default();
+        let projected = exposure.projected(order.side, added);
+        if let Some(limit) = limits.max_net_position {
+            if projected.unsigned_abs() > limit {
+                return Err(RiskRejection::NetPosition { projected, limit });
+            }
+        }
+        if let Some(limit) = limits.max_gross_position {
+            let gross = account
+                .exposures
+                .iter()
+                .filter(|(symbol, _)| **symbol != order.symbol)
+                .map(|(_, exposure)| exposure.position.unsigned_abs())
+                .fold(projected.unsigned_abs(), u64::saturating_add);
+            if gross > limit {
+                return Err(RiskRejection::GrossPosition {
+                    projected: gross,
+                    limit,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RiskCheck for AccountRisk {
+    fn check(&self, order: &Order, market: &MarketContext) -> Result<(), RiskRejection> {
+        let Some(client_id) = order.client_id.as_deref() else {
+            return self.default_limits.check_order(order, market);
+        };
+        let limits = self.limits(client_id);
+        limits.check_order(order, market)?;
+        self.check_account(client_id, &limits, order, order.remaining_quantity, true)
+    }
+
+    fn check_amend(
+        &self,
+        order: &Order,
+        previous: Quantity,
+        market: &MarketContext,
+    ) -> Result<(), RiskRejection> {
+        // Reducing an order only ever lowers exposure
+        if order.remaining_quantity <= previous {
+            return Ok(());
+        }
+
+        let Some(client_id) = order.client_id.as_deref() else {
+            return self.default_limits.check_order(order, market);
+        };
+        let limits = self.limits(client_id);
+        limits.check_order(order, market)?;
+        // The open quantity already counts what the order had
+        let increase = order.remaining_quantity - previous;
+        self.check_account(client_id, &limits, order, increase, false)
+    }
+
+    fn on_events(&self, order: Option<&Order>, events: &[MarketEvent]) {
+        let mut state = self.state.lock();
+        if let Some(order) = order {
+            state.track(order);
+        }
+
+        for event in events {
+            match event {
+                MarketEvent::Trade { trade } => {
+                    state.fill(&trade.buyer_order_id, trade.quantity);
+                    state.fill(&trade.seller_order_id, trade.quantity);
+                }
+                MarketEvent::OrderAdded { order } => state.track(order),
+                MarketEvent::OrderModified {
+                    order_id,
+                    new_quantity: Some(quantity),
+                    ..
+                } => state.update(order_id, *quantity, 0),
+                MarketEvent::OrderCancelled { order_id, .. }
+                | MarketEvent::OrderExpired { order_id, .. }
+                | MarketEvent::OrderFilled { order_id } => state.update(order_id, 0, 0),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::book::OrderBook;
+    use crate::orderbook::error::OrderBookError;
+    use crate::orderbook::exchange::{Exchange, InstrumentConfig};
+    use std::sync::Arc;
+
+    fn limit(symbol: &str, side: Side, price: Price, quantity: Quantity, client: &str) -> Order {
+        Order::new_limit(
+            symbol.to_string(),
+            side,
+            price,
+            quantity,
+            Some(client.to_string()),
+        )
+    }
+
+    #[test]
+    fn test_order_limits() {
+        let risk = AccountRisk::new(
+            RiskLimits::new()
+                .with_max_order_quantity(100)
+                .with_max_order_notional(1_000_000)
+                .with_price_collar(BandWidth::Absolute(50)),
+        )
+        .with_client_limits("big", RiskLimits::new());
+        let market = MarketContext {
+            best_bid: Some(9_990),
+            best_ask: Some(10_000),
+        };
+        let check = |order: Order| risk.check(&order, &market);
+
+        assert_eq!(check(limit("TEST", Side::Buy, 10_000, 100, "a")), Ok(()));
+        assert_eq!(
+            check(limit("TEST", Side::Buy, 9_000, 101, "a")),
+            Err(RiskRejection::OrderQuantity {
+                quantity: 101,
+                limit: 100
+            })
+        );
+        assert_eq!(
+            check(limit("TEST", Side::Buy, 10_050, 100, "a")),
+            Err(RiskRejection::OrderNotional {
+                notional: 1_005_000,
+                limit: 1_000_000
+            })
+        );
+        assert_eq!(
+            check(limit("TEST", Side::Buy, 10_060, 10, "a")),
+            Err(RiskRejection::PriceCollar {
+                price: 10_060,
+                limit: 10_050
+            })
+        );
+        assert_eq!(
+            check(limit("TEST", Side::Sell, 9_930, 10, "a")),
+            Err(RiskRejection::PriceCollar {
+                price: 9_930,
+                limit: 9_940
+            })
+        );
+        // Passive prices are not collared
+        assert_eq!(check(limit("TEST", Side::Sell, 12_000, 10, "a")), Ok(()));
+
+        // Clients with their own limits are not held to the defaults
+        assert_eq!(check(limit("TEST", Side::Buy, 20_000, 500, "big")), Ok(()));
+
+        // Market orders are valued at the opposite best
+        let market_buy = Order::new_market("TEST".to_string(), Side::Buy, 100, None);
+        assert_eq!(check(market_buy.clone()), Ok(()));
+        let market = MarketContext {
+            best_ask: Some(10_010),
+            ..market
+        };
+        assert_eq!(
+            risk.check(&market_buy, &market),
+            Err(RiskRejection::OrderNotional {
+                notional: 1_001_000,
+                limit: 1_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn test_account_limits() {
+        let risk = Arc::new(AccountRisk::new(
+            RiskLimits::new()
+                .with_max_open_orders(2)
+                .with_max_net_position(150)
+                .with_max_gross_position(200),
+        ));
+        let exchange = Exchange::new().with_risk_check(risk.clone());
+        for symbol in ["AAA", "BBB"] {
+            exchange
+                .add_instrument(InstrumentConfig::new(symbol))
+                .unwrap();
+        }
+        let add = |order: Order| exchange.add_limit_order(order).map(|_| ());
+
+        // Open buys count toward the net position they could reach
+        add(limit("AAA", Side::Buy, 9_900, 100, "alice")).unwrap();
+        assert_eq!(
+            add(limit("AAA", Side::Buy, 9_800, 60, "alice")),
+            Err(OrderBookError::RiskRejected(RiskRejection::NetPosition {
+                projected: 160,
+                limit: 150
+            }))
+        );
+        let sell = limit("AAA", Side::Sell, 10_100, 50, "alice");
+        let sell_id = sell.id;
+        add(sell).unwrap();
+        assert_eq!(
+            add(limit("AAA", Side::Buy, 9_800, 10, "alice")),
+            Err(OrderBookError::RiskRejected(RiskRejection::OpenOrders {
+                limit: 2
+            }))
+        );
+
+        // Fills move open quantity into the position
+        exchange
+            .add_market_order(Order::new_market(
+                "AAA".to_string(),
+                Side::Sell,
+                100,
+                Some("bob".to_string()),
+            ))
+            .unwrap();
+        assert_eq!(
+            risk.exposure("alice", "AAA"),
+            Exposure {
+                position: 100,
+                open_buy: 0,
+                open_sell: 50,
+            }
+        );
+        assert_eq!(risk.exposure("bob", "AAA").position, -100);
+        assert_eq!(risk.open_orders("alice"), 1);
+        assert_eq!(risk.open_orders("bob"), 0);
+
+        exchange.cancel_order("AAA", &sell_id).unwrap();
+        assert_eq!(risk.exposure("alice", "AAA").open_sell, 0);
+        assert_eq!(risk.open_orders("alice"), 0);
+
+        // The gross position spans both books
+        assert_eq!(
+            add(limit("BBB", Side::Sell, 5_000, 120, "alice")),
+            Err(OrderBookError::RiskRejected(RiskRejection::GrossPosition {
+                projected: 220,
+                limit: 200
+            }))
+        );
+        add(limit("BBB", Side::Sell, 5_000, 100, "alice")).unwrap();
+    }
+
+    #[test]
+    fn test_amends_are_checked() {
+        let risk = Arc::new(AccountRisk::new(
+            RiskLimits::new()
+                .with_max_order_quantity(100)
+                .with_max_open_orders(2)
+                .with_max_net_position(150),
+        ));
+        let book = OrderBook::new("TEST".to_string()).with_risk_check(risk.clone());
+        let small = limit("TEST", Side::Buy, 9_900, 1, "alice");
+        let small_id = small.id;
+        book.add_limit_order(small).unwrap();
+
+        assert_eq!(
+            book.modify_order_quantity(&small_id, 101).map(|_| ()),
+            Err(OrderBookError::RiskRejected(RiskRejection::OrderQuantity {
+                quantity: 101,
+                limit: 100
+            }))
+        );
+
+        // Only the increase is new exposure, and an amend is not a new order
+        let large = limit("TEST", Side::Buy, 9_800, 100, "alice");
+        book.add_limit_order(large).unwrap();
+        assert_eq!(
+            book.modify_order_quantity(&small_id, 60).map(|_| ()),
+            Err(OrderBookError::RiskRejected(RiskRejection::NetPosition {
+                projected: 160,
+                limit: 150
+            }))
+        );
+        book.modify_order_quantity(&small_id, 50).unwrap();
+        assert_eq!(risk.exposure("alice", "TEST").open_buy, 150);
+
+        // Reductions always pass
+        risk.set_client_limits("alice", RiskLimits::new().with_max_net_position(0));
+        book.modify_order_quantity(&small_id, 10).unwrap();
+        assert_eq!(risk.exposure("alice", "TEST").open_buy, 110);
+    }
+
+    #[test]
+    fn test_state_is_rebuilt_on_recovery() {
+        use crate::orderbook::journal::FsyncPolicy;
+
+        let path = std::env::temp_dir().join(format!("orderbook-risk-{}.wal", OrderId::new_v4()));
+        let listed = |risk: &Arc<AccountRisk>| {
+            let exchange = Exchange::new().with_risk_check(risk.clone());
+            let book = OrderBook::new("AAA".to_string());
+            exchange
+                .add_instrument_with_journal(
+                    InstrumentConfig::new("AAA"),
+                    book,
+                    &path,
+                    FsyncPolicy::Never,
+                )
+                .unwrap();
+            exchange
+        };
+        let limits = RiskLimits::new().with_max_net_position(150);
+
+        let exchange = listed(&Arc::new(AccountRisk::new(limits)));
+        for price in [9_900, 9_800] {
+            exchange
+                .add_limit_order(limit("AAA", Side::Buy, price, 60, "alice"))
+                .unwrap();
+        }
+        exchange
+            .add_limit_order(limit("AAA", Side::Sell, 9_900, 40, "bob"))
+            .unwrap();
+        drop(exchange);
+
+        // A fresh check sees the replayed orders and fills
+        let risk = Arc::new(AccountRisk::new(limits));
+        let exchange = listed(&risk);
+        assert_eq!(
+            risk.exposure("alice", "AAA"),
+            Exposure {
+                position: 40,
+                open_buy: 80,
+                open_sell: 0,
+            }
+        );
+        assert_eq!(risk.open_orders("alice"), 2);
+
+        // Fills against recovered orders reach the position
+        exchange
+            .add_limit_order(limit("AAA", Side::Sell, 9_900, 20, "bob"))
+            .unwrap();
+        assert_eq!(risk.exposure("alice", "AAA").position, 60);
+        assert_eq!(
+            exchange
+                .add_limit_order(limit("AAA", Side::Buy, 9_700, 40, "alice"))
+                .map(|_| ()),
+            Err(OrderBookError::RiskRejected(RiskRejection::NetPosition {
+                projected: 160,
+                limit: 150
+            }))
+        );
+
+        // A check attached after recovery still counts the resting orders
+        let late = Arc::new(AccountRisk::new(limits));
+        let book = OrderBook::recover_from(&path)
+            .unwrap()
+            .with_risk_check(late.clone());
+        assert_eq!(book.total_orders(), 1);
+        assert_eq!(late.open_orders("alice"), 1);
+        assert_eq!(late.exposure("alice", "AAA").open_buy, 60);
+
+        drop(exchange);
+        drop(book);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+//! Full-depth order book snapshots
+//!
+//! Unlike [`BookSnapshot`](crate::orderbook::types::BookSnapshot), which only
+//! carries aggregated depth, a [`FullSnapshot`] holds every resting order in
+//! queue order, so `OrderBook::from_snapshot` can rebuild the book with time
+//! priority intact.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::orderbook::types::{Order, Price, TradingPhase};
+
+/// Current on-disk snapshot format version
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Resting orders at one price, front of the queue first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelSnapshot {
+    pub price: Price,
+    pub orders: Vec<Order>,
+}
+
+/// Complete, restorable state of an order book
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FullSnapshot {
+    pub version: u32,
+    pub symbol: String,
+    pub timestamp: DateTime<Utc>,
+    /// Sequence number the next accepted command will receive
+    pub sequence_number: u64,
+    pub last_trade_price: Option<Price>,
+    pub total_trades: u64,
+    pub total_volume: u64,
+    #[serde(default)]
+    pub trading_phase: TradingPhase,
+    /// Reference price of the static price band
+    #[serde(default)]
+    pub reference_price: Option<Price>,
+    /// Bid levels, best (highest) price first
+    pub bids: Vec<LevelSnapshot>,
+    /// Ask levels, best (lowest) price first
+    pub asks: Vec<LevelSnapshot>,
+    /// Untriggered stop orders, in trigger order
+    pub stop_orders: Vec<Order>,
+}
+
+impl FullSnapshot {
+    /// Write the snapshot to `path`
+    ///
+    /// The snapshot is written to a temporary file and renamed into place, so
+    /// a crash never leaves a partially written snapshot at `path`.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+
+        let file = File::create(&tmp_path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        drop(writer);
+
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Read a snapshot from `path`, rejecting unknown format versions
+    pub fn read_from(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let snapshot: Self = serde_json::from_reader(reader)?;
+
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported snapshot version {}", snapshot.version),
+            ));
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Get the number of resting (non-stop) orders
+    pub fn order_count(&self) -> usize {
+        self.bids
+            .iter()
+            .chain(&self.asks)
+            .map(|level| level.orders.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::instrument::Instrument;
+    use crate::orderbook::risk::{AccountRisk, RiskLimits};
+    use crate::orderbook::types::{MarketEvent, OrderId, Quantity, Side};
+    use crate::orderbook::{OrderBook, OrderBookError};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn limit(side: Side, price: Price, quantity: Quantity) -> Order {
+        Order::new_limit("TEST".to_string(), side, price, quantity, None)
+    }
+
+    fn temp_snapshot_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("orderbook-snapshot-{}.json", Uuid::new_v4()))
+    }
+
+    fn populated_book() -> OrderBook {
+        let book = OrderBook::new("TEST".to_string());
+        for i in 0..5 {
+            book.add_limit_order(limit(Side::Buy, 9_900 + i * 10, 100))
+                .unwrap();
+            book.add_limit_order(limit(Side::Buy, 9_940, 10 + i))
+                .unwrap();
+            book.add_limit_order(limit(Side::Sell, 10_000 + i * 10, 100))
+                .unwrap();
+        }
+        // Partially fill the best ask and park a stop
+        book.add_limit_order(limit(Side::Buy, 10_000, 30)).unwrap();
+        book.add_stop_order(Order::new_stop(
+            "TEST".to_string(),
+            Side::Sell,
+            9_800,
+            25,
+            None,
+        ))
+        .unwrap();
+        book
+    }
+
+    fn fills(events: &[MarketEvent]) -> Vec<(OrderId, Quantity)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::Trade { trade } => Some((trade.buyer_order_id, trade.quantity)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let book = populated_book();
+        let snapshot = book.full_snapshot();
+        assert_eq!(snapshot.order_count(), book.total_orders());
+        assert_eq!(snapshot.stop_orders.len(), 1);
+        assert_eq!(snapshot.asks[0].orders[0].remaining_quantity, 70);
+
+        let path = temp_snapshot_path();
+        snapshot.write_to(&path).unwrap();
+        let restored = OrderBook::from_snapshot(FullSnapshot::read_from(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut again = restored.full_snapshot();
+        again.timestamp = snapshot.timestamp;
+        assert_eq!(
+            serde_json::to_string(&snapshot).unwrap(),
+            serde_json::to_string(&again).unwrap()
+        );
+        assert_eq!(
+            restored.get_stats().total_trades,
+            book.get_stats().total_trades
+        );
+        assert_eq!(restored.sequence_number(), book.sequence_number());
+
+        // Time priority survives: the same aggressor fills the same queue
+        let sweep = limit(Side::Sell, 9_900, 500);
+        let original_fills = fills(&book.add_limit_order(sweep.clone()).unwrap());
+        let restored_fills = fills(&restored.add_limit_order(sweep).unwrap());
+        assert_eq!(original_fills, restored_fills);
+    }
+
+    #[test]
+    fn test_restore_checks_instrument_and_risk() {
+        let snapshot = populated_book().full_snapshot();
+
+        // 9_910 is off a 20-tick grid
+        let instrument = Instrument::new("TEST").with_tick_size(20);
+        let result = OrderBook::new("TEST".to_string())
+            .with_instrument(instrument)
+            .unwrap()
+            .restore_snapshot(snapshot.clone());
+        assert!(matches!(result, Err(OrderBookError::InvalidPrice)));
+
+        let mut snapshot = snapshot;
+        snapshot.bids[0].orders[0].client_id = Some("alice".to_string());
+        let resting = snapshot.bids[0].orders[0].remaining_quantity;
+        let risk = Arc::new(AccountRisk::new(RiskLimits::new()));
+        OrderBook::new("TEST".to_string())
+            .with_risk_check(risk.clone())
+            .restore_snapshot(snapshot)
+            .unwrap();
+        assert_eq!(risk.open_orders("alice"), 1);
+        assert_eq!(risk.exposure("alice", "TEST").open_buy, resting);
+    }
+
+    #[test]
+    fn test_unknown_version_is_rejected() {
+        let mut snapshot = OrderBook::new("TEST".to_string()).full_snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+
+        let path = temp_snapshot_path();
+        snapshot.write_to(&path).unwrap();
+        let err = FullSnapshot::read_from(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        assert!(matches!(
+            OrderBook::from_snapshot(snapshot),
+            Err(OrderBookError::SystemError(_))
+        ));
+    }
+}
@@ -0,0 +1,193 @@
+use dashmap::DashMap;
+use tracing::debug;
+
+use crate::orderbook::ladder::{LadderOrder, PriceLadder};
+use crate::orderbook::types::{Order, OrderId, OrderLocation, Price, Side};
+
+/// Holds untriggered stop and stop-limit orders, keyed by stop price
+///
+/// Buy stops trigger when the last trade price rises to or above their stop
+/// price; sell stops trigger when it falls to or below it. Each side is a
+/// ladder ranked in trigger order, so the triggered stops are always a prefix.
+#[derive(Debug)]
+pub struct StopBook {
+    buy_stops: PriceLadder,
+    sell_stops: PriceLadder,
+    locations: DashMap<OrderId, OrderLocation>,
+}
+
+impl StopBook {
+    pub fn new() -> Self {
+        Self {
+            buy_stops: PriceLadder::new(LadderOrder::Ascending),
+            sell_stops: PriceLadder::new(LadderOrder::Descending),
+            locations: DashMap::new(),
+        }
+    }
+
+    /// Check whether a stop on `side` at `stop_price` fires at `last_trade_price`
+    pub fn is_triggered(side: Side, stop_price: Price, last_trade_price: Price) -> bool {
+        match side {
+            Side::Buy => last_trade_price >= stop_price,
+            Side::Sell => last_trade_price <= stop_price,
+        }
+    }
+
+    /// Park a stop order until its trigger price is reached
+    pub fn add_order(&self, order: Order) {
+        let stop_price = match order.stop_price() {
+            Some(price) => price,
+            None => return,
+        };
+        let side = order.side;
+        let order_id = order.id;
+
+        self.side_stops(side)
+            .get_or_insert(stop_price)
+            .add_order(order);
+
+        self.locations.insert(
+            order_id,
+            OrderLocation {
+                price: stop_price,
+                side,
+            },
+        );
+
+        debug!(
+            "Stop order {} parked at stop price {} on {} side",
+            order_id, stop_price, side
+        );
+    }
+
+    /// Remove an untriggered stop order
+    pub fn cancel_order(&self, order_id: &OrderId) -> Option<Order> {
+        let (_, location) = self.locations.remove(order_id)?;
+        let stops = self.side_stops(location.side);
+
+        let order = stops.get(location.price)?.remove_order(order_id);
+        stops.remove_if_empty(location.price);
+
+        order
+    }
+
+    /// Remove and return every stop triggered by `last_trade_price`
+    ///
+    /// Orders come back in a deterministic sequence: buy stops first, lowest
+    /// stop price first, then sell stops, highest stop price first. Within a
+    /// stop price, orders keep their arrival (FIFO) order.
+    pub fn take_triggered(&self, last_trade_price: Price) -> Vec<Order> {
+        let mut triggered = Vec::new();
+        self.drain_through(&self.buy_stops, last_trade_price, &mut triggered);
+        self.drain_through(&self.sell_stops, last_trade_price, &mut triggered);
+        triggered
+    }
+
+    /// Check whether `order_id` is an untriggered stop order
+    pub fn contains(&self, order_id: &OrderId) -> bool {
+        self.locations.contains_key(order_id)
+    }
+
+    /// Get every untriggered stop order in the sequence `take_triggered`
+    /// would release them: buy stops lowest first, then sell stops highest
+    /// first, FIFO within a stop price
+    pub fn orders(&self) -> Vec<Order> {
+        self.buy_stops
+            .levels()
+            .into_iter()
+            .chain(self.sell_stops.levels())
+            .flat_map(|(_, level)| level.get_all_orders())
+            .collect()
+    }
+
+    /// Get number of untriggered stop orders
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Check if there are no untriggered stop orders
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    fn side_stops(&self, side: Side) -> &PriceLadder {
+        match side {
+            Side::Buy => &self.buy_stops,
+            Side::Sell => &self.sell_stops,
+        }
+    }
+
+    fn drain_through(&self, stops: &PriceLadder, limit: Price, triggered: &mut Vec<Order>) {
+        for (price, _) in stops.levels_through(limit) {
+            if let Some(level) = stops.remove(price) {
+                for order in level.get_all_orders() {
+                    self.locations.remove(&order.id);
+                    triggered.push(order);
+                }
+            }
+        }
+    }
+}
+
+impl Default for StopBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop(side: Side, stop_price: Price) -> Order {
+        Order::new_stop("TEST".to_string(), side, stop_price, 100, None)
+    }
+
+    #[test]
+    fn test_trigger_conditions() {
+        assert!(StopBook::is_triggered(Side::Buy, 10000, 10000));
+        assert!(StopBook::is_triggered(Side::Buy, 10000, 10100));
+        assert!(!StopBook::is_triggered(Side::Buy, 10000, 9900));
+        assert!(StopBook::is_triggered(Side::Sell, 10000, 10000));
+        assert!(StopBook::is_triggered(Side::Sell, 10000, 9900));
+        assert!(!StopBook::is_triggered(Side::Sell, 10000, 10100));
+    }
+
+    #[test]
+    fn test_take_triggered_order() {
+        let book = StopBook::new();
+
+        let buy_high = stop(Side::Buy, 10200);
+        let buy_low_first = stop(Side::Buy, 10100);
+        let buy_low_second = stop(Side::Buy, 10100);
+        let sell = stop(Side::Sell, 9900);
+        let expected = vec![buy_low_first.id, buy_low_second.id, buy_high.id];
+
+        book.add_order(buy_high);
+        book.add_order(buy_low_first);
+        book.add_order(buy_low_second);
+        book.add_order(sell);
+        assert_eq!(book.len(), 4);
+
+        let triggered = book.take_triggered(10200);
+        let ids: Vec<OrderId> = triggered.iter().map(|o| o.id).collect();
+        assert_eq!(ids, expected);
+
+        // Sell stop is still parked
+        assert_eq!(book.len(), 1);
+        assert!(book.take_triggered(10000).is_empty());
+    }
+
+    #[test]
+    fn test_cancel_stop_order() {
+        let book = StopBook::new();
+        let order = stop(Side::Sell, 9900);
+        let order_id = order.id;
+
+        book.add_order(order);
+        assert!(book.cancel_order(&order_id).is_some());
+        assert!(book.is_empty());
+        assert!(book.cancel_order(&order_id).is_none());
+        assert!(book.take_triggered(9000).is_empty());
+    }
+}
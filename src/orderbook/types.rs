@@ -13,6 +13,16 @@ pub enum Side {
     Sell,
 }
 
+impl Side {
+    /// Get the other side of the book
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+}
+
 impl fmt::Display for Side {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -25,13 +35,105 @@ impl fmt::Display for Side {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OrderType {
     Market,
+    /// Market order whose unfilled remainder rests as a limit order at the
+    /// last price it executed at
+    MarketToLimit,
     Limit,
     Stop,
-    StopLimit { stop_price: Price },
+    StopLimit {
+        stop_price: Price,
+    },
     ImmediateOrCancel, // IOC
     FillOrKill,        // FOK
 }
 
+/// Trading status of an instrument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TradingStatus {
+    /// Accepting and matching orders
+    Open,
+    /// Temporarily suspended; only cancels are accepted
+    Halted,
+    /// Outside trading hours; only cancels are accepted
+    Closed,
+}
+
+/// Matching phase of an order book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TradingPhase {
+    /// Opening call: orders accumulate without matching until the uncross
+    PreOpen,
+    /// Incoming orders match as they arrive
+    #[default]
+    Continuous,
+    /// Closing call: orders accumulate without matching until the uncross
+    PreClose,
+    /// Call started by a circuit breaker; orders accumulate until the uncross
+    VolatilityAuction,
+    /// No matching and no new orders until trading is resumed
+    Halted,
+}
+
+impl TradingPhase {
+    /// Check whether orders accumulate for an auction instead of matching
+    pub fn is_call(&self) -> bool {
+        matches!(
+            self,
+            TradingPhase::PreOpen | TradingPhase::PreClose | TradingPhase::VolatilityAuction
+        )
+    }
+}
+
+/// Why a book stopped continuous matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HaltReason {
+    /// Halted by an operator
+    Admin,
+    /// An order would have traded at `price`, outside the band from `low`
+    /// to `high`
+    PriceBand {
+        price: Price,
+        low: Price,
+        high: Price,
+    },
+}
+
+/// What to do when an incoming order would trade against a resting order
+/// from the same client or STP group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelfTradePrevention {
+    /// Cancel the rest of the incoming order
+    CancelNewest,
+    /// Cancel the resting order and keep matching
+    CancelOldest,
+    /// Cancel both orders
+    CancelBoth,
+    /// Reduce both orders by the smaller open quantity, cancelling any
+    /// order left with nothing open
+    DecrementAndCancel,
+}
+
+/// How long an order rests before it expires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimeInForce {
+    /// Rests until filled or cancelled
+    #[default]
+    GoodTillCancel,
+    /// Expires when the trading session closes
+    Day,
+    /// Expires at the given time
+    GoodTillDate(DateTime<Utc>),
+}
+
+/// How a post-only order that would take liquidity on entry is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PostOnly {
+    /// Reject the order
+    Reject,
+    /// Reprice the order to one tick behind the opposite best
+    Slide,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OrderStatus {
     New,
@@ -55,6 +157,25 @@ pub struct Order {
     pub status: OrderStatus,
     pub timestamp: DateTime<Utc>,
     pub client_id: Option<String>,
+    /// Orders sharing an STP group never trade with each other; without a
+    /// group, orders of the same client never do
+    #[serde(default)]
+    pub stp_group: Option<String>,
+    /// Overrides the book's self-trade prevention mode when this order is
+    /// the aggressor
+    #[serde(default)]
+    pub stp_mode: Option<SelfTradePrevention>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    /// Peak size an iceberg order shows at a time; `None` shows everything
+    #[serde(default)]
+    pub display_quantity: Option<Quantity>,
+    /// Part of `remaining_quantity` an iceberg order holds in reserve
+    #[serde(default)]
+    pub hidden_quantity: Quantity,
+    /// Set on limit orders that must never trade on entry
+    #[serde(default)]
+    pub post_only: Option<PostOnly>,
 }
 
 impl Order {
@@ -77,6 +198,12 @@ impl Order {
             status: OrderStatus::New,
             timestamp: Utc::now(),
             client_id,
+            stp_group: None,
+            stp_mode: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0,
+            post_only: None,
         }
     }
 
@@ -98,6 +225,174 @@ impl Order {
             status: OrderStatus::New,
             timestamp: Utc::now(),
             client_id,
+            stp_group: None,
+            stp_mode: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0,
+            post_only: None,
+        }
+    }
+
+    pub fn new_market_to_limit(
+        symbol: String,
+        side: Side,
+        quantity: Quantity,
+        client_id: Option<String>,
+    ) -> Self {
+        Self {
+            order_type: OrderType::MarketToLimit,
+            ..Self::new_market(symbol, side, quantity, client_id)
+        }
+    }
+
+    /// Create a stop (stop-market) order; `stop_price` is held in `price`
+    pub fn new_stop(
+        symbol: String,
+        side: Side,
+        stop_price: Price,
+        quantity: Quantity,
+        client_id: Option<String>,
+    ) -> Self {
+        Self {
+            order_type: OrderType::Stop,
+            ..Self::new_limit(symbol, side, stop_price, quantity, client_id)
+        }
+    }
+
+    /// Create a stop-limit order that becomes a limit order at `limit_price`
+    pub fn new_stop_limit(
+        symbol: String,
+        side: Side,
+        stop_price: Price,
+        limit_price: Price,
+        quantity: Quantity,
+        client_id: Option<String>,
+    ) -> Self {
+        Self {
+            order_type: OrderType::StopLimit { stop_price },
+            ..Self::new_limit(symbol, side, limit_price, quantity, client_id)
+        }
+    }
+
+    pub fn with_stp_group(mut self, stp_group: impl Into<String>) -> Self {
+        self.stp_group = Some(stp_group.into());
+        self
+    }
+
+    pub fn with_self_trade_prevention(mut self, mode: SelfTradePrevention) -> Self {
+        self.stp_mode = Some(mode);
+        self
+    }
+
+    pub fn with_post_only(mut self, post_only: PostOnly) -> Self {
+        self.post_only = Some(post_only);
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    /// Make this an iceberg order that shows at most `display_quantity` at a
+    /// time and holds the rest in reserve
+    pub fn with_display_quantity(mut self, display_quantity: Quantity) -> Self {
+        self.display_quantity = Some(display_quantity);
+        self.reset_display();
+        self
+    }
+
+    pub fn is_iceberg(&self) -> bool {
+        self.display_quantity.is_some()
+    }
+
+    /// Get the open quantity shown in market data and matchable at the
+    /// order's queue position
+    pub fn visible_quantity(&self) -> Quantity {
+        self.remaining_quantity - self.hidden_quantity
+    }
+
+    /// Show a full display slice, holding the rest of the open quantity in
+    /// reserve
+    pub fn reset_display(&mut self) {
+        if let Some(display_quantity) = self.display_quantity {
+            self.hidden_quantity =
+                self.remaining_quantity - display_quantity.min(self.remaining_quantity);
+        }
+    }
+
+    /// Replace an exhausted display slice from the reserve
+    ///
+    /// Returns `true` if the order was refreshed; a refreshed order loses
+    /// its time priority.
+    pub fn refresh_display(&mut self) -> bool {
+        if self.visible_quantity() > 0 || self.hidden_quantity == 0 {
+            return false;
+        }
+        self.reset_display();
+        true
+    }
+
+    /// Change the open quantity, keeping an iceberg's visible slice where
+    /// possible and putting any increase in reserve
+    pub fn resize(&mut self, new_quantity: Quantity) {
+        let visible = if self.is_iceberg() {
+            self.visible_quantity().min(new_quantity)
+        } else {
+            new_quantity
+        };
+        self.remaining_quantity = new_quantity;
+        self.hidden_quantity = new_quantity - visible;
+    }
+
+    /// Get the order as public market data shows it, without its reserve
+    pub fn displayed(&self) -> Order {
+        Order {
+            remaining_quantity: self.visible_quantity(),
+            hidden_quantity: 0,
+            display_quantity: None,
+            ..self.clone()
+        }
+    }
+
+    /// Check whether the order's time in force has run out at `now`; DAY
+    /// orders only run out once the session has closed
+    pub fn is_expired(&self, now: DateTime<Utc>, session_closed: bool) -> bool {
+        match self.time_in_force {
+            TimeInForce::GoodTillCancel => false,
+            TimeInForce::Day => session_closed,
+            TimeInForce::GoodTillDate(expire_time) => expire_time <= now,
+        }
+    }
+
+    /// Get the key that identifies self-trades: the STP group if set,
+    /// otherwise the client id
+    pub fn stp_key(&self) -> Option<&str> {
+        self.stp_group.as_deref().or(self.client_id.as_deref())
+    }
+
+    /// Get the trigger price for stop and stop-limit orders
+    pub fn stop_price(&self) -> Option<Price> {
+        match self.order_type {
+            OrderType::Stop => Some(self.price),
+            OrderType::StopLimit { stop_price } => Some(stop_price),
+            _ => None,
+        }
+    }
+
+    /// Convert a triggered stop into the order it releases:
+    /// stop becomes market, stop-limit becomes limit
+    pub fn trigger(&mut self) {
+        match self.order_type {
+            OrderType::Stop => {
+                self.order_type = OrderType::Market;
+                self.price = 0;
+            }
+            OrderType::StopLimit { .. } => {
+                self.order_type = OrderType::Limit;
+            }
+            _ => {}
         }
     }
 
@@ -108,6 +403,8 @@ impl Order {
 
         self.remaining_quantity -= quantity;
         self.filled_quantity += quantity;
+        // Fills beyond the visible slice (as the aggressor) draw on the reserve
+        self.hidden_quantity = self.hidden_quantity.min(self.remaining_quantity);
 
         if self.remaining_quantity == 0 {
             self.status = OrderStatus::Filled;
@@ -122,6 +419,15 @@ impl Order {
         self.status = OrderStatus::Cancelled;
     }
 
+    pub fn expire(&mut self) {
+        self.status = OrderStatus::Expired;
+    }
+
+    /// Check that the order still has open quantity it may trade
+    pub fn is_active(&self) -> bool {
+        self.remaining_quantity > 0 && !self.is_complete()
+    }
+
     pub fn is_complete(&self) -> bool {
         matches!(
             self.status,
@@ -151,15 +457,36 @@ impl Trade {
         seller_order_id: OrderId,
         price: Price,
         quantity: Quantity,
+    ) -> Self {
+        Self::new_stamped(
+            Uuid::new_v4(),
+            Utc::now(),
+            symbol,
+            buyer_order_id,
+            seller_order_id,
+            price,
+            quantity,
+        )
+    }
+
+    /// Create a trade with a caller-supplied id and timestamp
+    pub fn new_stamped(
+        id: Uuid,
+        timestamp: DateTime<Utc>,
+        symbol: String,
+        buyer_order_id: OrderId,
+        seller_order_id: OrderId,
+        price: Price,
+        quantity: Quantity,
     ) -> Self {
         Self {
-            id: Uuid::new_v4(),
+            id,
             symbol,
             buyer_order_id,
             seller_order_id,
             price,
             quantity,
-            timestamp: Utc::now(),
+            timestamp,
         }
     }
 }
@@ -174,6 +501,8 @@ pub struct OrderLocation {
 pub struct BookSnapshot {
     pub symbol: String,
     pub timestamp: DateTime<Utc>,
+    /// Sequence number of the last depth update this snapshot reflects
+    pub sequence: u64,
     pub bids: Vec<PriceLevelInfo>,
     pub asks: Vec<PriceLevelInfo>,
     pub last_trade_price: Option<Price>,
@@ -196,6 +525,14 @@ pub enum MarketEvent {
         order_id: OrderId,
         remaining_quantity: Quantity,
     },
+    OrderExpired {
+        order_id: OrderId,
+        remaining_quantity: Quantity,
+    },
+    /// Incoming market order filled completely
+    OrderFilled {
+        order_id: OrderId,
+    },
     OrderModified {
         order_id: OrderId,
         new_price: Option<Price>,
@@ -204,9 +541,21 @@ pub enum MarketEvent {
     Trade {
         trade: Trade,
     },
+    StopTriggered {
+        order_id: OrderId,
+        stop_price: Price,
+        trigger_price: Price,
+    },
     BookSnapshot {
         snapshot: BookSnapshot,
     },
+    /// Continuous matching stopped; `phase` is `Halted` or `VolatilityAuction`
+    TradingHalted {
+        phase: TradingPhase,
+        reason: HaltReason,
+    },
+    /// Continuous matching resumed after a halt or volatility auction
+    TradingResumed,
 }
 
 #[cfg(test)]
@@ -247,6 +596,63 @@ mod tests {
         assert_eq!(order.status, OrderStatus::Filled);
     }
 
+    #[test]
+    fn test_stop_order_trigger() {
+        let mut stop = Order::new_stop("AAPL".to_string(), Side::Sell, 14900, 100, None);
+        assert_eq!(stop.stop_price(), Some(14900));
+        stop.trigger();
+        assert_eq!(stop.order_type, OrderType::Market);
+        assert_eq!(stop.stop_price(), None);
+
+        let mut stop_limit =
+            Order::new_stop_limit("AAPL".to_string(), Side::Buy, 15100, 15200, 100, None);
+        assert_eq!(stop_limit.stop_price(), Some(15100));
+        stop_limit.trigger();
+        assert_eq!(stop_limit.order_type, OrderType::Limit);
+        assert_eq!(stop_limit.price, 15200);
+    }
+
+    #[test]
+    fn test_time_in_force_expiry() {
+        let now = Utc::now();
+        let order = Order::new_limit("AAPL".to_string(), Side::Buy, 15000, 100, None);
+        assert!(!order.is_expired(now, true));
+
+        let day = order.clone().with_time_in_force(TimeInForce::Day);
+        assert!(!day.is_expired(now, false));
+        assert!(day.is_expired(now, true));
+
+        let gtd = order.with_time_in_force(TimeInForce::GoodTillDate(now));
+        assert!(!gtd.is_expired(now - chrono::Duration::seconds(1), false));
+        assert!(gtd.is_expired(now, false));
+    }
+
+    #[test]
+    fn test_iceberg_display() {
+        let mut order = Order::new_limit("AAPL".to_string(), Side::Sell, 15000, 250, None)
+            .with_display_quantity(100);
+        assert_eq!(order.visible_quantity(), 100);
+        assert_eq!(order.hidden_quantity, 150);
+
+        order.fill(100).unwrap();
+        assert_eq!(order.visible_quantity(), 0);
+        assert!(order.refresh_display());
+        assert_eq!(order.visible_quantity(), 100);
+        assert_eq!(order.displayed().remaining_quantity, 100);
+
+        order.resize(60);
+        assert_eq!((order.visible_quantity(), order.hidden_quantity), (60, 0));
+        order.resize(200);
+        assert_eq!((order.visible_quantity(), order.hidden_quantity), (60, 140));
+
+        // As the aggressor, an iceberg fills its slice and then its reserve
+        order.fill(180).unwrap();
+        assert_eq!((order.visible_quantity(), order.hidden_quantity), (0, 20));
+        assert!(order.refresh_display());
+        assert_eq!((order.visible_quantity(), order.hidden_quantity), (20, 0));
+        assert!(!order.refresh_display());
+    }
+
     #[test]
     fn test_overfill_error() {
         let mut order = Order::new_limit("AAPL".to_string(), Side::Buy, 15000, 100, None);
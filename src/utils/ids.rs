@@ -0,0 +1,66 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use uuid::Uuid;
+
+/// Source of order and trade identifiers for an order book
+///
+/// The default `RandomIds` issues v4 UUIDs; `SequentialIds` makes identifiers
+/// reproducible for replay and regression testing.
+pub trait IdGenerator: Send + Sync + Debug {
+    /// Get the next identifier
+    fn next_id(&self) -> Uuid;
+}
+
+/// Random (v4) UUID source
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// Deterministic UUID source: `seed` in the high 64 bits, a counter in the low
+#[derive(Debug)]
+pub struct SequentialIds {
+    seed: u64,
+    next: AtomicU64,
+}
+
+impl SequentialIds {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            next: AtomicU64::new(1),
+        }
+    }
+}
+
+impl Default for SequentialIds {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl IdGenerator for SequentialIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::from_u64_pair(self.seed, self.next.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential_ids() {
+        let ids = SequentialIds::new(7);
+        let first = ids.next_id();
+        let second = ids.next_id();
+
+        assert_eq!(first.as_u64_pair(), (7, 1));
+        assert_eq!(second.as_u64_pair(), (7, 2));
+        assert_eq!(SequentialIds::new(7).next_id(), first);
+    }
+}
@@ -1,5 +1,9 @@
+pub mod ids;
 pub mod time;
 
+pub use ids::{IdGenerator, RandomIds, SequentialIds};
+pub use time::{ClockSource, ManualClock, SystemClock};
+
 /// Convert price from ticks to human-readable format
 pub fn format_price(price_ticks: u64, tick_size: f64) -> String {
     format!("${:.2}", price_ticks as f64 * tick_size)
@@ -1,4 +1,6 @@
 use chrono::{DateTime, Utc};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// High-precision timestamp for latency measurements
@@ -99,6 +101,60 @@ impl Clock {
     }
 }
 
+/// Source of event timestamps for an order book
+///
+/// The default `SystemClock` reads wall-clock time; `ManualClock` makes
+/// timestamps reproducible for replay and regression testing.
+pub trait ClockSource: Send + Sync + Debug {
+    /// Get the current time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Wall-clock time source
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl ClockSource for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Deterministic time source that advances by a fixed step on every reading
+#[derive(Debug)]
+pub struct ManualClock {
+    nanos: AtomicI64,
+    step_nanos: i64,
+}
+
+impl ManualClock {
+    /// Create a clock that first reads `start`, then `start + step`, and so on
+    pub fn new(start: DateTime<Utc>, step: Duration) -> Self {
+        Self {
+            nanos: AtomicI64::new(start.timestamp_nanos_opt().unwrap_or_default()),
+            step_nanos: step.as_nanos() as i64,
+        }
+    }
+
+    /// Create a clock starting at the Unix epoch, advancing 1µs per reading
+    pub fn from_epoch() -> Self {
+        Self::new(DateTime::UNIX_EPOCH, Duration::from_micros(1))
+    }
+
+    /// Move the clock forward without taking a reading
+    pub fn advance(&self, by: Duration) {
+        self.nanos
+            .fetch_add(by.as_nanos() as i64, Ordering::Relaxed);
+    }
+}
+
+impl ClockSource for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        let nanos = self.nanos.fetch_add(self.step_nanos, Ordering::Relaxed);
+        DateTime::from_timestamp_nanos(nanos)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,4 +189,20 @@ mod tests {
         assert!(Clock::micros() > 0);
         assert!(Clock::millis() > 0);
     }
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::from_epoch();
+        let first = clock.now();
+        let second = clock.now();
+
+        assert_eq!(first, DateTime::UNIX_EPOCH);
+        assert_eq!(second - first, chrono::Duration::microseconds(1));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            clock.now() - second,
+            chrono::Duration::microseconds(1_000_001)
+        );
+    }
 }
@@ -0,0 +1,85 @@
+//! Scrapes the Prometheus endpoint. Installing the recorder is process-wide,
+//! so this lives in its own test binary.
+#![cfg(feature = "prometheus")]
+
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use orderbook_trading_engine::metrics::server::MetricsServer;
+use orderbook_trading_engine::{Order, OrderBook, OrderBookMetrics, Side};
+
+async fn get(addr: std::net::SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint() {
+    // Install before building the book's metrics so its series bind to it
+    let server = MetricsServer::install().unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server.run(listener));
+
+    let book = OrderBook::new("AAPL".to_string())
+        .with_metrics(Some(Arc::new(OrderBookMetrics::for_symbol("AAPL"))));
+    book.add_limit_order(Order::new_limit(
+        "AAPL".to_string(),
+        Side::Buy,
+        9_990,
+        100,
+        None,
+    ))
+    .unwrap();
+    book.add_limit_order(Order::new_limit(
+        "AAPL".to_string(),
+        Side::Sell,
+        10_010,
+        100,
+        None,
+    ))
+    .unwrap();
+
+    let response = get(addr, "/metrics").await;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    for line in [
+        "orderbook_orders_total{symbol=\"AAPL\",operation=\"add\"} 2",
+        "orderbook_levels_total{symbol=\"AAPL\",side=\"bid\"} 1",
+        "orderbook_levels_total{symbol=\"AAPL\",side=\"ask\"} 1",
+        "orderbook_orders_current{symbol=\"AAPL\"} 2",
+        "orderbook_spread_ticks{symbol=\"AAPL\"} 20",
+        "orderbook_best_bid{symbol=\"AAPL\"} 9990",
+        "orderbook_best_ask{symbol=\"AAPL\"} 10010",
+    ] {
+        assert!(
+            response.contains(line),
+            "missing `{}` in:\n{}",
+            line,
+            response
+        );
+    }
+    for name in [
+        "orderbook_orders_total",
+        "orderbook_trades_total",
+        "orderbook_volume_total",
+        "orderbook_notional_total",
+        "orderbook_operation_duration_seconds",
+    ] {
+        assert!(
+            response.contains(name),
+            "missing `{}` in:\n{}",
+            name,
+            response
+        );
+    }
+
+    assert!(get(addr, "/").await.starts_with("HTTP/1.1 404"));
+}